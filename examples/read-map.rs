@@ -11,10 +11,10 @@ fn main() -> anyhow::Result<()> {
 
 	for beatmap in mapinfo.maps {
 		println!("{} ({}):", beatmap.difficulty, beatmap.characteristic);
-		println!("\t{} notes", beatmap.map.beats.len());
-		for beat in beatmap.map.beats {
-			println!("\t{:?}", beat);
-		}
+		let stats = beatmap.map.stats();
+		println!("\t{} notes ({} red, {} blue)", stats.notes, stats.red_notes, stats.blue_notes);
+		println!("\t{} bombs, {} obstacles, {} chains, {} arcs", stats.bombs, stats.obstacles, stats.chains, beatmap.map.arcs.len());
+		println!("\t{:.2} NPS ({:.2} peak)", stats.nps, stats.peak_nps());
 	}
 
 	Ok(())
@@ -0,0 +1,5 @@
+mod parity;
+mod swings;
+
+pub use parity::{check_parity, Parity, ParityIssue, ParityIssueKind, SAME_SWING_WINDOW};
+pub use swings::{sps_curve, swings, swings_with, Swing, SwingOptions};
@@ -0,0 +1,228 @@
+use crate::schemas::{
+	beatmap::standard::{AnyNote, Beat, Beatmap, NoteColor, NoteDirection},
+	bsor::Hand
+};
+
+/// Notes of the same color closer together than this (in seconds) are treated as part of the same swing, e.g. stacks,
+/// sliders & windows.
+pub const SAME_SWING_WINDOW: f32 = 0.08;
+
+/// Which side of the hand leads a swing. Forehand swings are (roughly) downwards, and backhand swings upwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Parity {
+	Forehand,
+	Backhand
+}
+
+impl Parity {
+	pub fn opposite(&self) -> Self {
+		match self {
+			Self::Forehand => Self::Backhand,
+			Self::Backhand => Self::Forehand
+		}
+	}
+
+	/// The cut direction (see [`Beat::effective_cut_degrees`]) of a neutral swing with this parity.
+	fn neutral_degrees(&self) -> f32 {
+		match self {
+			Self::Forehand => 180.,
+			Self::Backhand => 0.
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParityIssueKind {
+	/// The note has to be swung with the same parity as the previous swing, requiring the hand to reset in between.
+	DoubleSwing,
+	/// The note can only be swung with the expected parity by bending the wrist outwards.
+	BrokenWrist
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParityIssue {
+	pub time: f32,
+	pub hand: Hand,
+	pub kind: ParityIssueKind
+}
+
+enum Fit {
+	Comfortable,
+	BrokenWrist,
+	Opposite
+}
+
+struct HandState {
+	parity: Parity,
+	time: f32,
+	x: f32,
+	y: f32,
+	/// Whether a bomb has already reset the hand since its last swing.
+	reset: bool
+}
+
+/// Tracks the expected forehand/backhand parity of each hand through the map, flagging notes which break it.
+///
+/// Dot notes take whichever parity is expected. A bomb in the column of a hand's last swing, on the side the saber
+/// ends up after swinging (below after a forehand, above after a backhand), forces the hand to reset, so the next
+/// swing is expected to have the same parity as the last one. Fake notes are ignored.
+pub fn check_parity(map: &Beatmap) -> Vec<ParityIssue> {
+	let mut hands: [Option<HandState>; 2] = [None, None];
+	let mut issues = Vec::new();
+	for note in map.iter_notes() {
+		match note {
+			AnyNote::Beat(beat) if !beat.fake => {
+				let state = &mut hands[beat.color as usize];
+				if let Some(state) = state.as_mut().filter(|s| beat.time - s.time < SAME_SWING_WINDOW) {
+					state.time = beat.time;
+					continue;
+				}
+
+				let expected = match state {
+					Some(state) => state.parity.opposite(),
+					None => match fit(beat, Parity::Forehand) {
+						Fit::Comfortable => Parity::Forehand,
+						_ => Parity::Backhand
+					}
+				};
+				let mut issue = |kind| {
+					issues.push(ParityIssue {
+						time: beat.time,
+						hand: hand(beat.color),
+						kind
+					})
+				};
+				let parity = match fit(beat, expected) {
+					Fit::Comfortable => expected,
+					Fit::BrokenWrist => {
+						issue(ParityIssueKind::BrokenWrist);
+						expected
+					}
+					Fit::Opposite => {
+						issue(ParityIssueKind::DoubleSwing);
+						expected.opposite()
+					}
+				};
+				*state = Some(HandState {
+					parity,
+					time: beat.time,
+					x: beat.x,
+					y: beat.y,
+					reset: false
+				});
+			}
+			AnyNote::Bomb(bomb) if !bomb.fake => {
+				for state in hands.iter_mut().flatten() {
+					let in_path = match state.parity {
+						Parity::Forehand => bomb.y <= state.y,
+						Parity::Backhand => bomb.y >= state.y
+					};
+					if !state.reset && bomb.time > state.time && bomb.x == state.x && in_path {
+						state.parity = state.parity.opposite();
+						state.reset = true;
+					}
+				}
+			}
+			_ => {}
+		}
+	}
+	issues
+}
+
+fn hand(color: NoteColor) -> Hand {
+	match color {
+		NoteColor::Red => Hand::Left,
+		NoteColor::Blue => Hand::Right
+	}
+}
+
+/// Returns how well the note can be swung with the given parity.
+fn fit(beat: &Beat, parity: Parity) -> Fit {
+	// dots can be cut from any direction, even when their angle offset rotates them
+	if beat.direction == NoteDirection::Any {
+		return Fit::Comfortable;
+	}
+	let Some(degrees) = beat.effective_cut_degrees() else {
+		return Fit::Comfortable;
+	};
+	// the note's rotation from a neutral swing, positive when rotated outwards for the right hand
+	let mut rotation = (parity.neutral_degrees() - degrees).rem_euclid(360.);
+	if rotation > 180. {
+		rotation -= 360.;
+	}
+	if beat.color == NoteColor::Red {
+		rotation = -rotation;
+	}
+
+	match rotation {
+		r if (-90. ..=45.).contains(&r) => Fit::Comfortable,
+		r if (45. ..=90.).contains(&r) => Fit::BrokenWrist,
+		_ => Fit::Opposite
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::schemas::beatmap::v3;
+
+	/// Builds a map at 60 BPM (so times equal beats) from `(beat, x, y, color, direction)` notes and `(beat, x, y)`
+	/// bombs.
+	fn map(notes: &[(f32, u8, u8, u8, u8)], bombs: &[(f32, u8, u8)]) -> Beatmap {
+		let notes: Vec<_> = notes
+			.iter()
+			.map(|(b, x, y, c, d)| format!(r#"{{ "b": {b}, "x": {x}, "y": {y}, "c": {c}, "d": {d} }}"#))
+			.collect();
+		let bombs: Vec<_> = bombs.iter().map(|(b, x, y)| format!(r#"{{ "b": {b}, "x": {x}, "y": {y} }}"#)).collect();
+		let json = format!(
+			r#"{{ "version": "3.2.0", "colorNotes": [{}], "bombNotes": [{}], "obstacles": [], "burstSliders": [], "bpmEvents": [] }}"#,
+			notes.join(","),
+			bombs.join(",")
+		);
+		Beatmap::from_v3(v3::Beatmap::from_string(json).unwrap(), 60.).unwrap()
+	}
+
+	fn kinds(map: &Beatmap) -> Vec<(f32, Hand, ParityIssueKind)> {
+		check_parity(map).into_iter().map(|i| (i.time, i.hand, i.kind)).collect()
+	}
+
+	#[test]
+	fn test_alternating_is_clean() {
+		// down, up, dot, up for each hand, with a 2-note stack on the first swing
+		let notes = [
+			(1., 2, 0, 1, 1),
+			(1., 2, 1, 1, 1),
+			(2., 2, 2, 1, 0),
+			(3., 2, 1, 1, 8),
+			(4., 2, 2, 1, 0),
+			(1., 1, 0, 0, 1),
+			(2., 1, 2, 0, 0)
+		];
+		assert!(check_parity(&map(&notes, &[])).is_empty());
+	}
+
+	#[test]
+	fn test_double_swing() {
+		let map = map(&[(1., 2, 0, 1, 1), (2., 2, 0, 1, 1), (3., 1, 2, 0, 0), (4., 1, 2, 0, 4)], &[]);
+		assert_eq!(kinds(&map), [(2., Hand::Right, ParityIssueKind::DoubleSwing), (4., Hand::Left, ParityIssueKind::DoubleSwing)]);
+	}
+
+	#[test]
+	fn test_broken_wrist() {
+		// a left cut is an outward backhand for the right hand, and a right cut is for the left hand
+		let map = map(&[(1., 2, 1, 1, 1), (2., 2, 1, 1, 2), (1., 1, 1, 0, 1), (2., 1, 1, 0, 3)], &[]);
+		assert_eq!(kinds(&map), [(2., Hand::Right, ParityIssueKind::BrokenWrist), (2., Hand::Left, ParityIssueKind::BrokenWrist)]);
+
+		// the opposite horizontal cuts are comfortable
+		let map = self::map(&[(1., 2, 1, 1, 1), (2., 2, 1, 1, 3), (1., 1, 1, 0, 1), (2., 1, 1, 0, 2)], &[]);
+		assert!(check_parity(&map).is_empty());
+	}
+
+	#[test]
+	fn test_bomb_reset() {
+		let notes = [(1., 3, 1, 1, 1), (3., 3, 1, 1, 1)];
+		assert!(check_parity(&map(&notes, &[(2., 3, 0)])).is_empty());
+		// bombs outside of the saber's path don't reset it
+		assert_eq!(kinds(&map(&notes, &[(2., 3, 2), (2., 2, 0)])), [(3., Hand::Right, ParityIssueKind::DoubleSwing)]);
+	}
+}
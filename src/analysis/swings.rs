@@ -0,0 +1,162 @@
+use super::SAME_SWING_WINDOW;
+use crate::schemas::beatmap::standard::{Beatmap, NoteColor};
+
+/// Thresholds for grouping notes into swings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SwingOptions {
+	/// The largest time in seconds between consecutive notes of the same swing.
+	pub time_window: f32,
+	/// The largest grid distance between consecutive notes of the same swing.
+	pub max_distance: f32
+}
+
+impl Default for SwingOptions {
+	fn default() -> Self {
+		Self {
+			time_window: SAME_SWING_WINDOW,
+			max_distance: 2.
+		}
+	}
+}
+
+/// A single swing of one saber, which may cut several notes (e.g. a stack, slider or chain).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Swing {
+	pub color: NoteColor,
+	/// The time of the first note in the swing.
+	pub time: f32,
+	/// The time of the last note in the swing.
+	pub end_time: f32,
+	/// The number of notes cut by the swing, not counting chain links.
+	pub notes: usize,
+	/// The number of chain links cut by the swing.
+	pub links: usize
+}
+
+/// Groups the map's notes into swings using the default [`SwingOptions`].
+pub fn swings(map: &Beatmap) -> Vec<Swing> {
+	swings_with(map, SwingOptions::default())
+}
+
+/// Groups the map's notes into swings, ordered by time. Consecutive notes of the same color are part of the same swing
+/// if they're within both thresholds of `options`. Fake notes are ignored.
+pub fn swings_with(map: &Beatmap, options: SwingOptions) -> Vec<Swing> {
+	let mut swings = Vec::new();
+	// the index of the current swing for each color, and the position of its last note
+	let mut current: [Option<(usize, f32, f32)>; 2] = [None, None];
+	for beat in map.beats.iter().filter(|b| !b.fake) {
+		let last = &mut current[beat.color as usize];
+		if let Some((i, x, y)) = last {
+			let swing: &mut Swing = &mut swings[*i];
+			if beat.time - swing.end_time <= options.time_window && (beat.x - *x).hypot(beat.y - *y) <= options.max_distance {
+				swing.end_time = beat.time;
+				swing.notes += 1;
+				(*x, *y) = (beat.x, beat.y);
+				continue;
+			}
+		}
+		*last = Some((swings.len(), beat.x, beat.y));
+		swings.push(Swing {
+			color: beat.color,
+			time: beat.time,
+			end_time: beat.time,
+			notes: 1,
+			links: 0
+		});
+	}
+
+	// chain heads are regular notes, so the links join the swing which cuts the head
+	for chain in map.chains.iter().filter(|c| !c.fake) {
+		let links = chain.num_slices.saturating_sub(1) as usize;
+		match swings
+			.iter_mut()
+			.find(|s| s.color == chain.color && s.time <= chain.time && chain.time <= s.end_time)
+		{
+			Some(swing) => {
+				swing.links += links;
+				swing.end_time = swing.end_time.max(chain.tail_time);
+			}
+			None => swings.push(Swing {
+				color: chain.color,
+				time: chain.time,
+				end_time: chain.tail_time,
+				notes: 0,
+				links
+			})
+		}
+	}
+	swings.sort_by(|a, b| a.time.total_cmp(&b.time));
+	swings
+}
+
+/// Returns the swings per second in consecutive windows of `window` seconds from the start of the song, as
+/// `(start time, sps)` pairs. Returns an empty curve for a map without swings or a non-positive window.
+pub fn sps_curve(map: &Beatmap, window: f32) -> Vec<(f32, f32)> {
+	let swings = swings(map);
+	let Some(last) = swings.last() else {
+		return Vec::new();
+	};
+	if window <= 0. {
+		return Vec::new();
+	}
+
+	let mut curve: Vec<_> = (0..=(last.time.max(0.) / window) as usize).map(|i| (i as f32 * window, 0.)).collect();
+	for swing in &swings {
+		curve[(swing.time.max(0.) / window) as usize].1 += 1. / window;
+	}
+	curve
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::schemas::beatmap::v3;
+
+	fn map(notes: &str, chains: &str) -> Beatmap {
+		let json =
+			format!(r#"{{ "version": "3.2.0", "colorNotes": [{notes}], "bombNotes": [], "obstacles": [], "burstSliders": [{chains}], "bpmEvents": [] }}"#);
+		Beatmap::from_v3(v3::Beatmap::from_string(json).unwrap(), 60.).unwrap()
+	}
+
+	#[test]
+	fn test_stack_and_tower() {
+		// a 2-wide stack, then a 3-high tower, then a lone note
+		let map = map(
+			r#"{ "b": 1, "x": 2, "y": 0, "c": 1, "d": 1 }, { "b": 1, "x": 3, "y": 0, "c": 1, "d": 1 },
+			{ "b": 2, "x": 3, "y": 0, "c": 1, "d": 0 }, { "b": 2.03, "x": 3, "y": 1, "c": 1, "d": 0 }, { "b": 2.06, "x": 3, "y": 2, "c": 1, "d": 0 },
+			{ "b": 3, "x": 0, "y": 0, "c": 0, "d": 1 }"#,
+			""
+		);
+		let swings = swings(&map);
+		assert_eq!(swings.iter().map(|s| (s.time, s.notes)).collect::<Vec<_>>(), [(1., 2), (2., 3), (3., 1)]);
+		assert_eq!(swings[1].end_time, 2.06);
+
+		// with a tighter window the tower is split up
+		let options = SwingOptions {
+			time_window: 0.01,
+			..Default::default()
+		};
+		assert_eq!(swings_with(&map, options).len(), 5);
+	}
+
+	#[test]
+	fn test_chain_links() {
+		let map = map(
+			r#"{ "b": 1, "x": 1, "y": 2, "c": 0, "d": 1 }"#,
+			r#"{ "b": 1, "x": 1, "y": 2, "c": 0, "d": 1, "tb": 1.25, "tx": 1, "ty": 0, "sc": 4, "s": 1 }"#
+		);
+		let swings = swings(&map);
+		assert_eq!(swings.len(), 1);
+		assert_eq!((swings[0].notes, swings[0].links, swings[0].end_time), (1, 3, 1.25));
+	}
+
+	#[test]
+	fn test_sps_curve() {
+		let map = map(
+			r#"{ "b": 0.5, "x": 1, "y": 0, "c": 0, "d": 1 }, { "b": 0.5, "x": 2, "y": 0, "c": 1, "d": 1 }, { "b": 2.5, "x": 1, "y": 0, "c": 0, "d": 0 }"#,
+			""
+		);
+		assert_eq!(sps_curve(&map, 1.), [(0., 2.), (1., 0.), (2., 1.)]);
+		assert!(sps_curve(&map, 0.).is_empty());
+	}
+}
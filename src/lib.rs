@@ -1,2 +1,3 @@
+pub mod analysis;
 pub mod schemas;
-mod util;
+pub mod util;
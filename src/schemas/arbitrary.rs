@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+
+use glam::{Quat, Vec3};
+use proptest::{arbitrary::Arbitrary, collection::vec, option, prelude::*, sample::select, strategy::BoxedStrategy};
+
+use super::{
+	beatmap::{v2, v3},
+	bsor::ReplayFrame,
+	mapinfo
+};
+
+// The generated values are limited to what each format can represent exactly, e.g. whole grid positions (fractional
+// ones go through the lossy mapping extensions encoding) and beats on a 1/16 grid, so that round trips are lossless.
+
+fn beat() -> impl Strategy<Value = f32> {
+	(0..16_000u32).prop_map(|x| x as f32 / 16.)
+}
+
+fn lane() -> impl Strategy<Value = f32> {
+	(0..4i32).prop_map(|x| x as f32)
+}
+
+fn layer() -> impl Strategy<Value = f32> {
+	(0..3i32).prop_map(|x| x as f32)
+}
+
+fn bpm() -> impl Strategy<Value = f32> {
+	(120..600u32).prop_map(|x| x as f32 / 2.)
+}
+
+fn name() -> impl Strategy<Value = String> {
+	"[a-zA-Z0-9 ]{0,16}"
+}
+
+fn v3_direction() -> impl Strategy<Value = v3::NoteDirection> {
+	use v3::NoteDirection::*;
+	prop_oneof![select(vec![Up, Down, Left, Right, UpLeft, UpRight, DownLeft, DownRight, Any]), (1000..=1360u16).prop_map(Precise)]
+}
+
+fn v3_color() -> impl Strategy<Value = v3::NoteColor> {
+	select(vec![v3::NoteColor::Red, v3::NoteColor::Blue])
+}
+
+fn v3_color_note() -> impl Strategy<Value = v3::ColorNote> {
+	(beat(), lane(), layer(), option::of((-180..180i32).prop_map(|x| x as f32)), v3_color(), v3_direction()).prop_map(
+		|(beat, x, y, angle_offset, color, direction)| v3::ColorNote {
+			beat,
+			x,
+			y,
+			angle_offset,
+			color,
+			direction,
+			extra: HashMap::new()
+		}
+	)
+}
+
+fn v3_bomb_note() -> impl Strategy<Value = v3::BombNote> {
+	(beat(), lane(), layer()).prop_map(|(beat, x, y)| v3::BombNote { beat, x, y, extra: HashMap::new() })
+}
+
+fn v3_obstacle() -> impl Strategy<Value = v3::Obstacle> {
+	(beat(), lane(), layer(), beat(), 1..=4i32, 1..=5i32).prop_map(|(beat, x, y, duration, width, height)| v3::Obstacle {
+		beat,
+		x,
+		y,
+		duration,
+		width: width as f32,
+		height: height as f32,
+		extra: HashMap::new()
+	})
+}
+
+fn v3_burst_slider() -> impl Strategy<Value = v3::BurstSlider> {
+	(beat(), lane(), layer(), v3_color(), v3_direction(), beat(), lane(), layer(), 2..32u8, (1..=8u32).prop_map(|x| x as f32 / 8.)).prop_map(
+		|(beat, x, y, color, direction, tail_beat, tail_x, tail_y, num_slices, squish_amount)| v3::BurstSlider {
+			beat,
+			x,
+			y,
+			color,
+			direction,
+			tail_beat,
+			tail_x,
+			tail_y,
+			num_slices,
+			squish_amount,
+			extra: HashMap::new()
+		}
+	)
+}
+
+fn v3_slider() -> impl Strategy<Value = v3::Slider> {
+	let multiplier = || (0..=16u32).prop_map(|x| x as f32 / 4.);
+	let mid_anchor_mode = select(vec![v3::MidAnchorMode::Straight, v3::MidAnchorMode::Clockwise, v3::MidAnchorMode::CounterClockwise]);
+	(
+		(beat(), lane(), layer(), v3_color(), v3_direction(), multiplier()),
+		(beat(), lane(), layer(), v3_direction(), multiplier(), mid_anchor_mode)
+	)
+		.prop_map(|((beat, x, y, color, direction, head_multiplier), (tail_beat, tail_x, tail_y, tail_direction, tail_multiplier, mid_anchor_mode))| {
+			v3::Slider {
+				beat,
+				x,
+				y,
+				color,
+				direction,
+				head_multiplier,
+				tail_beat,
+				tail_x,
+				tail_y,
+				tail_direction,
+				tail_multiplier,
+				mid_anchor_mode,
+				extra: HashMap::new()
+			}
+		})
+}
+
+fn v3_bpm_event() -> impl Strategy<Value = v3::BpmEvent> {
+	(beat(), bpm()).prop_map(|(song_time, beats)| v3::BpmEvent {
+		song_time,
+		beats,
+		extra: HashMap::new()
+	})
+}
+
+impl Arbitrary for v3::Beatmap {
+	type Parameters = ();
+	type Strategy = BoxedStrategy<Self>;
+
+	fn arbitrary_with(_: ()) -> Self::Strategy {
+		(
+			vec(v3_color_note(), 0..16),
+			vec(v3_bomb_note(), 0..8),
+			vec(v3_obstacle(), 0..8),
+			vec(v3_burst_slider(), 0..4),
+			vec(v3_slider(), 0..4),
+			vec(v3_bpm_event(), 0..4)
+		)
+			.prop_map(|(color_notes, bomb_notes, obstacles, burst_sliders, sliders, bpm_events)| v3::Beatmap {
+				version: String::from("3.3.0"),
+				color_notes,
+				bomb_notes,
+				obstacles,
+				burst_sliders,
+				sliders,
+				bpm_events,
+				..Default::default()
+			})
+			.boxed()
+	}
+}
+
+fn v2_note() -> impl Strategy<Value = v2::Note> {
+	use v2::NoteDirection::*;
+	let note_type = select(vec![v2::NoteType::Red, v2::NoteType::Blue, v2::NoteType::Bomb]);
+	let direction = prop_oneof![select(vec![Up, Down, Left, Right, UpLeft, UpRight, DownLeft, DownRight, Any]), (1000..=1360u16).prop_map(Precise)];
+	(beat(), lane(), layer(), note_type, direction).prop_map(|(beat, x, y, note_type, direction)| v2::Note {
+		beat,
+		x,
+		y,
+		note_type,
+		direction,
+		angle_offset: None,
+		custom_data: None,
+		extra: HashMap::new()
+	})
+}
+
+fn v2_obstacle() -> impl Strategy<Value = v2::Obstacle> {
+	(beat(), 0..=1u32, lane(), beat(), 1..=4i32).prop_map(|(beat, wall_type, x, duration, width)| v2::Obstacle {
+		beat,
+		wall_type,
+		x,
+		duration,
+		width: width as f32,
+		custom_data: None,
+		extra: HashMap::new()
+	})
+}
+
+fn v2_event() -> impl Strategy<Value = v2::Event> {
+	(beat(), 0..16i32, 0..8i32, option::of((0..=8u32).prop_map(|x| x as f32 / 4.))).prop_map(|(beat, event_type, value, float_value)| v2::Event {
+		beat,
+		event_type,
+		value,
+		float_value,
+		custom_data: None,
+		extra: HashMap::new()
+	})
+}
+
+fn v2_bpm_event() -> impl Strategy<Value = v2::BpmEvent> {
+	(beat(), bpm()).prop_map(|(song_time, beats)| v2::BpmEvent {
+		song_time,
+		beats,
+		extra: HashMap::new()
+	})
+}
+
+impl Arbitrary for v2::Beatmap {
+	type Parameters = ();
+	type Strategy = BoxedStrategy<Self>;
+
+	fn arbitrary_with(_: ()) -> Self::Strategy {
+		(vec(v2_note(), 0..16), vec(v2_obstacle(), 0..8), vec(v2_event(), 0..8), vec(v2_bpm_event(), 0..4))
+			.prop_map(|(notes, obstacles, events, bpm_events)| v2::Beatmap {
+				version: String::from("2.6.0"),
+				notes,
+				obstacles,
+				events,
+				bpm_events,
+				extra: HashMap::new()
+			})
+			.boxed()
+	}
+}
+
+fn difficulty() -> impl Strategy<Value = mapinfo::v2::Beatmap> {
+	use mapinfo::v2::DifficultyRank;
+	let ranks = vec![
+		("Easy", DifficultyRank::Easy),
+		("Normal", DifficultyRank::Normal),
+		("Hard", DifficultyRank::Hard),
+		("Expert", DifficultyRank::Expert),
+		("ExpertPlus", DifficultyRank::ExpertPlus),
+	];
+	(select(ranks), name(), (20..=60u32).prop_map(|x| x as f32 / 2.), (-8..=8i32).prop_map(|x| x as f32 / 4.)).prop_map(
+		|((difficulty, difficulty_rank), filename, njs, njs_offset)| mapinfo::v2::Beatmap {
+			difficulty: String::from(difficulty),
+			difficulty_rank,
+			filename,
+			njs,
+			njs_offset,
+			custom_data: None
+		}
+	)
+}
+
+impl Arbitrary for mapinfo::v2::MapInfo {
+	type Parameters = ();
+	type Strategy = BoxedStrategy<Self>;
+
+	fn arbitrary_with(_: ()) -> Self::Strategy {
+		let seconds = || (0..4800u32).prop_map(|x| x as f32 / 8.);
+		let sets = vec(
+			(select(vec!["Standard", "NoArrows", "OneSaber", "360Degree", "Lawless"]), vec(difficulty(), 1..5)).prop_map(|(characteristic, beatmaps)| {
+				mapinfo::v2::BeatmapSet {
+					characteristic: String::from(characteristic),
+					beatmaps
+				}
+			}),
+			0..4
+		);
+		((name(), name(), name(), name(), bpm()), (seconds(), seconds(), name(), name(), seconds()), sets)
+			.prop_map(
+				|(
+					(song_name, song_sub_name, song_author_name, level_author_name, bpm),
+					(preview_start_time, preview_duration, song_filename, cover_image_filename, song_time_offset),
+					beatmap_sets
+				)| mapinfo::v2::MapInfo {
+					version: String::from("2.1.0"),
+					song_name,
+					song_sub_name,
+					song_author_name,
+					level_author_name,
+					bpm,
+					shuffle: 0.,
+					shuffle_period: 0.5,
+					preview_start_time,
+					preview_duration,
+					song_filename,
+					cover_image_filename,
+					environment_name: String::from("DefaultEnvironment"),
+					song_time_offset,
+					beatmap_sets,
+					custom_data: None
+				}
+			)
+			.boxed()
+	}
+}
+
+fn pose() -> impl Strategy<Value = (Vec3, Quat)> {
+	let position = || -10f32..10f32;
+	let rotation = || -1f32..=1f32;
+	(position(), position(), position(), rotation(), rotation(), rotation(), rotation())
+		.prop_map(|(x, y, z, qx, qy, qz, qw)| (Vec3::new(x, y, z), Quat::from_xyzw(qx, qy, qz, qw)))
+}
+
+impl Arbitrary for ReplayFrame {
+	type Parameters = ();
+	type Strategy = BoxedStrategy<Self>;
+
+	fn arbitrary_with(_: ()) -> Self::Strategy {
+		(0f32..1000f32, 0..240i32, pose(), pose(), pose())
+			.prop_map(|(time, fps, head, left_hand, right_hand)| ReplayFrame {
+				time,
+				fps,
+				head,
+				left_hand,
+				right_hand
+			})
+			.boxed()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use proptest::prelude::*;
+
+	use crate::schemas::{
+		beatmap::{v2, v3},
+		bsor::ReplayFrame,
+		mapinfo
+	};
+
+	proptest! {
+		#[test]
+		fn v3_beatmap_round_trip(map: v3::Beatmap) {
+			let bytes = map.serialize_to_bytes(false).unwrap();
+			prop_assert_eq!(v3::Beatmap::from_reader(bytes.as_slice()).unwrap(), map);
+		}
+
+		#[test]
+		fn v2_beatmap_round_trip(map: v2::Beatmap) {
+			let bytes = map.serialize_to_bytes(false).unwrap();
+			prop_assert_eq!(v2::Beatmap::from_reader(bytes.as_slice()).unwrap(), map);
+		}
+
+		#[test]
+		fn v2_map_info_round_trip(info: mapinfo::v2::MapInfo) {
+			let bytes = info.serialize_to_bytes(false).unwrap();
+			prop_assert_eq!(mapinfo::v2::MapInfo::from_reader(bytes.as_slice()).unwrap(), info);
+		}
+
+		#[test]
+		fn replay_frame_round_trip(frame: ReplayFrame) {
+			let bytes = frame.serialize_to_vector();
+			prop_assert_eq!(ReplayFrame::from_reader(&mut bytes.as_slice()).unwrap(), frame);
+		}
+	}
+}
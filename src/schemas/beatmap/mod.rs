@@ -1,10 +1,11 @@
 use std::{
 	fs::File,
-	io::{BufReader, Read, Write},
-	path::Path
+	io::{self, BufReader, Read, Write},
+	path::{Path, PathBuf},
+	str::FromStr
 };
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use simd_json::{
 	derived::{ValueObjectAccess, ValueTryAsScalar},
 	OwnedValue
@@ -12,14 +13,24 @@ use simd_json::{
 use thiserror::Error;
 
 pub mod standard;
-mod util;
+pub mod util;
 pub mod v2;
 pub mod v3;
+pub mod v4;
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
 pub enum AnyverBeatmap {
 	V2(v2::Beatmap),
-	V3(v3::Beatmap)
+	V3(v3::Beatmap),
+	V4(v4::Beatmap)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SchemaVersion {
+	V2,
+	V3,
+	V4
 }
 
 impl From<v2::Beatmap> for AnyverBeatmap {
@@ -32,6 +43,11 @@ impl From<v3::Beatmap> for AnyverBeatmap {
 		Self::V3(value)
 	}
 }
+impl From<v4::Beatmap> for AnyverBeatmap {
+	fn from(value: v4::Beatmap) -> Self {
+		Self::V4(value)
+	}
+}
 
 impl TryInto<v2::Beatmap> for AnyverBeatmap {
 	type Error = Self;
@@ -55,44 +71,103 @@ impl TryInto<v3::Beatmap> for AnyverBeatmap {
 	}
 }
 
+impl TryInto<v4::Beatmap> for AnyverBeatmap {
+	type Error = Self;
+
+	fn try_into(self) -> Result<v4::Beatmap, Self::Error> {
+		match self {
+			AnyverBeatmap::V4(v4) => Ok(v4),
+			v => Err(v)
+		}
+	}
+}
+
 #[derive(Debug, Error)]
 pub enum AnyverParseError {
 	#[error("Failed to read file: {0}")]
 	IoError(#[from] std::io::Error),
+	#[error("Failed to read file '{}': {source}", .path.display())]
+	IoErrorWithPath { source: io::Error, path: PathBuf },
 	#[error("Failed to deserialize JSON: {0}")]
 	SimdJson(#[from] simd_json::Error),
 	#[error("Malformed JSON; expected field to be {}, got {}", .0.expected, .0.got)]
 	ExpectedType(#[from] simd_json::TryTypeError),
 	#[error("Unsupported map version type: {0}")]
-	UnsupportedVersion(String)
+	UnsupportedVersion(String),
+	#[error("Failed to convert beatmap: {0}")]
+	Conversion(#[from] standard::ConversionError)
+}
+
+impl AnyverParseError {
+	/// Wraps an I/O error with the path of the file that failed to be read.
+	pub fn from_io(source: io::Error, path: impl Into<PathBuf>) -> Self {
+		Self::IoErrorWithPath { source, path: path.into() }
+	}
 }
 
 impl AnyverBeatmap {
+	/// Returns the version string embedded in the beatmap, e.g. `"3.2.0"`.
+	pub fn version(&self) -> &str {
+		match self {
+			Self::V2(b) => &b.version,
+			Self::V3(b) => &b.version,
+			Self::V4(b) => &b.version
+		}
+	}
+
+	pub fn schema(&self) -> SchemaVersion {
+		match self {
+			Self::V2(_) => SchemaVersion::V2,
+			Self::V3(_) => SchemaVersion::V3,
+			Self::V4(_) => SchemaVersion::V4
+		}
+	}
+
+	/// Converts the beatmap to the schema with the given major version without going through [`standard::Beatmap`], so
+	/// custom data & unknown fields are kept. A beatmap which is already in that version is returned unchanged. Only
+	/// conversions between v2 & v3 are supported, and a v3 beatmap with chains, arcs, waypoints or lighting event boxes
+	/// fails with [`standard::ConversionError::LossyConversion`] rather than losing them in v2.
+	pub fn into_version(self, major: u8) -> Result<Self, standard::ConversionError> {
+		match (self, major) {
+			(map @ Self::V2(_), 2) | (map @ Self::V3(_), 3) | (map @ Self::V4(_), 4) => Ok(map),
+			(Self::V2(map), 3) => Ok(Self::V3(map.into())),
+			(Self::V3(map), 2) => match map.v2_unrepresentable() {
+				Some(dropped) => Err(standard::ConversionError::LossyConversion { to: 2, dropped }),
+				None => Ok(Self::V2(map.into()))
+			},
+			(map, to) => Err(standard::ConversionError::UnsupportedVersion { from: map.schema(), to })
+		}
+	}
+
 	pub fn serialize_to_string(&self, readable: bool) -> simd_json::Result<String> {
 		match self {
 			Self::V2(b) => b.serialize_to_string(readable),
-			Self::V3(b) => b.serialize_to_string(readable)
+			Self::V3(b) => b.serialize_to_string(readable),
+			Self::V4(b) => b.serialize_to_string(readable)
 		}
 	}
 
 	pub fn serialize_to_writer<W: Write>(&self, writer: W, readable: bool) -> simd_json::Result<()> {
 		match self {
 			Self::V2(b) => b.serialize_to_writer(writer, readable),
-			Self::V3(b) => b.serialize_to_writer(writer, readable)
+			Self::V3(b) => b.serialize_to_writer(writer, readable),
+			Self::V4(b) => b.serialize_to_writer(writer, readable)
 		}
 	}
 
 	pub fn serialize_to_file<P: AsRef<Path>>(&self, path: P, readable: bool) -> simd_json::Result<()> {
 		match self {
 			Self::V2(b) => b.serialize_to_file(path, readable),
-			Self::V3(b) => b.serialize_to_file(path, readable)
+			Self::V3(b) => b.serialize_to_file(path, readable),
+			Self::V4(b) => b.serialize_to_file(path, readable)
 		}
 	}
 
 	pub fn serialize_to_bytes(&self, readable: bool) -> simd_json::Result<Vec<u8>> {
 		match self {
 			Self::V2(b) => b.serialize_to_bytes(readable),
-			Self::V3(b) => b.serialize_to_bytes(readable)
+			Self::V3(b) => b.serialize_to_bytes(readable),
+			Self::V4(b) => b.serialize_to_bytes(readable)
 		}
 	}
 
@@ -104,26 +179,516 @@ impl AnyverBeatmap {
 		Self::inner_parse(simd_json::from_reader(reader)?)
 	}
 
+	/// Parses a beatmap from a mutable byte slice. The slice is used as scratch space during parsing, so its contents
+	/// are unspecified afterwards.
+	pub fn from_slice(bytes: &mut [u8]) -> Result<Self, AnyverParseError> {
+		Self::inner_parse(simd_json::from_slice(bytes)?)
+	}
+
+	pub fn from_bytes(bytes: &mut Vec<u8>) -> Result<Self, AnyverParseError> {
+		Self::from_slice(bytes.as_mut_slice())
+	}
+
 	pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, AnyverParseError> {
-		Self::from_reader(BufReader::new(File::open(path)?))
+		let path = path.as_ref();
+		Self::from_reader(BufReader::new(File::open(path).map_err(|e| AnyverParseError::from_io(e, path))?))
 	}
 
 	fn inner_parse(value: OwnedValue) -> Result<Self, AnyverParseError> {
-		if let Some(version) = value.get("_version") {
-			let version = version.try_as_str()?;
-			if version.starts_with("2.") {
-				return Ok(AnyverBeatmap::V2(v2::Beatmap::deserialize(value)?));
-			} else {
-				return Err(AnyverParseError::UnsupportedVersion(version.to_string()));
-			}
-		} else if let Some(version) = value.get("version") {
-			let version = version.try_as_str()?;
-			if version.starts_with("3.") {
-				return Ok(AnyverBeatmap::V3(v3::Beatmap::deserialize(value)?));
-			} else {
-				return Err(AnyverParseError::UnsupportedVersion(version.to_string()));
+		// most v2 maps use `_version`, but a few in the wild use the bare `version` key, so go by the major version instead
+		let version = match value.get("_version").or_else(|| value.get("version")) {
+			Some(version) => version.try_as_str()?,
+			None => return Err(AnyverParseError::UnsupportedVersion(String::from("unknown")))
+		};
+		match version.split('.').next() {
+			Some("2") => Ok(AnyverBeatmap::V2(v2::Beatmap::deserialize(value)?)),
+			Some("3") => Ok(AnyverBeatmap::V3(v3::Beatmap::deserialize(value)?)),
+			Some("4") => Ok(AnyverBeatmap::V4(v4::Beatmap::deserialize(value)?)),
+			_ => Err(AnyverParseError::UnsupportedVersion(version.to_string()))
+		}
+	}
+}
+
+impl FromStr for AnyverBeatmap {
+	type Err = AnyverParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::from_string(s)
+	}
+}
+
+impl TryFrom<&[u8]> for AnyverBeatmap {
+	type Error = AnyverParseError;
+
+	fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+		Self::from_reader(bytes)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{fmt::Debug, str::FromStr};
+
+	use simd_json::{
+		derived::ValueObjectAccess,
+		prelude::{ValueAsContainer, ValueAsScalar}
+	};
+
+	use super::{standard, v2, v3, v4, AnyverBeatmap, AnyverParseError, SchemaVersion};
+
+	/// Asserts that both beatmaps convert to the same map, including fakes.
+	fn assert_semantically_eq(a: &AnyverBeatmap, b: &AnyverBeatmap) {
+		let options = standard::ConversionOptions {
+			include_fake: standard::FakeHandling::IncludeFlagged
+		};
+		let convert = |map: &AnyverBeatmap| standard::Beatmap::from_any_with(map.clone(), 60., options).unwrap();
+		assert_eq!(convert(a).semantically_eq(&convert(b), 1e-6), Ok(()));
+	}
+
+	#[test]
+	fn test_detect_v4() {
+		let map = AnyverBeatmap::from_string(
+			r#"{
+				"version": "4.0.0",
+				"colorNotes": [{ "b": 2.0, "r": 0, "i": 0 }, { "b": 4.0, "r": 0, "i": 1 }],
+				"colorNotesData": [{ "x": 1, "y": 0, "c": 0, "d": 1, "a": 0 }, { "x": 2, "y": 0, "c": 1, "d": 1, "a": 0 }],
+				"bombNotes": [{ "b": 3.0, "r": 0, "i": 0 }],
+				"bombNotesData": [{ "x": 0, "y": 2 }],
+				"obstacles": [{ "b": 1.0, "r": 0, "i": 0 }],
+				"obstaclesData": [{ "d": 2.0, "x": 0, "y": 0, "w": 1, "h": 5 }],
+				"chains": [{ "hb": 5.0, "tb": 5.5, "hr": 0, "tr": 0, "i": 1, "ci": 0 }],
+				"chainsData": [{ "tx": 2, "ty": 2, "c": 4, "s": 1.0 }]
+			}"#
+		)
+		.unwrap();
+		assert!(matches!(map, AnyverBeatmap::V4(_)));
+
+		let map = standard::Beatmap::from_any(map, 120.0).unwrap();
+		assert_eq!(map.beats.len(), 2);
+		assert_eq!(map.beats[1].time, 2.0);
+		assert_eq!(map.bombs.len(), 1);
+		assert_eq!(map.obstacles[0].duration, 1.0);
+		assert_eq!(map.chains[0].tail_y, 2.0);
+	}
+
+	#[test]
+	fn test_v3_fake_objects() {
+		let AnyverBeatmap::V3(map) = AnyverBeatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [],
+				"bombNotes": [],
+				"obstacles": [],
+				"burstSliders": [],
+				"bpmEvents": [],
+				"waypoints": [{ "b": 2.0, "x": 3, "y": 1, "d": 6 }],
+				"fakeObstacles": [{ "b": 1.0, "x": 1, "y": 2, "d": 2.0, "w": 2, "h": 3 }],
+				"customData": {
+					"fakeBombNotes": [{ "b": 3.0, "x": 0, "y": 2 }],
+					"fakeObstacles": [{ "b": 6.0, "x": 0, "y": 0, "d": 1.0, "w": 1, "h": 1 }],
+					"fakeBurstSliders": [{ "b": 5.0, "x": 1, "y": 0, "c": 0, "d": 1, "tb": 5.5, "tx": 1, "ty": 1, "sc": 4, "s": 1.0 }],
+					"pointDefinitions": []
+				}
+			}"#
+		)
+		.unwrap() else {
+			panic!("expected a v3 map");
+		};
+
+		// Heck keeps fakes in customData; the top-level key is only a fallback, so both end up in the same list
+		assert_eq!((map.total_note_count(), map.total_obstacle_count(), map.total_chain_count()), (1, 2, 1));
+
+		let bombs = map.fake_bomb_notes.as_ref().unwrap();
+		assert_eq!((bombs[0].beat, bombs[0].y), (3.0, 2.0));
+		let obstacles = map.fake_obstacles.as_ref().unwrap();
+		assert_eq!((obstacles[0].duration, obstacles[0].width, obstacles[0].height), (2.0, 2.0, 3.0));
+		assert_eq!(obstacles[1].beat, 6.0);
+		assert_eq!(map.fake_burst_sliders.as_ref().unwrap()[0].num_slices, 4);
+		assert_eq!((map.waypoints[0].x, map.waypoints[0].offset_direction), (3.0, v3::NoteDirection::DownLeft));
+
+		let custom_data = map.custom_data.as_ref().unwrap().as_object().unwrap();
+		assert!(custom_data.contains_key("pointDefinitions") && !custom_data.contains_key("fakeBombNotes"));
+
+		// fakes are written back under customData only
+		let value: simd_json::OwnedValue = simd_json::serde::to_owned_value(&map).unwrap();
+		let value = value.as_object().unwrap();
+		assert!(!value.contains_key("fakeObstacles") && !value.contains_key("fakeBombNotes"));
+		let custom_data = value["customData"].as_object().unwrap();
+		assert_eq!(custom_data["fakeObstacles"].as_array().unwrap().len(), 2);
+		assert!(custom_data.contains_key("fakeBombNotes") && custom_data.contains_key("fakeBurstSliders") && custom_data.contains_key("pointDefinitions"));
+		assert!(!custom_data.contains_key("fakeColorNotes"));
+
+		let AnyverBeatmap::V3(reparsed) = AnyverBeatmap::from_string(simd_json::to_string(&map).unwrap()).unwrap() else {
+			panic!("expected a v3 map");
+		};
+		assert_eq!(reparsed, map);
+	}
+
+	#[test]
+	fn test_detect_v2_bare_version_key() {
+		let map = AnyverBeatmap::from_string(r#"{ "version": "2.0.0", "_notes": [], "_obstacles": [] }"#).unwrap();
+		assert_eq!(map.version(), "2.0.0");
+		assert_eq!(map.schema(), SchemaVersion::V2);
+		let AnyverBeatmap::V2(v2) = &map else {
+			panic!("expected a v2 map");
+		};
+		assert_eq!((v2.total_note_count(), v2.total_obstacle_count(), v2.total_chain_count()), (0, 0, 0));
+	}
+
+	#[test]
+	fn test_v2_split_notes_and_bombs() {
+		let map = v2::Beatmap::from_string(
+			r#"{
+				"_version": "2.6.0",
+				"_notes": [
+					{ "_time": 1.0, "_lineIndex": 1, "_lineLayer": 0, "_type": 0, "_cutDirection": 1 },
+					{ "_time": 2.0, "_lineIndex": 2, "_lineLayer": 1, "_type": 3, "_cutDirection": 0 },
+					{ "_time": 3.0, "_lineIndex": 2, "_lineLayer": 0, "_type": 1, "_cutDirection": 1 }
+				],
+				"_obstacles": []
+			}"#
+		)
+		.unwrap();
+		let (notes, bombs) = map.split_notes_and_bombs();
+		assert_eq!(notes.iter().map(|x| x.beat).collect::<Vec<_>>(), [1.0, 3.0]);
+		assert_eq!(bombs.iter().map(|x| x.beat).collect::<Vec<_>>(), [2.0]);
+	}
+
+	#[test]
+	fn test_serialize_delegates() {
+		let map = AnyverBeatmap::from_string(r#"{ "_version": "2.5.0", "_notes": [], "_obstacles": [] }"#).unwrap();
+		#[derive(serde::Serialize)]
+		struct Wrapper {
+			map: AnyverBeatmap
+		}
+
+		let serialized = simd_json::to_string(&Wrapper { map }).unwrap();
+		assert!(serialized.contains(r#""map":{"_version":"2.5.0""#), "{serialized}");
+	}
+
+	#[test]
+	fn test_v3_basic_event_default_float_value() {
+		let map = v3::Beatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [],
+				"bombNotes": [],
+				"obstacles": [],
+				"burstSliders": [],
+				"bpmEvents": [],
+				"basicBeatmapEvents": [{ "b": 1, "et": 0, "i": 1 }, { "b": 2, "et": 0, "i": 1, "f": 0.5 }]
+			}"#
+		)
+		.unwrap();
+		// a missing `f` is full brightness, as it is in v2
+		let values: Vec<_> = map.basic_beatmap_events.iter().map(|x| x.float_value).collect();
+		assert_eq!(values, [1., 0.5]);
+	}
+
+	#[test]
+	fn test_v3_custom_data_round_trip() {
+		let AnyverBeatmap::V3(map) = AnyverBeatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [],
+				"bombNotes": [],
+				"obstacles": [],
+				"burstSliders": [],
+				"bpmEvents": [],
+				"customData": {
+					"pointDefinitions": { "bounce": [[0, 0, 0, 0], [0, 2, 0, 0.5, "easeOutBounce"]] },
+					"customEvents": [{ "b": 4.0, "t": "AnimateTrack", "d": { "track": "notes", "duration": 2, "position": "bounce" } }]
+				}
+			}"#
+		)
+		.unwrap() else {
+			panic!("expected a v3 map");
+		};
+		assert!(map.custom_data.as_ref().unwrap().get("customEvents").is_some());
+		assert!(map.extra.is_empty());
+
+		let serialized = map.serialize_to_string(false).unwrap();
+		assert!(serialized.contains(r#""pointDefinitions":{"bounce":"#), "{serialized}");
+		assert!(serialized.contains(r#""t":"AnimateTrack""#), "{serialized}");
+		assert!(!v3::Beatmap::default().serialize_to_string(false).unwrap().contains("customData"));
+	}
+
+	#[test]
+	fn test_unknown_fields_round_trip() {
+		let v2 = AnyverBeatmap::from_string(
+			r#"{
+				"_version": "2.2.0",
+				"_notes": [{ "_time": 1.0, "_lineIndex": 1, "_lineLayer": 0, "_type": 0, "_cutDirection": 1, "myEditorField": "note" }],
+				"_obstacles": [{ "_time": 2.0, "_type": 0, "_lineIndex": 0, "_duration": 1.0, "_width": 1, "myEditorField": [1, 2] }],
+				"myEditorField": { "nested": true }
+			}"#
+		)
+		.unwrap();
+		let v3 = AnyverBeatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [{ "b": 1.0, "x": 1, "y": 0, "c": 0, "d": 1, "a": 0, "myEditorField": "note" }],
+				"bombNotes": [{ "b": 1.5, "x": 2, "y": 0, "myEditorField": 3 }],
+				"obstacles": [],
+				"burstSliders": [],
+				"bpmEvents": [],
+				"myEditorField": { "nested": true }
+			}"#
+		)
+		.unwrap();
+
+		for map in [v2, v3] {
+			let serialized = map.serialize_to_string(false).unwrap();
+			assert_eq!(serialized.matches("myEditorField").count(), 3, "{serialized}");
+			let reparsed = AnyverBeatmap::from_string(serialized).unwrap();
+			assert_eq!(reparsed.serialize_to_string(false).unwrap().matches("myEditorField").count(), 3);
+			assert_semantically_eq(&map, &reparsed);
+		}
+	}
+
+	#[test]
+	fn test_precise_direction_round_trip() {
+		let json = r#"{
+			"_version": "2.2.0",
+			"_notes": [
+				{ "_time": 1, "_lineIndex": 1, "_lineLayer": 0, "_type": 0, "_cutDirection": 1050 },
+				{ "_time": 2, "_lineIndex": 1500, "_lineLayer": 0, "_type": 1, "_cutDirection": 1270 },
+				{ "_time": 3, "_lineIndex": 2, "_lineLayer": 0, "_type": 1, "_cutDirection": 1 }
+			],
+			"_obstacles": []
+		}"#;
+		let map = v2::Beatmap::from_string(json).unwrap();
+		assert_eq!(map.notes[0].direction, v2::NoteDirection::Precise(1050));
+		assert_eq!(map.notes[0].direction.cardinal(), v2::NoteDirection::DownLeft);
+		assert_eq!(map.notes[0].direction.to_degrees(), Some(230.));
+		assert_eq!(map.notes[1].direction.cardinal(), v2::NoteDirection::Right);
+		assert_eq!(map.notes[2].direction, v2::NoteDirection::Down);
+
+		let directions = |map: &v2::Beatmap| {
+			let value = simd_json::to_owned_value(&mut map.serialize_to_bytes(false).unwrap()).unwrap();
+			let notes = value.get("_notes").unwrap().as_array().unwrap().clone();
+			notes
+				.iter()
+				.map(|x| x.get("_cutDirection").unwrap().as_u64().unwrap())
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(directions(&map), [1050, 1270, 1]);
+
+		// and through v3 & back
+		let AnyverBeatmap::V3(v3) = AnyverBeatmap::V2(map.clone()).into_version(3).unwrap() else {
+			panic!("expected a v3 map");
+		};
+		assert_eq!(v3.color_notes[1].direction, v3::NoteDirection::Precise(1270));
+		assert_semantically_eq(&AnyverBeatmap::V2(map), &AnyverBeatmap::V3(v3.clone()));
+		assert_eq!(directions(&v3.into()), [1050, 1270, 1]);
+
+		let map = standard::Beatmap::from_string(json, 60.).unwrap();
+		assert_eq!(map.beats[0].direction, standard::NoteDirection::DownLeft);
+	}
+
+	#[test]
+	fn test_missing_file_error_has_path() {
+		let err = AnyverBeatmap::from_file("tests/data/maps/missing.dat").unwrap_err();
+		assert!(matches!(&err, AnyverParseError::IoErrorWithPath { path, .. } if path.ends_with("missing.dat")));
+		assert!(err.to_string().contains("tests/data/maps/missing.dat"), "{err}");
+	}
+
+	#[test]
+	fn test_trait_parsing() {
+		fn parse<T: FromStr>(s: &str) -> T
+		where
+			T::Err: Debug
+		{
+			s.parse().unwrap()
+		}
+
+		let v2: v2::Beatmap = parse(
+			r#"{ "_version": "2.6.0", "_notes": [{ "_time": 1, "_lineIndex": 1, "_lineLayer": 0, "_type": 0, "_cutDirection": 1 }], "_obstacles": [], "_events": [] }"#
+		);
+		assert_eq!(v2.notes.len(), 1);
+		let v3: v3::Beatmap = parse(
+			r#"{ "version": "3.2.0", "colorNotes": [{ "b": 1, "x": 1, "y": 0, "c": 0, "d": 1 }], "bombNotes": [], "obstacles": [], "burstSliders": [], "bpmEvents": [] }"#
+		);
+		assert_eq!(v3.color_notes.len(), 1);
+		let v4: v4::Beatmap = parse(
+			r#"{ "version": "4.0.0", "colorNotes": [{ "b": 1, "r": 0, "i": 0 }], "colorNotesData": [{ "x": 1, "y": 0, "c": 0, "d": 1, "a": 0 }],
+				"bombNotes": [], "bombNotesData": [], "obstacles": [], "obstaclesData": [], "chains": [], "chainsData": [] }"#
+		);
+		assert_eq!(v4.color_notes.len(), 1);
+		let json = r#"{ "version": "3.2.0", "colorNotes": [], "bombNotes": [], "obstacles": [], "burstSliders": [], "bpmEvents": [] }"#;
+		assert!(matches!(parse::<AnyverBeatmap>(json), AnyverBeatmap::V3(_)));
+
+		let bytes = std::fs::read("tests/data/maps/1579c_ExpertPlusStandard.dat").unwrap();
+		assert!(matches!(AnyverBeatmap::try_from(bytes.as_slice()), Ok(AnyverBeatmap::V2(_))));
+		assert!("{".parse::<v3::Beatmap>().is_err());
+	}
+
+	#[test]
+	fn test_into_version_v2_to_v3() {
+		let map = AnyverBeatmap::from_string(
+			r#"{
+				"_version": "2.6.0",
+				"_notes": [
+					{ "_time": 1, "_lineIndex": 1, "_lineLayer": 0, "_type": 0, "_cutDirection": 1, "_customData": { "_track": "a" } },
+					{ "_time": 2, "_lineIndex": 2, "_lineLayer": 1, "_type": 3, "_cutDirection": 0 },
+					{ "_time": 3, "_lineIndex": 2, "_lineLayer": 0, "_type": 1, "_cutDirection": 5, "_customData": { "_fake": true } }
+				],
+				"_obstacles": [{ "_time": 1, "_type": 1, "_lineIndex": 0, "_duration": 2, "_width": 2 }],
+				"_events": [
+					{ "_time": 0, "_type": 1, "_value": 3 },
+					{ "_time": 1, "_type": 5, "_value": 1 },
+					{ "_time": 2, "_type": 14, "_value": 1 },
+					{ "_time": 4, "_type": 100, "_value": 0, "_floatValue": 150 }
+				],
+				"_customData": { "_bookmarks": [] }
+			}"#
+		)
+		.unwrap();
+
+		let converted = map.clone().into_version(3).unwrap();
+		// the notes, bombs, fakes & obstacles are the same map
+		assert_semantically_eq(&map, &converted);
+		let AnyverBeatmap::V3(map) = converted else {
+			panic!("expected a v3 map");
+		};
+		assert_eq!(map.color_notes[0].extra["customData"].get("_track").unwrap(), "a");
+		assert!(map.fake_color_notes.is_some());
+		assert_eq!((map.basic_beatmap_events.len(), map.basic_beatmap_events[0].float_value), (1, 1.));
+		assert!(map.color_boost_beatmap_events[0].on);
+		assert_eq!(map.rotation_events[0].rotation, -45.);
+		assert_eq!((map.bpm_events[0].song_time, map.bpm_events[0].beats), (4., 150.));
+		assert!(map.custom_data.unwrap().get("_bookmarks").is_some());
+		assert!(map.extra.is_empty());
+	}
+
+	#[test]
+	fn test_into_version_v2_v3_v2_fakes() {
+		let map = AnyverBeatmap::from_string(
+			r#"{
+				"_version": "2.6.0",
+				"_notes": [
+					{ "_time": 1, "_lineIndex": 1, "_lineLayer": 0, "_type": 0, "_cutDirection": 1 },
+					{ "_time": 2, "_lineIndex": 2, "_lineLayer": 0, "_type": 1, "_cutDirection": 5, "_customData": { "_fake": true } },
+					{ "_time": 3, "_lineIndex": 0, "_lineLayer": 2, "_type": 3, "_cutDirection": 0, "_customData": { "_fake": true } }
+				],
+				"_obstacles": [{ "_time": 4, "_type": 0, "_lineIndex": 3, "_duration": 1, "_width": 1, "_customData": { "_fake": true } }],
+				"_events": [],
+				"_customData": { "_bookmarks": [] }
+			}"#
+		)
+		.unwrap();
+
+		// Heck only reads fakes from customData, next to the carried over map custom data
+		let v3 = simd_json::to_string(&map.clone().into_version(3).unwrap()).unwrap();
+		let value: simd_json::OwnedValue = simd_json::from_slice(&mut v3.clone().into_bytes()).unwrap();
+		let value = value.as_object().unwrap();
+		assert!(
+			["fakeColorNotes", "fakeBombNotes", "fakeObstacles"]
+				.iter()
+				.all(|x| !value.contains_key(*x))
+		);
+		let custom_data = value["customData"].as_object().unwrap();
+		assert!(custom_data.contains_key("_bookmarks"));
+		for key in ["fakeColorNotes", "fakeBombNotes", "fakeObstacles"] {
+			assert_eq!(custom_data[key].as_array().unwrap().len(), 1, "{key}");
+		}
+
+		let v3 = AnyverBeatmap::from_string(v3).unwrap();
+		assert_semantically_eq(&map, &v3);
+		let AnyverBeatmap::V2(v2) = v3.into_version(2).unwrap() else {
+			panic!("expected a v2 map");
+		};
+		assert_eq!(v2.notes.iter().filter(|x| x.is_fake()).count(), 2);
+		assert!(v2.obstacles[0].is_fake());
+		let custom_data = v2.extra["_customData"].as_object().unwrap();
+		assert!(custom_data.contains_key("_bookmarks") && !custom_data.contains_key("fakeColorNotes"));
+		assert_semantically_eq(&map, &AnyverBeatmap::V2(v2));
+	}
+
+	#[test]
+	fn test_into_version_v3_to_v2() {
+		let map = AnyverBeatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [{ "b": 2, "x": 1, "y": 0, "c": 0, "d": 1, "a": 15, "customData": { "track": "a" } }],
+				"bombNotes": [{ "b": 1, "x": 2, "y": 2 }],
+				"obstacles": [{ "b": 1, "x": 0, "y": 0, "d": 2, "w": 1, "h": 5 }],
+				"burstSliders": [],
+				"bpmEvents": [{ "b": 0, "m": 120 }],
+				"rotationEvents": [{ "b": 3, "e": 1, "r": 30 }, { "b": 4, "e": 0, "r": 100 }],
+				"colorBoostBeatmapEvents": [{ "b": 5, "o": true }],
+				"fakeObstacles": [{ "b": 6, "x": 3, "y": 0, "d": 1, "w": 1, "h": 1 }],
+				"lightColorEventBoxGroups": [],
+				"customData": { "customEvents": [] }
+			}"#
+		)
+		.unwrap();
+
+		// v3 only data which v2 can't represent is an error rather than being dropped
+		let AnyverBeatmap::V3(v3) = &map else {
+			panic!("expected a v3 map");
+		};
+		let lossy = |f: &dyn Fn(&mut v3::Beatmap)| {
+			let mut v3 = v3.clone();
+			f(&mut v3);
+			match AnyverBeatmap::V3(v3).into_version(2) {
+				Err(standard::ConversionError::LossyConversion { to: 2, dropped }) => dropped,
+				x => panic!("expected a lossy conversion error, got {x:?}")
 			}
+		};
+		let chain: v3::BurstSlider =
+			simd_json::serde::from_slice(&mut br#"{ "b": 2, "x": 1, "y": 0, "c": 0, "d": 1, "tb": 2.5, "tx": 1, "ty": 2, "sc": 4, "s": 1 }"#.to_vec()).unwrap();
+		assert_eq!(lossy(&|x| x.burst_sliders.push(chain.clone())), "chains");
+		assert_eq!(lossy(&|x| x.fake_burst_sliders = Some(vec![chain.clone()])), "chains");
+		assert_eq!(
+			lossy(&|x| {
+				x.extra
+					.insert(String::from("lightColorEventBoxGroups"), simd_json::json!([{ "b": 1, "g": 0, "e": [] }]));
+			}),
+			"lighting event boxes"
+		);
+
+		let converted = map.clone().into_version(2).unwrap();
+		assert_semantically_eq(&map, &converted);
+		let AnyverBeatmap::V2(map) = converted else {
+			panic!("expected a v2 map");
+		};
+		// sorted by beat
+		assert_eq!(map.notes.iter().map(|x| (x.beat, x.note_type)).collect::<Vec<_>>(), [(1., v2::NoteType::Bomb), (2., v2::NoteType::Red)]);
+		assert_eq!(map.notes[1].angle_offset, Some(15.));
+		assert_eq!(map.notes[1].custom_data.as_ref().unwrap().get("track").unwrap(), "a");
+		assert_eq!(map.obstacles.iter().map(|x| (x.wall_type, x.is_fake())).collect::<Vec<_>>(), [(0, false), (1200, true)]);
+		let events: Vec<_> = map.events.iter().map(|x| (x.event_type, x.value)).collect();
+		assert_eq!(events, [(15, 5), (14, 1460), (5, 1)]);
+		assert_eq!(map.events[0].lane_rotation(), Some(30.));
+		assert_eq!(map.bpm_events[0].beats, 120.);
+		assert!(map.extra["_customData"].get("customEvents").is_some());
+
+		let map = AnyverBeatmap::V2(map);
+		assert!(matches!(map.into_version(2), Ok(AnyverBeatmap::V2(_))));
+		let v4 = AnyverBeatmap::V4(v4::Beatmap::from_string(r#"{ "version": "4.0.0", "colorNotes": [], "colorNotesData": [], "bombNotes": [], "bombNotesData": [], "obstacles": [], "obstaclesData": [], "chains": [], "chainsData": [] }"#).unwrap());
+		assert!(matches!(v4.into_version(3), Err(standard::ConversionError::UnsupportedVersion { from: SchemaVersion::V4, to: 3 })));
+	}
+
+	#[test]
+	fn test_entry_points_agree() {
+		const PATH: &str = "tests/data/maps/1579c_ExpertPlusStandard.dat";
+		let bytes = std::fs::read(PATH).unwrap();
+
+		let maps = [
+			AnyverBeatmap::from_file(PATH).unwrap(),
+			AnyverBeatmap::from_reader(bytes.as_slice()).unwrap(),
+			AnyverBeatmap::from_string(String::from_utf8(bytes.clone()).unwrap()).unwrap(),
+			AnyverBeatmap::from_bytes(&mut bytes.clone()).unwrap(),
+			AnyverBeatmap::from_slice(&mut bytes.clone()).unwrap()
+		];
+		// compare as values, since the order of unknown fields isn't stable between parses
+		let values: Vec<_> = maps
+			.iter()
+			.map(|m| simd_json::to_owned_value(&mut m.serialize_to_bytes(false).unwrap()).unwrap())
+			.collect();
+		for value in &values[1..] {
+			assert_eq!(value, &values[0]);
 		}
-		Err(AnyverParseError::UnsupportedVersion(String::from("unknown")))
 	}
 }
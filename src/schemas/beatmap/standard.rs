@@ -1,49 +1,87 @@
-use std::{io::Read, path::Path};
+use std::{collections::HashMap, fmt::Debug, io::Read, ops::Range, path::Path};
 
-use super::{v2, v3, AnyverBeatmap, AnyverParseError};
+use glam::Vec2;
+use thiserror::Error;
+
+pub use super::v3::MidAnchorMode;
+use super::{util, v2, v3, v4, AnyverBeatmap, AnyverParseError, SchemaVersion};
+use crate::schemas::mapinfo::standard::BeatmapCharacteristic;
 
 #[derive(Debug, Clone)]
 pub struct Beatmap {
 	pub beats: Vec<Beat>,
 	pub bombs: Vec<Bomb>,
 	pub obstacles: Vec<Obstacle>,
-	pub chains: Vec<Chain>
+	pub chains: Vec<Chain>,
+	pub arcs: Vec<Arc>,
+	/// Lane rotation events, as used by 360 & 90 degree maps.
+	pub rotations: Vec<LaneRotation>,
+	/// Basic lighting & color boost events.
+	pub lights: Vec<LightEvent>,
+	/// Whether the map uses mapping extensions, which lets objects leave the standard grid. Set when converting a map
+	/// with precise positions, sizes, wall types or cut directions, and when loading a difficulty which lists mapping
+	/// extensions as a requirement (the only way to tell for maps which just use extra whole lanes).
+	pub mapping_extensions: bool
 }
 
 impl Beatmap {
-	pub fn from_any(beatmap: AnyverBeatmap, bpm: f32) -> Self {
+	/// The default time window in seconds used by [`Self::swing_pairs`].
+	pub const DEFAULT_SWING_WINDOW: f32 = 0.5;
+
+	/// Converts a beatmap of any version, excluding fake objects.
+	pub fn from_any(beatmap: AnyverBeatmap, bpm: f32) -> Result<Self, ConversionError> {
+		Self::from_any_with(beatmap, bpm, ConversionOptions::default())
+	}
+
+	/// Converts a beatmap of any version, keeping fake objects according to `options`.
+	pub fn from_any_with(beatmap: AnyverBeatmap, bpm: f32, options: ConversionOptions) -> Result<Self, ConversionError> {
 		match beatmap {
-			AnyverBeatmap::V2(v2) => Self::from_v2(v2, bpm),
-			AnyverBeatmap::V3(v3) => Self::from_v3(v3, bpm)
+			AnyverBeatmap::V2(v2) => Self::from_v2_with(v2, bpm, options),
+			AnyverBeatmap::V3(v3) => Self::from_v3_with(v3, bpm, options),
+			AnyverBeatmap::V4(v4) => {
+				let mut map = Self::from_v4(v4, bpm)?;
+				map.retain_fakes(options.include_fake);
+				Ok(map)
+			}
 		}
 	}
 
 	pub fn from_file<P: AsRef<Path>>(path: P, bpm: f32) -> Result<Self, AnyverParseError> {
-		Ok(Self::from_any(AnyverBeatmap::from_file(path)?, bpm))
+		Ok(Self::from_any(AnyverBeatmap::from_file(path)?, bpm)?)
 	}
 
 	pub fn from_string(s: impl Into<String>, bpm: f32) -> Result<Self, AnyverParseError> {
-		Ok(Self::from_any(AnyverBeatmap::from_string(s)?, bpm))
+		Ok(Self::from_any(AnyverBeatmap::from_string(s)?, bpm)?)
 	}
 
 	pub fn from_reader<R: Read>(reader: R, bpm: f32) -> Result<Self, AnyverParseError> {
-		Ok(Self::from_any(AnyverBeatmap::from_reader(reader)?, bpm))
+		Ok(Self::from_any(AnyverBeatmap::from_reader(reader)?, bpm)?)
+	}
+
+	pub fn from_v2(beatmap: v2::Beatmap, bpm: f32) -> Result<Self, ConversionError> {
+		Self::from_v2_with(beatmap, bpm, ConversionOptions::default())
 	}
 
-	pub fn from_v2(beatmap: v2::Beatmap, bpm: f32) -> Self {
-		let bpm_events = beatmap.bpm_events.into_iter().map(BpmEvent::from).collect();
-		let bpm_tracker = BpmTracker::new(bpm, bpm_events);
+	/// Converts a v2 beatmap, where fake objects are those marked `_fake` in their custom data.
+	pub fn from_v2_with(beatmap: v2::Beatmap, bpm: f32, options: ConversionOptions) -> Result<Self, ConversionError> {
+		let bpm_tracker = BpmTracker::from_v2(&beatmap, bpm);
+		let mapping_extensions =
+			beatmap.notes.iter().any(|x| matches!(x.direction, v2::NoteDirection::Precise(_))) || beatmap.obstacles.iter().any(|x| x.wall_type >= 1000);
 
 		let mut beats = Vec::new();
 		let mut bombs = Vec::new();
-		for note in beatmap.notes {
+		for (index, note) in beatmap.notes.into_iter().enumerate() {
+			let invalid = |note: v2::Note| ConversionError::InvalidNote { index, beat: note.beat };
+			let fake = note.is_fake();
 			if note.note_type == v2::NoteType::Bomb {
-				let mut bomb: Bomb = note.try_into().unwrap();
+				let mut bomb = Bomb::try_from(note).map_err(invalid)?;
 				bomb.time = bpm_tracker.beat_to_song_time(bomb.beat);
+				bomb.fake = fake;
 				bombs.push(bomb);
 			} else {
-				let mut beat: Beat = note.try_into().unwrap();
+				let mut beat = Beat::try_from(note).map_err(invalid)?;
 				beat.time = bpm_tracker.beat_to_song_time(beat.beat);
+				beat.fake = fake;
 				beats.push(beat);
 			}
 		}
@@ -51,7 +89,10 @@ impl Beatmap {
 		let obstacles = beatmap
 			.obstacles
 			.into_iter()
-			.map(Obstacle::from)
+			.map(|x| {
+				let fake = x.is_fake();
+				Obstacle { fake, ..Obstacle::from(x) }
+			})
 			.map(|mut x| {
 				let start_time = bpm_tracker.beat_to_song_time(x.beat);
 				let end_time = bpm_tracker.beat_to_song_time(x.beat + x.duration_beats);
@@ -62,40 +103,89 @@ impl Beatmap {
 			})
 			.collect();
 
-		Self {
+		let rotations = beatmap
+			.events
+			.iter()
+			.filter_map(|x| {
+				Some(LaneRotation {
+					beat: x.beat,
+					time: bpm_tracker.beat_to_song_time(x.beat),
+					rotation: x.lane_rotation()?,
+					early: x.event_type == v2::Event::EARLY_LANE_ROTATION
+				})
+			})
+			.collect();
+
+		let lights = beatmap
+			.events
+			.iter()
+			.filter(|x| x.is_lighting())
+			.map(|x| LightEvent {
+				beat: x.beat,
+				time: bpm_tracker.beat_to_song_time(x.beat),
+				event_type: x.event_type,
+				value: x.value,
+				float_value: x.float_value.unwrap_or(1.)
+			})
+			.collect();
+
+		let mut map = Self {
 			beats,
 			bombs,
 			obstacles,
-			chains: Vec::new()
-		}
+			chains: Vec::new(),
+			arcs: Vec::new(),
+			rotations,
+			lights,
+			mapping_extensions
+		};
+		map.mapping_extensions |= map.has_precise_positions();
+		map.retain_fakes(options.include_fake);
+		map.sort_by_time();
+		Ok(map)
 	}
 
-	pub fn from_v3(beatmap: v3::Beatmap, bpm: f32) -> Self {
-		let bpm_events = beatmap.bpm_events.into_iter().map(BpmEvent::from).collect();
-		let bpm_tracker = BpmTracker::new(bpm, bpm_events);
+	pub fn from_v3(beatmap: v3::Beatmap, bpm: f32) -> Result<Self, ConversionError> {
+		Self::from_v3_with(beatmap, bpm, ConversionOptions::default())
+	}
 
-		let bombs = beatmap
-			.bomb_notes
-			.into_iter()
-			.map(Bomb::from)
+	/// Converts a v3 beatmap, optionally including its fake (non-interactable) objects, which are marked with `fake`.
+	pub fn from_v3_with_fakes(beatmap: v3::Beatmap, bpm: f32, include_fakes: bool) -> Result<Self, ConversionError> {
+		let include_fake = if include_fakes { FakeHandling::IncludeFlagged } else { FakeHandling::Exclude };
+		Self::from_v3_with(beatmap, bpm, ConversionOptions { include_fake })
+	}
+
+	/// Converts a v3 beatmap, where fake objects are those in the `fake*` arrays of its custom data.
+	pub fn from_v3_with(beatmap: v3::Beatmap, bpm: f32, options: ConversionOptions) -> Result<Self, ConversionError> {
+		let include_fakes = options.include_fake != FakeHandling::Exclude;
+		fn with_fakes<T, U: From<T>>(objects: Vec<T>, fakes: Option<Vec<T>>, include_fakes: bool, mark_fake: fn(&mut U)) -> impl Iterator<Item = U> {
+			let fakes = fakes.filter(|_| include_fakes).into_iter().flatten();
+			objects.into_iter().map(U::from).chain(fakes.map(U::from).map(move |mut x| {
+				mark_fake(&mut x);
+				x
+			}))
+		}
+
+		let bpm_tracker = BpmTracker::from_v3(&beatmap, bpm);
+		let mapping_extensions = beatmap
+			.color_notes
+			.iter()
+			.chain(beatmap.fake_color_notes.iter().flatten())
+			.any(|x| matches!(x.direction, v3::NoteDirection::Precise(_)));
+
+		let bombs = with_fakes(beatmap.bomb_notes, beatmap.fake_bomb_notes, include_fakes, |x: &mut Bomb| x.fake = true)
 			.map(|mut x| {
 				x.time = bpm_tracker.beat_to_song_time(x.beat);
 				x
 			})
 			.collect();
-		let beats = beatmap
-			.color_notes
-			.into_iter()
-			.map(Beat::from)
+		let beats = with_fakes(beatmap.color_notes, beatmap.fake_color_notes, include_fakes, |x: &mut Beat| x.fake = true)
 			.map(|mut x| {
 				x.time = bpm_tracker.beat_to_song_time(x.beat);
 				x
 			})
 			.collect();
-		let obstacles = beatmap
-			.obstacles
-			.into_iter()
-			.map(Obstacle::from)
+		let obstacles = with_fakes(beatmap.obstacles, beatmap.fake_obstacles, include_fakes, |x: &mut Obstacle| x.fake = true)
 			.map(|mut x| {
 				let start_time = bpm_tracker.beat_to_song_time(x.beat);
 				let end_time = bpm_tracker.beat_to_song_time(x.beat + x.duration_beats);
@@ -105,10 +195,17 @@ impl Beatmap {
 				x
 			})
 			.collect();
-		let chains = beatmap
-			.burst_sliders
+		let chains = with_fakes(beatmap.burst_sliders, beatmap.fake_burst_sliders, include_fakes, |x: &mut Chain| x.fake = true)
+			.map(|mut x| {
+				x.time = bpm_tracker.beat_to_song_time(x.beat);
+				x.tail_time = bpm_tracker.beat_to_song_time(x.tail_beat);
+				x
+			})
+			.collect();
+		let arcs = beatmap
+			.sliders
 			.into_iter()
-			.map(Chain::from)
+			.map(Arc::from)
 			.map(|mut x| {
 				x.time = bpm_tracker.beat_to_song_time(x.beat);
 				x.tail_time = bpm_tracker.beat_to_song_time(x.tail_beat);
@@ -116,355 +213,3498 @@ impl Beatmap {
 			})
 			.collect();
 
-		Self { beats, bombs, obstacles, chains }
-	}
-}
+		let rotations = beatmap
+			.rotation_events
+			.into_iter()
+			.map(|x| LaneRotation {
+				beat: x.beat,
+				time: bpm_tracker.beat_to_song_time(x.beat),
+				rotation: x.rotation,
+				early: x.execution_time == 0
+			})
+			.collect();
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-#[repr(u8)]
-pub enum NoteColor {
-	Red = 0,
-	Blue = 1
-}
+		let boosts = beatmap.color_boost_beatmap_events.into_iter().map(|x| LightEvent {
+			beat: x.beat,
+			time: bpm_tracker.beat_to_song_time(x.beat),
+			event_type: v2::Event::COLOR_BOOST,
+			value: x.on as i32,
+			float_value: 1.
+		});
+		let mut lights: Vec<_> = beatmap
+			.basic_beatmap_events
+			.into_iter()
+			.filter(|x| !matches!(x.event_type, v2::Event::EARLY_LANE_ROTATION | v2::Event::LATE_LANE_ROTATION | v2::Event::BPM_CHANGE))
+			.map(|x| LightEvent {
+				beat: x.beat,
+				time: bpm_tracker.beat_to_song_time(x.beat),
+				event_type: x.event_type,
+				value: x.value,
+				float_value: x.float_value
+			})
+			.chain(boosts)
+			.collect();
+		lights.sort_by(|a, b| a.time.total_cmp(&b.time));
 
-impl TryFrom<v2::NoteType> for NoteColor {
-	type Error = v2::NoteType;
+		let mut map = Self {
+			beats,
+			bombs,
+			obstacles,
+			chains,
+			arcs,
+			rotations,
+			lights,
+			mapping_extensions
+		};
+		map.mapping_extensions |= map.has_precise_positions();
+		map.retain_fakes(options.include_fake);
+		map.sort_by_time();
+		Ok(map)
+	}
 
-	fn try_from(value: v2::NoteType) -> Result<Self, Self::Error> {
-		match value {
-			v2::NoteType::Red => Ok(NoteColor::Red),
-			v2::NoteType::Blue => Ok(NoteColor::Blue),
-			v => Err(v)
+	/// Converts a v4 beatmap. v4 beatmaps do not carry BPM changes (they live in the separate audio data file), so
+	/// all times are computed from `bpm` alone. Arcs, lane rotations & lighting are not yet read from v4 beatmaps.
+	///
+	/// Fails if an object references data (e.g. an entry of `color_notes_data`) which doesn't exist.
+	pub fn from_v4(beatmap: v4::Beatmap, bpm: f32) -> Result<Self, ConversionError> {
+		fn data<T>(data: &[T], index: usize, object: ObjectIndex) -> Result<&T, ConversionError> {
+			data.get(index).ok_or(ConversionError::MissingData(object))
 		}
+
+		let bpm_tracker = BpmTracker::new(bpm, []);
+
+		let bombs = beatmap
+			.bomb_notes
+			.iter()
+			.enumerate()
+			.map(|(i, x)| {
+				let mut x = Bomb::from((x, data(&beatmap.bomb_notes_data, x.index, ObjectIndex::Bomb(i))?));
+				x.time = bpm_tracker.beat_to_song_time(x.beat);
+				Ok(x)
+			})
+			.collect::<Result<_, _>>()?;
+		let beats = beatmap
+			.color_notes
+			.iter()
+			.enumerate()
+			.map(|(i, x)| {
+				let mut x = Beat::from((x, data(&beatmap.color_notes_data, x.index, ObjectIndex::Beat(i))?));
+				x.time = bpm_tracker.beat_to_song_time(x.beat);
+				Ok(x)
+			})
+			.collect::<Result<_, _>>()?;
+		let obstacles = beatmap
+			.obstacles
+			.iter()
+			.enumerate()
+			.map(|(i, x)| {
+				let mut x = Obstacle::from((x, data(&beatmap.obstacles_data, x.index, ObjectIndex::Obstacle(i))?));
+				let start_time = bpm_tracker.beat_to_song_time(x.beat);
+				let end_time = bpm_tracker.beat_to_song_time(x.beat + x.duration_beats);
+				x.time = start_time;
+				x.end_time = end_time;
+				x.duration = end_time - start_time;
+				Ok(x)
+			})
+			.collect::<Result<_, _>>()?;
+		let chains = beatmap
+			.chains
+			.iter()
+			.enumerate()
+			.map(|(i, x)| {
+				let head = data(&beatmap.color_notes_data, x.index, ObjectIndex::Chain(i))?;
+				let mut x = Chain::from((x, head, data(&beatmap.chains_data, x.chain_index, ObjectIndex::Chain(i))?));
+				x.time = bpm_tracker.beat_to_song_time(x.beat);
+				x.tail_time = bpm_tracker.beat_to_song_time(x.tail_beat);
+				Ok(x)
+			})
+			.collect::<Result<_, _>>()?;
+
+		let mut map = Self {
+			beats,
+			bombs,
+			obstacles,
+			chains,
+			arcs: Vec::new(),
+			rotations: Vec::new(),
+			lights: Vec::new(),
+			mapping_extensions: false
+		};
+		map.mapping_extensions = map.has_precise_positions();
+		map.sort_by_time();
+		Ok(map)
 	}
-}
 
-impl From<v3::NoteColor> for NoteColor {
-	fn from(value: v3::NoteColor) -> Self {
-		match value {
-			v3::NoteColor::Red => NoteColor::Red,
-			v3::NoteColor::Blue => NoteColor::Blue
+	/// Yields every object in time order, without allocating. Objects at the same time are ordered beats, bombs,
+	/// obstacles, chains, then arcs.
+	///
+	/// This relies on each object vector being sorted by time, which the conversion functions guarantee; call
+	/// [`Self::sort_by_time`] after modifying object times.
+	pub fn objects(&self) -> Objects<'_> {
+		Objects {
+			beats: &self.beats,
+			bombs: &self.bombs,
+			obstacles: &self.obstacles,
+			chains: &self.chains,
+			arcs: &self.arcs
 		}
 	}
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-#[repr(u8)]
-pub enum NoteDirection {
-	Up = 0,
-	Down = 1,
-	Left = 2,
-	Right = 3,
-	UpLeft = 4,
-	UpRight = 5,
-	DownLeft = 6,
-	DownRight = 7,
-	Any = 8
-}
+	/// Sorts each object vector by time, keeping the relative order of objects at the same time.
+	pub fn sort_by_time(&mut self) {
+		self.beats.sort_by(|a, b| a.time.total_cmp(&b.time));
+		self.bombs.sort_by(|a, b| a.time.total_cmp(&b.time));
+		self.obstacles.sort_by(|a, b| a.time.total_cmp(&b.time));
+		self.chains.sort_by(|a, b| a.time.total_cmp(&b.time));
+		self.arcs.sort_by(|a, b| a.time.total_cmp(&b.time));
+		self.rotations.sort_by(|a, b| a.time.total_cmp(&b.time));
+		self.lights.sort_by(|a, b| a.time.total_cmp(&b.time));
+	}
 
-impl From<v2::NoteDirection> for NoteDirection {
-	fn from(value: v2::NoteDirection) -> Self {
-		match value {
-			v2::NoteDirection::Up => NoteDirection::Up,
-			v2::NoteDirection::Down => NoteDirection::Down,
-			v2::NoteDirection::Left => NoteDirection::Left,
-			v2::NoteDirection::Right => NoteDirection::Right,
-			v2::NoteDirection::UpLeft => NoteDirection::UpLeft,
-			v2::NoteDirection::UpRight => NoteDirection::UpRight,
-			v2::NoteDirection::DownLeft => NoteDirection::DownLeft,
-			v2::NoteDirection::DownRight => NoteDirection::DownRight,
-			v2::NoteDirection::Any => NoteDirection::Any
-		}
+	/// Returns the time the last object ends, including obstacle ends and chain & arc tails, or `0` for an empty map.
+	pub fn end_time(&self) -> f32 {
+		self.objects().map(|x| x.end_time()).fold(0., f32::max)
 	}
-}
 
-impl From<v3::NoteDirection> for NoteDirection {
-	fn from(value: v3::NoteDirection) -> Self {
-		match value {
-			v3::NoteDirection::Up => NoteDirection::Up,
-			v3::NoteDirection::Down => NoteDirection::Down,
-			v3::NoteDirection::Left => NoteDirection::Left,
-			v3::NoteDirection::Right => NoteDirection::Right,
-			v3::NoteDirection::UpLeft => NoteDirection::UpLeft,
-			v3::NoteDirection::UpRight => NoteDirection::UpRight,
-			v3::NoteDirection::DownLeft => NoteDirection::DownLeft,
-			v3::NoteDirection::DownRight => NoteDirection::DownRight,
-			v3::NoteDirection::Any => NoteDirection::Any
-		}
+	/// Adds the objects & events of `other` to this map, keeping everything sorted by time.
+	pub fn merge(&mut self, other: Beatmap) {
+		self.beats.extend(other.beats);
+		self.bombs.extend(other.bombs);
+		self.obstacles.extend(other.obstacles);
+		self.chains.extend(other.chains);
+		self.arcs.extend(other.arcs);
+		self.rotations.extend(other.rotations);
+		self.lights.extend(other.lights);
+		self.mapping_extensions |= other.mapping_extensions;
+		self.sort_by_time();
 	}
-}
 
-#[derive(Debug, Clone)]
-pub struct Beat {
-	beat: f32,
-	pub time: f32,
-	pub x: f32,
-	pub y: f32,
-	pub angle_offset: Option<f32>,
-	pub color: NoteColor,
-	pub direction: NoteDirection
-}
+	/// Whether any object sits between grid positions or has a fractional size, which only mapping extensions can do.
+	fn has_precise_positions(&self) -> bool {
+		let fractional = |values: &[f32]| values.iter().any(|x| x.fract() != 0.);
+		self.beats.iter().any(|x| fractional(&[x.x, x.y]))
+			|| self.bombs.iter().any(|x| fractional(&[x.x, x.y]))
+			|| self.obstacles.iter().any(|x| fractional(&[x.x, x.y, x.width, x.height]))
+			|| self.chains.iter().any(|x| fractional(&[x.x, x.y, x.tail_x, x.tail_y]))
+			|| self.arcs.iter().any(|x| fractional(&[x.x, x.y, x.tail_x, x.tail_y]))
+	}
 
-impl TryFrom<v2::Note> for Beat {
-	type Error = v2::Note;
+	/// Adds `other` to the end of this map, starting `gap` seconds after [`Self::end_time`]. Only times are shifted,
+	/// so the beat values of the appended objects are still relative to the start of `other`.
+	pub fn append_after(&mut self, mut other: Beatmap, gap: f32) {
+		let offset = self.end_time() + gap;
+		other.map_times(|t| t + offset);
+		self.merge(other);
+	}
 
-	fn try_from(value: v2::Note) -> Result<Self, Self::Error> {
-		if value.note_type == v2::NoteType::Bomb {
-			return Err(value);
+	/// Adds `offset` seconds to the time of every object & event, e.g. to correct for an audio offset. Beat values are
+	/// left as they are. `negative` decides what happens to times which would end up before the start of the song; with
+	/// [`NegativeTimes::Error`] the map is left unchanged.
+	pub fn shift_time(&mut self, offset: f32, negative: NegativeTimes) -> Result<(), NegativeTimeError> {
+		match negative {
+			NegativeTimes::Allow => self.map_times(|t| t + offset),
+			NegativeTimes::Clamp => self.map_times(|t| (t + offset).max(0.)),
+			NegativeTimes::Error => {
+				let start = self
+					.objects()
+					.map(|x| x.time())
+					.chain(self.rotations.iter().map(|x| x.time))
+					.chain(self.lights.iter().map(|x| x.time))
+					.fold(f32::INFINITY, f32::min);
+				if start + offset < 0. {
+					return Err(NegativeTimeError { offset });
+				}
+				self.map_times(|t| t + offset)
+			}
 		}
+		Ok(())
+	}
 
-		Ok(Self {
-			beat: value.beat,
-			time: 0.0,
-			x: value.x,
-			y: value.y,
-			angle_offset: value.angle_offset,
-			color: value.note_type.try_into().unwrap(),
-			direction: value.direction.into()
-		})
+	/// Applies `f` to the time of every object & event, keeping obstacle durations in sync.
+	fn map_times(&mut self, f: impl Fn(f32) -> f32) {
+		for x in &mut self.beats {
+			x.time = f(x.time);
+		}
+		for x in &mut self.bombs {
+			x.time = f(x.time);
+		}
+		for x in &mut self.obstacles {
+			x.time = f(x.time);
+			x.end_time = f(x.end_time);
+			x.duration = x.end_time - x.time;
+		}
+		for x in &mut self.chains {
+			x.time = f(x.time);
+			x.tail_time = f(x.tail_time);
+		}
+		for x in &mut self.arcs {
+			x.time = f(x.time);
+			x.tail_time = f(x.tail_time);
+		}
+		for x in &mut self.rotations {
+			x.time = f(x.time);
+		}
+		for x in &mut self.lights {
+			x.time = f(x.time);
+		}
 	}
-}
 
-impl From<v3::ColorNote> for Beat {
-	fn from(value: v3::ColorNote) -> Self {
-		Self {
-			beat: value.beat,
-			time: 0.0,
-			x: value.x,
-			y: value.y,
-			angle_offset: value.angle_offset,
-			color: value.color.into(),
-			direction: value.direction.into()
+	/// Returns the objects in the time window `start..end`. Notes, chains & arcs are included if they start within the
+	/// window, and obstacles if they're active at any point during it.
+	///
+	/// Finding the obstacles scans every obstacle which starts before the window, so use [`Self::time_index`] instead
+	/// for repeated queries. Like [`Self::objects`], this relies on each object vector being sorted by time.
+	pub fn objects_between(&self, start: f32, end: f32) -> TimeWindow<'_> {
+		// obstacles are sorted by start time, so any that are still active may have started long before the window
+		let candidates = &self.obstacles[..self.obstacles.partition_point(|x| x.time < end)];
+		TimeWindow::new(self, start, end, candidates)
+	}
+
+	/// Builds an index for answering [`TimeIndex::objects_between`] queries in `O(log n + k)`. The index borrows the
+	/// map, so it can't go stale. Like [`Self::objects`], this relies on each object vector being sorted by time.
+	pub fn time_index(&self) -> TimeIndex<'_> {
+		let mut end = f32::NEG_INFINITY;
+		TimeIndex {
+			map: self,
+			obstacle_ends: self
+				.obstacles
+				.iter()
+				.map(|x| {
+					end = end.max(x.end_time);
+					end
+				})
+				.collect()
 		}
 	}
-}
 
-#[derive(Debug, Clone)]
-pub struct Bomb {
-	beat: f32,
-	pub time: f32,
-	pub x: f32,
-	pub y: f32
-}
+	/// Returns the beats from `time` to `time + lookahead` inclusive, i.e. the notes on screen at `time` for a given
+	/// reaction time. Relies on `beats` being sorted by time.
+	pub fn cells_at(&self, time: f32, lookahead: f32) -> Vec<&Beat> {
+		let first = self.beats.partition_point(|x| x.time < time);
+		self.beats[first..].iter().take_while(|x| x.time <= time + lookahead).collect()
+	}
 
-impl TryFrom<v2::Note> for Bomb {
-	type Error = v2::Note;
+	/// Computes object counts & note density, using buckets of [`MapStats::DEFAULT_BUCKET_SIZE`] seconds.
+	pub fn stats(&self) -> MapStats {
+		self.stats_with_bucket_size(MapStats::DEFAULT_BUCKET_SIZE)
+	}
 
-	fn try_from(value: v2::Note) -> Result<Self, Self::Error> {
-		if value.note_type != v2::NoteType::Bomb {
-			return Err(value);
+	/// Computes object counts & note density, with the density histogram split into buckets of `bucket_size` seconds
+	/// starting from the beginning of the song.
+	pub fn stats_with_bucket_size(&self, bucket_size: f32) -> MapStats {
+		let (first, last) = self
+			.objects()
+			.map(|x| x.time())
+			.fold(None, |acc: Option<(f32, f32)>, t| Some(acc.map_or((t, t), |(a, b)| (a.min(t), b.max(t)))))
+			.unwrap_or_default();
+
+		let mut density = Vec::new();
+		for beat in &self.beats {
+			let bucket = (beat.time.max(0.) / bucket_size) as usize;
+			if density.len() <= bucket {
+				density.resize(bucket + 1, 0);
+			}
+			density[bucket] += 1;
 		}
-		Ok(Self {
-			beat: value.beat,
-			time: 0.0,
-			x: value.x,
-			y: value.y
-		})
-	}
-}
 
-impl From<v3::BombNote> for Bomb {
-	fn from(value: v3::BombNote) -> Self {
-		Self {
-			beat: value.beat,
-			time: 0.0,
-			x: value.x,
-			y: value.y
+		let span = last - first;
+		MapStats {
+			notes: self.beats.len(),
+			red_notes: self.beats.iter().filter(|x| x.color == NoteColor::Red).count(),
+			blue_notes: self.beats.iter().filter(|x| x.color == NoteColor::Blue).count(),
+			bombs: self.bombs.len(),
+			obstacles: self.obstacles.len(),
+			chains: self.chains.len(),
+			nps: if span > 0. { self.beats.len() as f32 / span } else { 0. },
+			bucket_size,
+			density
 		}
 	}
-}
 
-#[derive(Debug, Clone)]
-pub struct Obstacle {
-	beat: f32,
-	pub time: f32,
-	pub x: f32,
-	pub y: f32,
-	duration_beats: f32,
-	pub duration: f32,
-	pub end_time: f32,
-	pub width: f32,
-	pub height: f32
-}
+	/// Returns the highest notes per second over any `window` seconds of the map, along with the time that window
+	/// starts. Returns `(0, 0)` for an empty map or a non-positive window.
+	pub fn peak_nps(&self, window: f32) -> (f32, f32) {
+		if window <= 0. {
+			return (0., 0.);
+		}
 
-impl From<v2::Obstacle> for Obstacle {
-	fn from(value: v2::Obstacle) -> Self {
-		let (y, height) = match value.wall_type {
-			0 => (0., 5.),
-			1 => (2., 3.),
-			t => {
-				let mut value = t;
-				let h = if t >= 4001 && t <= 410000 {
-					value -= 4001;
-					value / 1000
-				} else {
-					value - 1000
-				};
-				let h = ((h as f32 / 1000.) * 5.) * 1000. + 1000.;
+		let (mut peak, mut peak_time) = (0, 0.);
+		let mut start = 0;
+		for (end, beat) in self.beats.iter().enumerate() {
+			while beat.time - self.beats[start].time >= window {
+				start += 1;
+			}
+			if end + 1 - start > peak {
+				peak = end + 1 - start;
+				peak_time = self.beats[start].time;
+			}
+		}
+		(peak as f32 / window, peak_time)
+	}
 
-				let mut sh = 0.0;
-				let mut v1 = t;
-				if t >= 4001 && t <= 410000 {
-					v1 -= 4001;
-					sh = v1 as f32 % 1000.;
-				}
+	/// Samples the notes per second over `window` seconds every `step` seconds from the start of the song until the
+	/// last beat, returning `(time, nps)` pairs. Returns an empty curve for an empty map or a non-positive window/step.
+	pub fn nps_curve(&self, window: f32, step: f32) -> Vec<(f32, f32)> {
+		let Some(last) = self.beats.last() else {
+			return Vec::new();
+		};
+		if window <= 0. || step <= 0. {
+			return Vec::new();
+		}
 
-				let l = ((sh / 750.) * 5.) * 1000. + 1334.;
-				(l / 1000. - 2., (h - 1000.) / 1000.)
+		let mut curve = Vec::new();
+		let (mut start, mut end) = (0, 0);
+		for i in 0..=(last.time.max(0.) / step) as usize {
+			let time = i as f32 * step;
+			while start < self.beats.len() && self.beats[start].time < time {
+				start += 1;
 			}
-		};
-		Self {
-			beat: value.beat,
-			time: 0.,
-			x: value.x,
-			y,
-			duration_beats: value.duration,
-			duration: 0.0,
-			end_time: 0.0,
-			height,
-			width: value.width
+			end = end.max(start);
+			while end < self.beats.len() && self.beats[end].time < time + window {
+				end += 1;
+			}
+			curve.push((time, (end - start) as f32 / window));
 		}
+		curve
 	}
-}
 
-impl From<v3::Obstacle> for Obstacle {
-	fn from(value: v3::Obstacle) -> Self {
-		Self {
-			beat: value.beat,
-			time: 0.0,
-			x: value.x,
-			y: value.y,
-			duration_beats: value.duration,
-			duration: 0.0,
-			end_time: 0.0,
-			height: value.height,
-			width: value.width
+	/// Counts the beats of each color & cut direction.
+	pub fn count_by_color_and_direction(&self) -> HashMap<(NoteColor, NoteDirection), usize> {
+		let mut counts = HashMap::new();
+		for beat in &self.beats {
+			*counts.entry((beat.color, beat.direction)).or_insert(0) += 1;
+		}
+		counts
+	}
+
+	/// Computes note counts, density & direction histograms for each color. Chain heads are regular beats so they're
+	/// included in the note counts, while the rest of the chain's slices are counted separately as links.
+	pub fn color_stats(&self) -> ColorStats {
+		let (first, last) = self
+			.objects()
+			.map(|x| x.time())
+			.fold(None, |acc: Option<(f32, f32)>, t| Some(acc.map_or((t, t), |(a, b)| (a.min(t), b.max(t)))))
+			.unwrap_or_default();
+
+		let mut stats = [ColorNoteStats::default(); 2];
+		for beat in &self.beats {
+			let stats = &mut stats[beat.color as usize];
+			stats.notes += 1;
+			stats.directions[beat.direction as usize] += 1;
+		}
+		for chain in &self.chains {
+			stats[chain.color as usize].chain_links += chain.num_slices.saturating_sub(1) as usize;
+		}
+
+		let span = last - first;
+		for stats in &mut stats {
+			stats.nps = if span > 0. { stats.notes as f32 / span } else { 0. };
+		}
+		let [red, blue] = stats;
+		ColorStats {
+			red,
+			blue,
+			balance: if red.notes + blue.notes > 0 {
+				red.notes as f32 / (red.notes + blue.notes) as f32
+			} else {
+				0.5
+			}
+		}
+	}
+
+	/// Returns the most common cut direction of the red & blue beats. Ties go to the direction which comes first in
+	/// [`NoteDirection`], and a color without any beats gives [`NoteDirection::Any`].
+	pub fn most_common_direction_per_color(&self) -> (NoteDirection, NoteDirection) {
+		let counts = self.count_by_color_and_direction();
+		let most_common = |color| {
+			let mut best = (NoteDirection::Any, 0);
+			for direction in [
+				NoteDirection::Up,
+				NoteDirection::Down,
+				NoteDirection::Left,
+				NoteDirection::Right,
+				NoteDirection::UpLeft,
+				NoteDirection::UpRight,
+				NoteDirection::DownLeft,
+				NoteDirection::DownRight,
+				NoteDirection::Any
+			] {
+				let count = counts.get(&(color, direction)).copied().unwrap_or(0);
+				if count > best.1 {
+					best = (direction, count);
+				}
+			}
+			best.0
+		};
+		(most_common(NoteColor::Red), most_common(NoteColor::Blue))
+	}
+
+	/// Returns each pair of consecutive same-color beats at most [`Self::DEFAULT_SWING_WINDOW`] seconds apart.
+	pub fn swing_pairs(&self) -> Vec<(&Beat, &Beat)> {
+		self.swing_pairs_within(Self::DEFAULT_SWING_WINDOW)
+	}
+
+	/// Returns each pair of consecutive same-color beats at most `window` seconds apart, ordered by the first beat's
+	/// time. Fake beats are ignored.
+	pub fn swing_pairs_within(&self, window: f32) -> Vec<(&Beat, &Beat)> {
+		let mut last: [Option<&Beat>; 2] = [None, None];
+		let mut pairs = Vec::new();
+		for beat in self.beats.iter().filter(|x| !x.fake) {
+			if let Some(prev) = last[beat.color as usize].replace(beat) {
+				if beat.time - prev.time <= window {
+					pairs.push((prev, beat));
+				}
+			}
+		}
+		pairs.sort_by(|a, b| a.0.time.total_cmp(&b.0.time));
+		pairs
+	}
+
+	/// Returns the `(start, end, color)` of each stretch of consecutive beats of a single color, from the first beat of
+	/// the stretch to the last, which lasts longer than `min_duration` seconds. Fake beats are ignored.
+	pub fn single_color_spans(&self, min_duration: f32) -> Vec<(f32, f32, NoteColor)> {
+		let mut spans = Vec::new();
+		let mut current: Option<(f32, f32, NoteColor)> = None;
+		for beat in self.beats.iter().filter(|x| !x.fake) {
+			match &mut current {
+				Some((_, end, color)) if *color == beat.color => *end = beat.time,
+				_ => {
+					spans.extend(current.filter(|(start, end, _)| end - start > min_duration));
+					current = Some((beat.time, beat.time, beat.color));
+				}
+			}
+		}
+		spans.extend(current.filter(|(start, end, _)| end - start > min_duration));
+		spans
+	}
+
+	/// Returns the first beat within `tolerance` seconds of `time` at the grid position `(x, y)`.
+	pub fn note_at_position(&self, x: f32, y: f32, time: f32, tolerance: f32) -> Option<&Beat> {
+		let first = self.beats.partition_point(|b| b.time < time - tolerance);
+		self.beats[first..]
+			.iter()
+			.take_while(|b| b.time <= time + tolerance)
+			.find(|b| at_position(b.x, b.y, x, y))
+	}
+
+	/// Returns the first bomb within `tolerance` seconds of `time` at the grid position `(x, y)`.
+	pub fn bomb_at_position(&self, x: f32, y: f32, time: f32, tolerance: f32) -> Option<&Bomb> {
+		let first = self.bombs.partition_point(|b| b.time < time - tolerance);
+		self.bombs[first..]
+			.iter()
+			.take_while(|b| b.time <= time + tolerance)
+			.find(|b| at_position(b.x, b.y, x, y))
+	}
+
+	/// Drops the fake or real objects, as requested by `handling`. Arcs can't be fake, so they're dropped when only
+	/// fakes are kept.
+	fn retain_fakes(&mut self, handling: FakeHandling) {
+		let keep_fake = match handling {
+			FakeHandling::IncludeFlagged => return,
+			FakeHandling::Exclude => false,
+			FakeHandling::Only => true
+		};
+		self.beats.retain(|x| x.fake == keep_fake);
+		self.bombs.retain(|x| x.fake == keep_fake);
+		self.obstacles.retain(|x| x.fake == keep_fake);
+		self.chains.retain(|x| x.fake == keep_fake);
+		if keep_fake {
+			self.arcs.clear();
+		}
+	}
+
+	/// Merges obstacles with the same position & size which start at most `gap` seconds after the previous one ends (or
+	/// overlap it), extending the earlier obstacle to cover both.
+	pub fn coalesce_obstacles(&mut self, gap: f32) {
+		let mut merged: Vec<Obstacle> = Vec::with_capacity(self.obstacles.len());
+		// the index in `merged` of the last obstacle with each position & size
+		let mut lanes = HashMap::new();
+		for obstacle in self.obstacles.drain(..) {
+			let lane = (obstacle.x.to_bits(), obstacle.y.to_bits(), obstacle.width.to_bits(), obstacle.height.to_bits(), obstacle.fake);
+			if let Some(&i) = lanes.get(&lane) {
+				let last: &mut Obstacle = &mut merged[i];
+				if obstacle.time - last.end_time <= gap {
+					last.end_time = last.end_time.max(obstacle.end_time);
+					last.duration = last.end_time - last.time;
+					last.duration_beats = (last.beat + last.duration_beats).max(obstacle.beat + obstacle.duration_beats) - last.beat;
+					continue;
+				}
+			}
+			lanes.insert(lane, merged.len());
+			merged.push(obstacle);
+		}
+		self.obstacles = merged;
+	}
+
+	/// Yields the obstacles which span the lane `x`, i.e. where `obstacle.x <= x < obstacle.x + obstacle.width`.
+	pub fn obstacles_covering_lane(&self, x: f32) -> impl Iterator<Item = &Obstacle> {
+		self.obstacles.iter().filter(move |o| o.x <= x && x < o.x + o.width)
+	}
+
+	/// Yields the obstacles which overlap the rectangle with its bottom left corner at `(x, y)`.
+	pub fn obstacles_covering_region(&self, x: f32, y: f32, w: f32, h: f32) -> impl Iterator<Item = &Obstacle> {
+		self.obstacles
+			.iter()
+			.filter(move |o| o.x < x + w && x < o.x + o.width && o.y < y + h && y < o.y + o.height)
+	}
+
+	/// Yields the obstacles which are [`ObstacleKind::VisionBlock`]s.
+	pub fn vision_blocks(&self) -> impl Iterator<Item = &Obstacle> {
+		self.obstacles.iter().filter(|x| x.kind() == ObstacleKind::VisionBlock)
+	}
+
+	/// Returns the total lane rotation in degrees applied to objects at `time`.
+	pub fn rotation_at(&self, time: f32) -> f32 {
+		self.rotations
+			.iter()
+			.filter(|x| x.time < time || (x.early && x.time == time))
+			.map(|x| x.rotation)
+			.sum()
+	}
+
+	/// Yields all beats & bombs in time order.
+	pub fn iter_notes(&self) -> impl Iterator<Item = AnyNote<'_>> {
+		let mut notes: Vec<_> = self.beats.iter().map(AnyNote::Beat).chain(self.bombs.iter().map(AnyNote::Bomb)).collect();
+		notes.sort_by(|a, b| a.time().total_cmp(&b.time()));
+		notes.into_iter()
+	}
+
+	/// Yields the start & end of every beat, bomb, obstacle and chain in time order. Events at the same time are
+	/// ordered by kind as listed in [`TimedEvent`].
+	pub fn iter_events_sorted(&self) -> impl Iterator<Item = TimedEvent<'_>> {
+		let mut events: Vec<_> = self
+			.beats
+			.iter()
+			.map(TimedEvent::Beat)
+			.chain(self.bombs.iter().map(TimedEvent::Bomb))
+			.chain(
+				self.obstacles
+					.iter()
+					.flat_map(|x| [TimedEvent::ObstacleStart(x), TimedEvent::ObstacleEnd(x)])
+			)
+			.chain(self.chains.iter().flat_map(|x| [TimedEvent::ChainHead(x), TimedEvent::ChainTail(x)]))
+			.collect();
+		events.sort_by(|a, b| a.time().total_cmp(&b.time()).then(a.order().cmp(&b.order())));
+		events.into_iter()
+	}
+
+	/// Rounds every object's beat to the nearest `1/precision` of a beat, recomputing its time with `bpm_tracker`.
+	pub fn snap_all(&mut self, precision: u32, bpm_tracker: &BpmTracker) {
+		for beat in &mut self.beats {
+			beat.beat = snap_beat(beat.beat, precision);
+			beat.time = bpm_tracker.beat_to_song_time(beat.beat);
+		}
+		for bomb in &mut self.bombs {
+			bomb.beat = snap_beat(bomb.beat, precision);
+			bomb.time = bpm_tracker.beat_to_song_time(bomb.beat);
+		}
+		for obstacle in &mut self.obstacles {
+			let end_beat = snap_beat(obstacle.beat + obstacle.duration_beats, precision);
+			obstacle.beat = snap_beat(obstacle.beat, precision);
+			obstacle.duration_beats = end_beat - obstacle.beat;
+			obstacle.time = bpm_tracker.beat_to_song_time(obstacle.beat);
+			obstacle.end_time = bpm_tracker.beat_to_song_time(end_beat);
+			obstacle.duration = obstacle.end_time - obstacle.time;
+		}
+		for chain in &mut self.chains {
+			chain.beat = snap_beat(chain.beat, precision);
+			chain.tail_beat = snap_beat(chain.tail_beat, precision);
+			chain.time = bpm_tracker.beat_to_song_time(chain.beat);
+			chain.tail_time = bpm_tracker.beat_to_song_time(chain.tail_beat);
 		}
+		for arc in &mut self.arcs {
+			arc.beat = snap_beat(arc.beat, precision);
+			arc.tail_beat = snap_beat(arc.tail_beat, precision);
+			arc.time = bpm_tracker.beat_to_song_time(arc.beat);
+			arc.tail_time = bpm_tracker.beat_to_song_time(arc.tail_beat);
+		}
+	}
+
+	/// Flips the map left-to-right across the standard grid, swapping colors. See [`Self::mirror_with`].
+	pub fn mirror(&mut self) {
+		self.mirror_with(GridSpec::STANDARD, &BeatmapCharacteristic::Standard);
+	}
+
+	/// Flips the map left-to-right across the given grid. Colors are swapped, except for characteristics which only
+	/// use one saber.
+	pub fn mirror_with(&mut self, grid: GridSpec, characteristic: &BeatmapCharacteristic) {
+		let last_column = grid.columns as f32 - 1.;
+		let swap_colors = *characteristic != BeatmapCharacteristic::OneSaber;
+		let mirror_color = |color: NoteColor| if swap_colors { color.opposite() } else { color };
+
+		for beat in &mut self.beats {
+			beat.x = last_column - beat.x;
+			beat.color = mirror_color(beat.color);
+			beat.direction = beat.direction.mirrored();
+			beat.angle_offset = beat.angle_offset.map(|a| -a);
+		}
+		for bomb in &mut self.bombs {
+			bomb.x = last_column - bomb.x;
+		}
+		for obstacle in &mut self.obstacles {
+			obstacle.x = grid.columns as f32 - obstacle.x - obstacle.width;
+		}
+		for chain in &mut self.chains {
+			chain.x = last_column - chain.x;
+			chain.tail_x = last_column - chain.tail_x;
+			chain.color = mirror_color(chain.color);
+			chain.direction = chain.direction.mirrored();
+		}
+		for arc in &mut self.arcs {
+			arc.x = last_column - arc.x;
+			arc.tail_x = last_column - arc.tail_x;
+			arc.color = mirror_color(arc.color);
+			arc.direction = arc.direction.mirrored();
+			arc.tail_direction = arc.tail_direction.mirrored();
+			arc.mid_anchor_mode = arc.mid_anchor_mode.mirrored();
+		}
+		for rotation in &mut self.rotations {
+			rotation.rotation = -rotation.rotation;
+		}
+	}
+
+	/// Compares two maps object-by-object, ignoring the order objects are stored in and how they were represented in
+	/// their source schema. Times & positions are compared within `tolerance`.
+	pub fn semantically_eq(&self, other: &Self, tolerance: f32) -> Result<(), Vec<Difference>> {
+		fn sorted<T>(objects: &[T], key: impl Fn(&T) -> (f32, f32, f32)) -> Vec<&T> {
+			let mut sorted: Vec<&T> = objects.iter().collect();
+			sorted.sort_by(|a, b| {
+				let (a, b) = (key(a), key(b));
+				a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)).then(a.2.total_cmp(&b.2))
+			});
+			sorted
+		}
+
+		let mut diff = DiffCollector { tolerance, differences: Vec::new() };
+
+		diff.count(ObjectKind::Beat, self.beats.len(), other.beats.len());
+		let (a, b) = (sorted(&self.beats, |x| (x.time, x.x, x.y)), sorted(&other.beats, |x| (x.time, x.x, x.y)));
+		for (i, (a, b)) in a.into_iter().zip(b).enumerate() {
+			let object = ObjectIndex::Beat(i);
+			diff.float(object, "time", a.time, b.time);
+			diff.float(object, "x", a.x, b.x);
+			diff.float(object, "y", a.y, b.y);
+			diff.float(object, "angle_offset", a.angle_offset.unwrap_or(0.), b.angle_offset.unwrap_or(0.));
+			diff.exact(object, "color", a.color, b.color);
+			diff.exact(object, "direction", a.direction, b.direction);
+			diff.exact(object, "fake", a.fake, b.fake);
+		}
+
+		diff.count(ObjectKind::Bomb, self.bombs.len(), other.bombs.len());
+		let (a, b) = (sorted(&self.bombs, |x| (x.time, x.x, x.y)), sorted(&other.bombs, |x| (x.time, x.x, x.y)));
+		for (i, (a, b)) in a.into_iter().zip(b).enumerate() {
+			let object = ObjectIndex::Bomb(i);
+			diff.float(object, "time", a.time, b.time);
+			diff.float(object, "x", a.x, b.x);
+			diff.float(object, "y", a.y, b.y);
+			diff.exact(object, "fake", a.fake, b.fake);
+		}
+
+		diff.count(ObjectKind::Obstacle, self.obstacles.len(), other.obstacles.len());
+		let (a, b) = (sorted(&self.obstacles, |x| (x.time, x.x, x.y)), sorted(&other.obstacles, |x| (x.time, x.x, x.y)));
+		for (i, (a, b)) in a.into_iter().zip(b).enumerate() {
+			let object = ObjectIndex::Obstacle(i);
+			diff.float(object, "time", a.time, b.time);
+			diff.float(object, "x", a.x, b.x);
+			diff.float(object, "y", a.y, b.y);
+			diff.float(object, "duration", a.duration, b.duration);
+			diff.float(object, "width", a.width, b.width);
+			diff.float(object, "height", a.height, b.height);
+			diff.exact(object, "fake", a.fake, b.fake);
+		}
+
+		diff.count(ObjectKind::Chain, self.chains.len(), other.chains.len());
+		let (a, b) = (sorted(&self.chains, |x| (x.time, x.x, x.y)), sorted(&other.chains, |x| (x.time, x.x, x.y)));
+		for (i, (a, b)) in a.into_iter().zip(b).enumerate() {
+			let object = ObjectIndex::Chain(i);
+			diff.float(object, "time", a.time, b.time);
+			diff.float(object, "x", a.x, b.x);
+			diff.float(object, "y", a.y, b.y);
+			diff.float(object, "tail_time", a.tail_time, b.tail_time);
+			diff.float(object, "tail_x", a.tail_x, b.tail_x);
+			diff.float(object, "tail_y", a.tail_y, b.tail_y);
+			diff.float(object, "squish_factor", a.squish_factor, b.squish_factor);
+			diff.exact(object, "color", a.color, b.color);
+			diff.exact(object, "direction", a.direction, b.direction);
+			diff.exact(object, "num_slices", a.num_slices, b.num_slices);
+			diff.exact(object, "fake", a.fake, b.fake);
+		}
+
+		diff.count(ObjectKind::Arc, self.arcs.len(), other.arcs.len());
+		let (a, b) = (sorted(&self.arcs, |x| (x.time, x.x, x.y)), sorted(&other.arcs, |x| (x.time, x.x, x.y)));
+		for (i, (a, b)) in a.into_iter().zip(b).enumerate() {
+			let object = ObjectIndex::Arc(i);
+			diff.float(object, "time", a.time, b.time);
+			diff.float(object, "x", a.x, b.x);
+			diff.float(object, "y", a.y, b.y);
+			diff.float(object, "head_multiplier", a.head_multiplier, b.head_multiplier);
+			diff.float(object, "tail_time", a.tail_time, b.tail_time);
+			diff.float(object, "tail_x", a.tail_x, b.tail_x);
+			diff.float(object, "tail_y", a.tail_y, b.tail_y);
+			diff.float(object, "tail_multiplier", a.tail_multiplier, b.tail_multiplier);
+			diff.exact(object, "color", a.color, b.color);
+			diff.exact(object, "direction", a.direction, b.direction);
+			diff.exact(object, "tail_direction", a.tail_direction, b.tail_direction);
+			diff.exact(object, "mid_anchor_mode", a.mid_anchor_mode, b.mid_anchor_mode);
+		}
+
+		if diff.differences.is_empty() { Ok(()) } else { Err(diff.differences) }
+	}
+
+	/// Checks the map for common mistakes: objects outside of the standard grid, overlapping notes, notes inside
+	/// obstacles, bombs on top of notes & chains which end before they start.
+	///
+	/// The grid isn't checked for [mapping extensions](Self::mapping_extensions) maps, which are expected to leave it.
+	/// Noodle Extensions positions live in custom data, which isn't kept.
+	pub fn validate(&self) -> Vec<Issue> {
+		self.validate_for(GridSpec::STANDARD, &BeatmapCharacteristic::Standard)
+	}
+
+	/// [`Self::validate`], along with [`Self::validate_with`]'s checks for the given grid & characteristic.
+	pub(crate) fn validate_for(&self, grid: GridSpec, characteristic: &BeatmapCharacteristic) -> Vec<Issue> {
+		const EPSILON: f32 = 1e-3;
+
+		let mut issues = self.validate_with(grid, characteristic);
+		if self.mapping_extensions {
+			issues.retain(|x| x.kind != IssueKind::OutOfGrid);
+		}
+
+		for (i, beat) in self.beats.iter().enumerate() {
+			let overlaps = self.beats[i + 1..]
+				.iter()
+				.take_while(|b| b.time - beat.time < EPSILON)
+				.any(|b| at_position(b.x, b.y, beat.x, beat.y));
+			if overlaps {
+				issues.push(Issue::new(beat.time, ObjectIndex::Beat(i), IssueKind::OverlappingNotes));
+			}
+		}
+		for (i, obstacle) in self.obstacles.iter().enumerate() {
+			let first = self.beats.partition_point(|b| b.time < obstacle.time);
+			for (j, beat) in self.beats.iter().enumerate().skip(first).take_while(|(_, b)| b.time < obstacle.end_time) {
+				let inside = obstacle.x <= beat.x && beat.x < obstacle.x + obstacle.width && obstacle.y <= beat.y && beat.y < obstacle.y + obstacle.height;
+				if inside {
+					issues.push(Issue::new(beat.time, ObjectIndex::Beat(j), IssueKind::NoteInObstacle(i)));
+				}
+			}
+		}
+		for (i, bomb) in self.bombs.iter().enumerate() {
+			if self.note_at_position(bomb.x, bomb.y, bomb.time, EPSILON).is_some() {
+				issues.push(Issue::new(bomb.time, ObjectIndex::Bomb(i), IssueKind::BombOnNote));
+			}
+		}
+		for (i, chain) in self.chains.iter().enumerate() {
+			if chain.tail_time < chain.time {
+				issues.push(Issue::new(chain.time, ObjectIndex::Chain(i), IssueKind::ReversedChain));
+			}
+		}
+		issues
+	}
+
+	/// Checks the map for objects which don't make sense for the given grid & characteristic.
+	pub fn validate_with(&self, grid: GridSpec, characteristic: &BeatmapCharacteristic) -> Vec<Issue> {
+		let mut issues = Vec::new();
+		for (i, beat) in self.beats.iter().enumerate() {
+			if !grid.contains(beat.x, beat.y) {
+				issues.push(Issue::new(beat.time, ObjectIndex::Beat(i), IssueKind::OutOfGrid));
+			}
+			if *characteristic == BeatmapCharacteristic::NoArrows && beat.direction != NoteDirection::Any {
+				issues.push(Issue::new(beat.time, ObjectIndex::Beat(i), IssueKind::DirectionalNote));
+			}
+		}
+		for (i, bomb) in self.bombs.iter().enumerate() {
+			if !grid.contains(bomb.x, bomb.y) {
+				issues.push(Issue::new(bomb.time, ObjectIndex::Bomb(i), IssueKind::OutOfGrid));
+			}
+		}
+		for (i, obstacle) in self.obstacles.iter().enumerate() {
+			if obstacle.x < 0. || obstacle.x + obstacle.width > grid.columns as f32 {
+				issues.push(Issue::new(obstacle.time, ObjectIndex::Obstacle(i), IssueKind::OutOfGrid));
+			}
+		}
+		for (i, chain) in self.chains.iter().enumerate() {
+			if !grid.contains(chain.x, chain.y) || !grid.contains(chain.tail_x, chain.tail_y) {
+				issues.push(Issue::new(chain.time, ObjectIndex::Chain(i), IssueKind::OutOfGrid));
+			}
+		}
+		for (i, arc) in self.arcs.iter().enumerate() {
+			if !grid.contains(arc.x, arc.y) || !grid.contains(arc.tail_x, arc.tail_y) {
+				issues.push(Issue::new(arc.time, ObjectIndex::Arc(i), IssueKind::OutOfGrid));
+			}
+		}
+		issues
 	}
 }
 
-#[derive(Debug, Clone)]
-pub struct Chain {
-	beat: f32,
-	pub time: f32,
-	pub x: f32,
-	pub y: f32,
-	pub color: NoteColor,
-	pub direction: NoteDirection,
-	tail_beat: f32,
-	pub tail_time: f32,
-	pub tail_x: f32,
-	pub tail_y: f32,
-	pub num_slices: u8,
-	pub squish_factor: f32
+fn at_position(x: f32, y: f32, target_x: f32, target_y: f32) -> bool {
+	const EPSILON: f32 = 1e-3;
+	(x - target_x).abs() < EPSILON && (y - target_y).abs() < EPSILON
 }
 
-impl From<v3::BurstSlider> for Chain {
-	fn from(value: v3::BurstSlider) -> Self {
-		Self {
-			beat: value.beat,
-			time: 0.0,
-			x: value.x,
-			y: value.y,
-			color: value.color.into(),
-			direction: value.direction.into(),
-			tail_beat: value.tail_beat,
-			tail_time: 0.0,
-			tail_x: value.tail_x,
-			tail_y: value.tail_y,
-			num_slices: value.num_slices,
-			squish_factor: value.squish_amount
-		}
+/// Which fake (non-interactable) objects to keep when converting a beatmap. Fakes don't count towards scoring, but are
+/// needed to render the map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FakeHandling {
+	#[default]
+	Exclude,
+	/// Keep fakes alongside the real objects, marked with `fake`.
+	IncludeFlagged,
+	/// Keep only the fakes.
+	Only
+}
+
+/// Options for converting a beatmap with e.g. [`Beatmap::from_any_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConversionOptions {
+	pub include_fake: FakeHandling
+}
+
+/// What [`Beatmap::shift_time`] does with times which would become negative.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NegativeTimes {
+	/// Leave objects before the start of the song.
+	Allow,
+	/// Move objects to the start of the song, shortening obstacles which would start before it.
+	Clamp,
+	/// Fail without shifting anything.
+	Error
+}
+
+#[derive(Debug, Error)]
+#[error("Shifting by {offset} seconds would move objects before the start of the song")]
+pub struct NegativeTimeError {
+	pub offset: f32
+}
+
+/// An error converting a beatmap to the standard representation.
+#[derive(Debug, Error)]
+pub enum ConversionError {
+	#[error("Note {index} at beat {beat} is neither a color note nor a bomb")]
+	InvalidNote { index: usize, beat: f32 },
+	#[error("{0:?} references data which doesn't exist")]
+	MissingData(ObjectIndex),
+	#[error("Can't convert a {from:?} beatmap to version {to}")]
+	UnsupportedVersion { from: SchemaVersion, to: u8 },
+	#[error("Converting to version {to} would drop the beatmap's {dropped}")]
+	LossyConversion { to: u8, dropped: &'static str }
+}
+
+/// The dimensions of the note grid a beatmap is played on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GridSpec {
+	pub columns: u8,
+	pub rows: u8
+}
+
+impl GridSpec {
+	pub const STANDARD: GridSpec = GridSpec { columns: 4, rows: 3 };
+
+	/// Returns whether the given note position lies on the grid.
+	pub fn contains(&self, x: f32, y: f32) -> bool {
+		x >= 0. && x <= self.columns as f32 - 1. && y >= 0. && y <= self.rows as f32 - 1.
 	}
 }
 
-struct BpmEvent {
-	song_time: f32,
-	beats: f32
+impl Default for GridSpec {
+	fn default() -> Self {
+		Self::STANDARD
+	}
 }
 
-impl From<v2::BpmEvent> for BpmEvent {
-	fn from(value: v2::BpmEvent) -> Self {
-		Self {
-			song_time: value.song_time,
-			beats: value.beats
+/// An object's position on the standard grid: a whole lane (0-3) & layer (0-2), or a precise position for anything
+/// off the grid, as used by mapping extensions & noodle maps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GridPosition {
+	Grid { lane: u8, layer: u8 },
+	Precise { x: f32, y: f32 }
+}
+
+impl GridPosition {
+	pub fn new(x: f32, y: f32) -> Self {
+		match (grid_index(x, GridSpec::STANDARD.columns), grid_index(y, GridSpec::STANDARD.rows)) {
+			(Some(lane), Some(layer)) => Self::Grid { lane, layer },
+			_ => Self::Precise { x, y }
 		}
 	}
-}
 
-impl From<v3::BpmEvent> for BpmEvent {
-	fn from(value: v3::BpmEvent) -> Self {
-		Self {
-			song_time: value.song_time,
-			beats: value.beats
+	pub fn lane(&self) -> Option<u8> {
+		match self {
+			Self::Grid { lane, .. } => Some(*lane),
+			Self::Precise { .. } => None
+		}
+	}
+
+	pub fn layer(&self) -> Option<u8> {
+		match self {
+			Self::Grid { layer, .. } => Some(*layer),
+			Self::Precise { .. } => None
 		}
 	}
 }
 
-#[derive(Clone)]
-struct BpmChangeEvent {
-	bpm: f32,
-	start_time: f32,
-	start_bpm_time: f32
+/// Returns `value` as an index into `0..count` if it's a whole number in range.
+fn grid_index(value: f32, count: u8) -> Option<u8> {
+	(value.fract() == 0. && value >= 0. && value < count as f32).then_some(value as u8)
 }
 
-struct BpmTracker {
-	base_bpm: f32,
-	changes: Vec<BpmChangeEvent>
+/// Refers to an object in a [`Beatmap`] by its index into the corresponding vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectIndex {
+	Beat(usize),
+	Bomb(usize),
+	Obstacle(usize),
+	Chain(usize),
+	Arc(usize)
 }
 
-impl BpmTracker {
-	pub fn new(start_bpm: f32, events: Vec<BpmEvent>) -> Self {
-		let mut base_bpm = start_bpm;
-		let mut changes = Vec::new();
-		if !events.is_empty() {
-			let mut n_base = 0;
-			if events[0].song_time == 0. {
-				n_base = 1;
-				base_bpm = events[0].beats;
-				changes.push(BpmChangeEvent {
-					bpm: base_bpm,
-					start_time: 0.,
-					start_bpm_time: 0.
-				});
-			}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectKind {
+	Beat,
+	Bomb,
+	Obstacle,
+	Chain,
+	Arc
+}
 
-			for event in events.iter().skip(n_base) {
-				let last_change = changes.get(changes.len() - 1).cloned().unwrap_or(BpmChangeEvent {
-					bpm: base_bpm,
-					start_time: 0.,
-					start_bpm_time: 0.
-				});
-				changes.push(BpmChangeEvent {
-					bpm: event.beats,
-					start_bpm_time: event.song_time,
-					start_time: last_change.start_time + ((event.song_time - last_change.start_bpm_time) / last_change.bpm) * 60.0
-				});
-			}
+/// A mismatch found by [`Beatmap::semantically_eq`]. Objects are indexed in time order, not storage order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+	Count {
+		kind: ObjectKind,
+		left: usize,
+		right: usize
+	},
+	Field {
+		object: ObjectIndex,
+		field: &'static str,
+		left: String,
+		right: String
+	}
+}
+
+struct DiffCollector {
+	tolerance: f32,
+	differences: Vec<Difference>
+}
+
+impl DiffCollector {
+	fn count(&mut self, kind: ObjectKind, left: usize, right: usize) {
+		if left != right {
+			self.differences.push(Difference::Count { kind, left, right });
 		}
-		Self { base_bpm, changes }
 	}
 
-	pub fn beat_to_song_time(&self, time: f32) -> f32 {
-		if self.changes.is_empty() {
-			return time * (60.0 / self.base_bpm);
+	fn float(&mut self, object: ObjectIndex, field: &'static str, left: f32, right: f32) {
+		if (left - right).abs() > self.tolerance {
+			self.differences.push(Difference::Field {
+				object,
+				field,
+				left: left.to_string(),
+				right: right.to_string()
+			});
 		}
+	}
 
-		let mut i = 0;
-		while i < self.changes.len() - 1 && self.changes[i + 1].start_bpm_time < time {
-			i += 1;
+	fn exact<T: PartialEq + Debug>(&mut self, object: ObjectIndex, field: &'static str, left: T, right: T) {
+		if left != right {
+			self.differences.push(Difference::Field {
+				object,
+				field,
+				left: format!("{left:?}"),
+				right: format!("{right:?}")
+			});
 		}
-		let prev_bpm_change = &self.changes[i];
-		prev_bpm_change.start_time + ((time - prev_bpm_change.start_bpm_time) / prev_bpm_change.bpm) * 60.0
 	}
 }
 
-#[cfg(test)]
-mod tests {
-	use super::Beatmap;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IssueKind {
+	/// The object is placed (at least partially) outside of the grid.
+	OutOfGrid,
+	/// The note has a cut direction in a characteristic where all notes should be dot notes.
+	DirectionalNote,
+	/// The note is at the same time & position as a later note.
+	OverlappingNotes,
+	/// The note is inside the obstacle at the given index.
+	NoteInObstacle(usize),
+	/// The bomb is at the same time & position as a note.
+	BombOnNote,
+	/// The chain's tail is before its head.
+	ReversedChain
+}
 
-	#[test]
-	fn test_mapping_extensions_ok() {
-		assert!(Beatmap::from_file("tests/data/maps/1579c_ExpertPlusStandard.dat", 222.0).is_ok());
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+	pub time: f32,
+	pub object: ObjectIndex,
+	pub kind: IssueKind
+}
+
+impl Issue {
+	fn new(time: f32, object: ObjectIndex, kind: IssueKind) -> Self {
+		Self { time, object, kind }
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum NoteColor {
+	Red = 0,
+	Blue = 1
+}
+
+impl NoteColor {
+	pub fn opposite(&self) -> Self {
+		match self {
+			Self::Red => Self::Blue,
+			Self::Blue => Self::Red
+		}
+	}
+}
+
+impl TryFrom<v2::NoteType> for NoteColor {
+	type Error = v2::NoteType;
+
+	fn try_from(value: v2::NoteType) -> Result<Self, Self::Error> {
+		match value {
+			v2::NoteType::Red => Ok(NoteColor::Red),
+			v2::NoteType::Blue => Ok(NoteColor::Blue),
+			v => Err(v)
+		}
+	}
+}
+
+impl From<v3::NoteColor> for NoteColor {
+	fn from(value: v3::NoteColor) -> Self {
+		match value {
+			v3::NoteColor::Red => NoteColor::Red,
+			v3::NoteColor::Blue => NoteColor::Blue
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum NoteDirection {
+	Up = 0,
+	Down = 1,
+	Left = 2,
+	Right = 3,
+	UpLeft = 4,
+	UpRight = 5,
+	DownLeft = 6,
+	DownRight = 7,
+	Any = 8
+}
+
+impl NoteDirection {
+	/// Returns this direction flipped left-to-right.
+	pub fn mirrored(&self) -> Self {
+		match self {
+			Self::Left => Self::Right,
+			Self::Right => Self::Left,
+			Self::UpLeft => Self::UpRight,
+			Self::UpRight => Self::UpLeft,
+			Self::DownLeft => Self::DownRight,
+			Self::DownRight => Self::DownLeft,
+			d => *d
+		}
+	}
+
+	/// Returns the rotation of the cut direction in degrees, as used by the game: down is 0, right is 90 & left is -90.
+	/// Returns `None` for [`Self::Any`].
+	pub fn angle(&self) -> Option<f32> {
+		match self {
+			Self::Up => Some(180.),
+			Self::Down => Some(0.),
+			Self::Left => Some(-90.),
+			Self::Right => Some(90.),
+			Self::UpLeft => Some(-135.),
+			Self::UpRight => Some(135.),
+			Self::DownLeft => Some(-45.),
+			Self::DownRight => Some(45.),
+			Self::Any => None
+		}
+	}
+
+	/// Returns the cut direction in degrees clockwise from up, in `0..360`, or `None` for [`Self::Any`]. Unlike
+	/// [`Self::angle`] this isn't the game's note rotation, but the direction the saber moves in.
+	pub fn to_degrees(&self) -> Option<f32> {
+		match self {
+			Self::Up => Some(0.),
+			Self::UpRight => Some(45.),
+			Self::Right => Some(90.),
+			Self::DownRight => Some(135.),
+			Self::Down => Some(180.),
+			Self::DownLeft => Some(225.),
+			Self::Left => Some(270.),
+			Self::UpLeft => Some(315.),
+			Self::Any => None
+		}
+	}
+
+	/// Returns the nearest cut direction to `degrees` clockwise from up (see [`Self::to_degrees`]). Non-finite angles
+	/// give [`Self::Any`].
+	pub fn from_degrees(degrees: f32) -> Self {
+		if !degrees.is_finite() {
+			return Self::Any;
+		}
+		const CLOCKWISE: [NoteDirection; 8] = [
+			NoteDirection::Up,
+			NoteDirection::UpRight,
+			NoteDirection::Right,
+			NoteDirection::DownRight,
+			NoteDirection::Down,
+			NoteDirection::DownLeft,
+			NoteDirection::Left,
+			NoteDirection::UpLeft
+		];
+		CLOCKWISE[((degrees / 45.).round() as i64).rem_euclid(8) as usize]
+	}
+
+	/// Returns the unit vector of the cut direction on the grid, with `+x` to the right and `+y` up, or `None` for
+	/// [`Self::Any`].
+	pub fn to_unit_vector(&self) -> Option<Vec2> {
+		const D: f32 = std::f32::consts::FRAC_1_SQRT_2;
+		match self {
+			Self::Up => Some(Vec2::new(0., 1.)),
+			Self::Down => Some(Vec2::new(0., -1.)),
+			Self::Left => Some(Vec2::new(-1., 0.)),
+			Self::Right => Some(Vec2::new(1., 0.)),
+			Self::UpLeft => Some(Vec2::new(-D, D)),
+			Self::UpRight => Some(Vec2::new(D, D)),
+			Self::DownLeft => Some(Vec2::new(-D, -D)),
+			Self::DownRight => Some(Vec2::new(D, -D)),
+			Self::Any => None
+		}
+	}
+}
+
+impl From<v2::NoteDirection> for NoteDirection {
+	fn from(value: v2::NoteDirection) -> Self {
+		match value {
+			v2::NoteDirection::Up => NoteDirection::Up,
+			v2::NoteDirection::Down => NoteDirection::Down,
+			v2::NoteDirection::Left => NoteDirection::Left,
+			v2::NoteDirection::Right => NoteDirection::Right,
+			v2::NoteDirection::UpLeft => NoteDirection::UpLeft,
+			v2::NoteDirection::UpRight => NoteDirection::UpRight,
+			v2::NoteDirection::DownLeft => NoteDirection::DownLeft,
+			v2::NoteDirection::DownRight => NoteDirection::DownRight,
+			v2::NoteDirection::Any => NoteDirection::Any,
+			// close enough approximation for mapping extensions' 360 degree note rotation
+			v2::NoteDirection::Precise(value) => util::split_precise_direction(value as u32).0
+		}
+	}
+}
+
+impl From<v3::NoteDirection> for NoteDirection {
+	fn from(value: v3::NoteDirection) -> Self {
+		match value {
+			v3::NoteDirection::Up => NoteDirection::Up,
+			v3::NoteDirection::Down => NoteDirection::Down,
+			v3::NoteDirection::Left => NoteDirection::Left,
+			v3::NoteDirection::Right => NoteDirection::Right,
+			v3::NoteDirection::UpLeft => NoteDirection::UpLeft,
+			v3::NoteDirection::UpRight => NoteDirection::UpRight,
+			v3::NoteDirection::DownLeft => NoteDirection::DownLeft,
+			v3::NoteDirection::DownRight => NoteDirection::DownRight,
+			v3::NoteDirection::Any => NoteDirection::Any,
+			// close enough approximation for mapping extensions' 360 degree note rotation
+			v3::NoteDirection::Precise(value) => util::split_precise_direction(value as u32).0
+		}
+	}
+}
+
+impl From<NoteDirection> for v2::NoteDirection {
+	fn from(value: NoteDirection) -> Self {
+		match value {
+			NoteDirection::Up => v2::NoteDirection::Up,
+			NoteDirection::Down => v2::NoteDirection::Down,
+			NoteDirection::Left => v2::NoteDirection::Left,
+			NoteDirection::Right => v2::NoteDirection::Right,
+			NoteDirection::UpLeft => v2::NoteDirection::UpLeft,
+			NoteDirection::UpRight => v2::NoteDirection::UpRight,
+			NoteDirection::DownLeft => v2::NoteDirection::DownLeft,
+			NoteDirection::DownRight => v2::NoteDirection::DownRight,
+			NoteDirection::Any => v2::NoteDirection::Any
+		}
+	}
+}
+
+impl From<NoteDirection> for v3::NoteDirection {
+	fn from(value: NoteDirection) -> Self {
+		match value {
+			NoteDirection::Up => v3::NoteDirection::Up,
+			NoteDirection::Down => v3::NoteDirection::Down,
+			NoteDirection::Left => v3::NoteDirection::Left,
+			NoteDirection::Right => v3::NoteDirection::Right,
+			NoteDirection::UpLeft => v3::NoteDirection::UpLeft,
+			NoteDirection::UpRight => v3::NoteDirection::UpRight,
+			NoteDirection::DownLeft => v3::NoteDirection::DownLeft,
+			NoteDirection::DownRight => v3::NoteDirection::DownRight,
+			NoteDirection::Any => v3::NoteDirection::Any
+		}
+	}
+}
+
+/// A beat or a bomb, as yielded by [`Beatmap::iter_notes`].
+#[derive(Debug, Clone, Copy)]
+pub enum AnyNote<'a> {
+	Beat(&'a Beat),
+	Bomb(&'a Bomb)
+}
+
+impl AnyNote<'_> {
+	pub fn time(&self) -> f32 {
+		match self {
+			Self::Beat(x) => x.time,
+			Self::Bomb(x) => x.time
+		}
+	}
+
+	pub fn position(&self) -> (f32, f32) {
+		match self {
+			Self::Beat(x) => (x.x, x.y),
+			Self::Bomb(x) => (x.x, x.y)
+		}
+	}
+}
+
+/// A point in time where something happens in a beatmap, as yielded by [`Beatmap::iter_events_sorted`].
+#[derive(Debug, Clone, Copy)]
+pub enum TimedEvent<'a> {
+	Beat(&'a Beat),
+	Bomb(&'a Bomb),
+	ObstacleStart(&'a Obstacle),
+	ObstacleEnd(&'a Obstacle),
+	ChainHead(&'a Chain),
+	ChainTail(&'a Chain)
+}
+
+impl TimedEvent<'_> {
+	pub fn time(&self) -> f32 {
+		match self {
+			Self::Beat(x) => x.time,
+			Self::Bomb(x) => x.time,
+			Self::ObstacleStart(x) => x.time,
+			Self::ObstacleEnd(x) => x.end_time,
+			Self::ChainHead(x) => x.time,
+			Self::ChainTail(x) => x.tail_time
+		}
+	}
+
+	fn order(&self) -> u8 {
+		match self {
+			Self::Beat(_) => 0,
+			Self::Bomb(_) => 1,
+			Self::ObstacleStart(_) => 2,
+			Self::ObstacleEnd(_) => 3,
+			Self::ChainHead(_) => 4,
+			Self::ChainTail(_) => 5
+		}
+	}
+}
+
+/// Any object in a beatmap, as yielded by iterating over a [`Beatmap`].
+#[derive(Debug, Clone)]
+pub enum BeatmapObject {
+	Beat(Beat),
+	Bomb(Bomb),
+	Obstacle(Obstacle),
+	Chain(Chain),
+	Arc(Arc)
+}
+
+impl BeatmapObject {
+	pub fn time(&self) -> f32 {
+		match self {
+			Self::Beat(x) => x.time,
+			Self::Bomb(x) => x.time,
+			Self::Obstacle(x) => x.time,
+			Self::Chain(x) => x.time,
+			Self::Arc(x) => x.time
+		}
+	}
+}
+
+/// Any object in a beatmap, as yielded by iterating over a `&`[`Beatmap`].
+#[derive(Debug, Clone, Copy)]
+pub enum BeatmapObjectRef<'a> {
+	Beat(&'a Beat),
+	Bomb(&'a Bomb),
+	Obstacle(&'a Obstacle),
+	Chain(&'a Chain),
+	Arc(&'a Arc)
+}
+
+impl BeatmapObjectRef<'_> {
+	pub fn time(&self) -> f32 {
+		match self {
+			Self::Beat(x) => x.time,
+			Self::Bomb(x) => x.time,
+			Self::Obstacle(x) => x.time,
+			Self::Chain(x) => x.time,
+			Self::Arc(x) => x.time
+		}
+	}
+
+	/// The time the object ends: the end of an obstacle, the tail of a chain or arc, or the time of a note.
+	pub fn end_time(&self) -> f32 {
+		match self {
+			Self::Obstacle(x) => x.end_time,
+			Self::Chain(x) => x.tail_time,
+			Self::Arc(x) => x.tail_time,
+			_ => self.time()
+		}
+	}
+}
+
+/// Yields every object in time order. Objects at the same time are ordered beats, bombs, obstacles, chains, then arcs.
+impl IntoIterator for Beatmap {
+	type Item = BeatmapObject;
+	type IntoIter = std::vec::IntoIter<BeatmapObject>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		let mut objects: Vec<_> = self
+			.beats
+			.into_iter()
+			.map(BeatmapObject::Beat)
+			.chain(self.bombs.into_iter().map(BeatmapObject::Bomb))
+			.chain(self.obstacles.into_iter().map(BeatmapObject::Obstacle))
+			.chain(self.chains.into_iter().map(BeatmapObject::Chain))
+			.chain(self.arcs.into_iter().map(BeatmapObject::Arc))
+			.collect();
+		objects.sort_by(|a, b| a.time().total_cmp(&b.time()));
+		objects.into_iter()
+	}
+}
+
+/// Yields every object in time order, see [`Beatmap::objects`].
+impl<'a> IntoIterator for &'a Beatmap {
+	type Item = BeatmapObjectRef<'a>;
+	type IntoIter = Objects<'a>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.objects()
+	}
+}
+
+/// Object counts & note density for a beatmap, created by [`Beatmap::stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapStats {
+	pub notes: usize,
+	pub red_notes: usize,
+	pub blue_notes: usize,
+	pub bombs: usize,
+	pub obstacles: usize,
+	pub chains: usize,
+	/// Notes per second over the span from the first to the last object.
+	pub nps: f32,
+	/// The length in seconds of each bucket in `density`.
+	pub bucket_size: f32,
+	/// The number of notes in each bucket, starting from the beginning of the song.
+	pub density: Vec<u32>
+}
+
+impl MapStats {
+	pub const DEFAULT_BUCKET_SIZE: f32 = 1.;
+
+	/// Returns the highest number of notes per second in any bucket.
+	pub fn peak_nps(&self) -> f32 {
+		self.density.iter().max().map_or(0., |&x| x as f32 / self.bucket_size)
+	}
+}
+
+/// Per-color note statistics for a beatmap, created by [`Beatmap::color_stats`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorStats {
+	pub red: ColorNoteStats,
+	pub blue: ColorNoteStats,
+	/// The fraction of notes which are red (left hand), or 0.5 for a map without notes.
+	pub balance: f32
+}
+
+impl ColorStats {
+	pub fn get(&self, color: NoteColor) -> &ColorNoteStats {
+		match color {
+			NoteColor::Red => &self.red,
+			NoteColor::Blue => &self.blue
+		}
+	}
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ColorNoteStats {
+	pub notes: usize,
+	/// The number of chain slices after the head.
+	pub chain_links: usize,
+	/// Notes per second over the span from the first to the last object of the map.
+	pub nps: f32,
+	/// The number of notes with each cut direction, indexed by `NoteDirection as usize`.
+	pub directions: [usize; 9]
+}
+
+impl ColorNoteStats {
+	pub fn direction_count(&self, direction: NoteDirection) -> usize {
+		self.directions[direction as usize]
+	}
+}
+
+/// The objects in a time window, created by [`Beatmap::objects_between`].
+#[derive(Debug, Clone)]
+pub struct TimeWindow<'a> {
+	pub beats: &'a [Beat],
+	pub bombs: &'a [Bomb],
+	pub chains: &'a [Chain],
+	pub arcs: &'a [Arc],
+	pub obstacles: ActiveObstacles<'a>
+}
+
+impl<'a> TimeWindow<'a> {
+	fn new(map: &'a Beatmap, start: f32, end: f32, obstacle_candidates: &'a [Obstacle]) -> Self {
+		fn window<T>(objects: &[T], start: f32, end: f32, time: impl Fn(&T) -> f32) -> &[T] {
+			let first = objects.partition_point(|x| time(x) < start);
+			let last = objects.partition_point(|x| time(x) < end).max(first);
+			&objects[first..last]
+		}
+
+		Self {
+			beats: window(&map.beats, start, end, |x| x.time),
+			bombs: window(&map.bombs, start, end, |x| x.time),
+			chains: window(&map.chains, start, end, |x| x.time),
+			arcs: window(&map.arcs, start, end, |x| x.time),
+			obstacles: ActiveObstacles {
+				candidates: obstacle_candidates.iter(),
+				start
+			}
+		}
+	}
+}
+
+/// The obstacles which are active during a [`TimeWindow`], in order of their start time.
+#[derive(Debug, Clone)]
+pub struct ActiveObstacles<'a> {
+	candidates: std::slice::Iter<'a, Obstacle>,
+	start: f32
+}
+
+impl<'a> Iterator for ActiveObstacles<'a> {
+	type Item = &'a Obstacle;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let start = self.start;
+		self.candidates.find(|x| x.end_time > start)
+	}
+}
+
+/// An index over a [`Beatmap`] for fast time window queries, created by [`Beatmap::time_index`].
+#[derive(Debug, Clone)]
+pub struct TimeIndex<'a> {
+	map: &'a Beatmap,
+	/// The latest end time of the obstacles up to & including each index, which is sorted as it never decreases.
+	obstacle_ends: Vec<f32>
+}
+
+impl<'a> TimeIndex<'a> {
+	/// Like [`Beatmap::objects_between`], but only visits the obstacles from the first one which is still active at
+	/// `start` to the last one which starts before `end`.
+	pub fn objects_between(&self, start: f32, end: f32) -> TimeWindow<'a> {
+		let obstacles = &self.map.obstacles;
+		let first = self.obstacle_ends.partition_point(|&x| x <= start);
+		let last = obstacles.partition_point(|x| x.time < end).max(first);
+		TimeWindow::new(self.map, start, end, &obstacles[first..last])
+	}
+}
+
+/// A k-way merge over a [`Beatmap`]'s objects, created by [`Beatmap::objects`].
+#[derive(Debug, Clone)]
+pub struct Objects<'a> {
+	beats: &'a [Beat],
+	bombs: &'a [Bomb],
+	obstacles: &'a [Obstacle],
+	chains: &'a [Chain],
+	arcs: &'a [Arc]
+}
+
+impl<'a> Iterator for Objects<'a> {
+	type Item = BeatmapObjectRef<'a>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		fn take<'a, T>(objects: &mut &'a [T]) -> &'a T {
+			let (first, rest) = objects.split_first().unwrap();
+			*objects = rest;
+			first
+		}
+
+		let heads = [
+			self.beats.first().map(|x| x.time),
+			self.bombs.first().map(|x| x.time),
+			self.obstacles.first().map(|x| x.time),
+			self.chains.first().map(|x| x.time),
+			self.arcs.first().map(|x| x.time)
+		];
+		// `min_by` returns the first of equal elements, which gives the tie order
+		let (next, _) = heads
+			.into_iter()
+			.enumerate()
+			.filter_map(|(i, time)| Some((i, time?)))
+			.min_by(|a, b| a.1.total_cmp(&b.1))?;
+		Some(match next {
+			0 => BeatmapObjectRef::Beat(take(&mut self.beats)),
+			1 => BeatmapObjectRef::Bomb(take(&mut self.bombs)),
+			2 => BeatmapObjectRef::Obstacle(take(&mut self.obstacles)),
+			3 => BeatmapObjectRef::Chain(take(&mut self.chains)),
+			_ => BeatmapObjectRef::Arc(take(&mut self.arcs))
+		})
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let len = self.beats.len() + self.bombs.len() + self.obstacles.len() + self.chains.len() + self.arcs.len();
+		(len, Some(len))
+	}
+}
+
+impl ExactSizeIterator for Objects<'_> {}
+
+#[derive(Debug, Clone)]
+pub struct Beat {
+	beat: f32,
+	pub time: f32,
+	pub x: f32,
+	pub y: f32,
+	pub angle_offset: Option<f32>,
+	pub color: NoteColor,
+	pub direction: NoteDirection,
+	/// Whether this is a fake (non-interactable) note.
+	pub fake: bool
+}
+
+impl Beat {
+	pub fn beat(&self) -> f32 {
+		self.beat
+	}
+
+	pub fn position(&self) -> GridPosition {
+		GridPosition::new(self.x, self.y)
+	}
+
+	/// Returns the lane (0-3) of the note, or `None` if it's not in a whole lane on the grid.
+	pub fn lane(&self) -> Option<u8> {
+		grid_index(self.x, GridSpec::STANDARD.columns)
+	}
+
+	/// Returns the layer (0-2) of the note, or `None` if it's not in a whole layer on the grid.
+	pub fn layer(&self) -> Option<u8> {
+		grid_index(self.y, GridSpec::STANDARD.rows)
+	}
+
+	/// Returns [`Self::effective_cut_degrees`] as the game's note rotation (see [`NoteDirection::angle`]) in
+	/// `-180..=180`, or `None` for dot notes, which can be cut from any direction even if the offset rotates them.
+	pub fn cut_angle(&self) -> Option<f32> {
+		if self.direction == NoteDirection::Any {
+			return None;
+		}
+		self.effective_cut_degrees().map(|degrees| 180. - degrees)
+	}
+
+	/// Returns the direction the note has to be cut in, in degrees clockwise from up (see
+	/// [`NoteDirection::to_degrees`]), with its angle offset applied. The offset rotates notes counterclockwise, and
+	/// rotates dot notes from down as the game does. Returns `None` for dot notes without an offset.
+	pub fn effective_cut_degrees(&self) -> Option<f32> {
+		let base = match (self.direction, self.angle_offset) {
+			(NoteDirection::Any, None) => return None,
+			(NoteDirection::Any, Some(_)) => NoteDirection::Down.to_degrees()?,
+			(direction, _) => direction.to_degrees()?
+		};
+		Some((base - self.angle_offset.unwrap_or(0.)).rem_euclid(360.))
+	}
+
+	/// Returns the unit vector of [`Self::effective_cut_degrees`], with `+x` to the right and `+y` up.
+	pub fn effective_cut_vector(&self) -> Option<Vec2> {
+		let radians = self.effective_cut_degrees()?.to_radians();
+		Some(Vec2::new(radians.sin(), radians.cos()))
+	}
+}
+
+impl TryFrom<v2::Note> for Beat {
+	type Error = v2::Note;
+
+	fn try_from(value: v2::Note) -> Result<Self, Self::Error> {
+		let Ok(color) = NoteColor::try_from(value.note_type) else {
+			return Err(value);
+		};
+
+		Ok(Self {
+			beat: value.beat,
+			time: 0.0,
+			x: value.x,
+			y: value.y,
+			angle_offset: value.angle_offset,
+			color,
+			direction: value.direction.into(),
+			fake: false
+		})
+	}
+}
+
+impl From<v3::ColorNote> for Beat {
+	fn from(value: v3::ColorNote) -> Self {
+		Self {
+			beat: value.beat,
+			time: 0.0,
+			x: value.x,
+			y: value.y,
+			angle_offset: value.angle_offset,
+			color: value.color.into(),
+			direction: value.direction.into(),
+			fake: false
+		}
+	}
+}
+
+impl From<(&v4::ColorNote, &v4::ColorNoteData)> for Beat {
+	fn from((note, data): (&v4::ColorNote, &v4::ColorNoteData)) -> Self {
+		Self {
+			beat: note.beat,
+			time: 0.0,
+			x: data.x,
+			y: data.y,
+			angle_offset: data.angle_offset,
+			color: data.color.into(),
+			direction: data.direction.into(),
+			fake: false
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Bomb {
+	beat: f32,
+	pub time: f32,
+	pub x: f32,
+	pub y: f32,
+	/// Whether this is a fake (non-interactable) bomb.
+	pub fake: bool
+}
+
+impl Bomb {
+	pub fn beat(&self) -> f32 {
+		self.beat
+	}
+
+	pub fn position(&self) -> GridPosition {
+		GridPosition::new(self.x, self.y)
+	}
+
+	/// Returns the lane (0-3) of the bomb, or `None` if it's not in a whole lane on the grid.
+	pub fn lane(&self) -> Option<u8> {
+		grid_index(self.x, GridSpec::STANDARD.columns)
+	}
+
+	/// Returns the layer (0-2) of the bomb, or `None` if it's not in a whole layer on the grid.
+	pub fn layer(&self) -> Option<u8> {
+		grid_index(self.y, GridSpec::STANDARD.rows)
+	}
+}
+
+impl TryFrom<v2::Note> for Bomb {
+	type Error = v2::Note;
+
+	fn try_from(value: v2::Note) -> Result<Self, Self::Error> {
+		if value.note_type != v2::NoteType::Bomb {
+			return Err(value);
+		}
+		Ok(Self {
+			beat: value.beat,
+			time: 0.0,
+			x: value.x,
+			y: value.y,
+			fake: false
+		})
+	}
+}
+
+impl From<v3::BombNote> for Bomb {
+	fn from(value: v3::BombNote) -> Self {
+		Self {
+			beat: value.beat,
+			time: 0.0,
+			x: value.x,
+			y: value.y,
+			fake: false
+		}
+	}
+}
+
+impl From<(&v4::BombNote, &v4::BombNoteData)> for Bomb {
+	fn from((note, data): (&v4::BombNote, &v4::BombNoteData)) -> Self {
+		Self {
+			beat: note.beat,
+			time: 0.0,
+			x: data.x,
+			y: data.y,
+			fake: false
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Obstacle {
+	beat: f32,
+	pub time: f32,
+	pub x: f32,
+	pub y: f32,
+	duration_beats: f32,
+	pub duration: f32,
+	pub end_time: f32,
+	pub width: f32,
+	pub height: f32,
+	/// Whether this is a fake (non-interactable) obstacle.
+	pub fake: bool
+}
+
+impl Obstacle {
+	pub fn beat(&self) -> f32 {
+		self.beat
+	}
+
+	pub fn duration_beats(&self) -> f32 {
+		self.duration_beats
+	}
+
+	/// Returns the position of the obstacle's bottom left corner.
+	pub fn position(&self) -> GridPosition {
+		GridPosition::new(self.x, self.y)
+	}
+
+	/// Returns the lane (0-3) of the obstacle's left edge, or `None` if it's not in a whole lane on the grid.
+	pub fn lane(&self) -> Option<u8> {
+		grid_index(self.x, GridSpec::STANDARD.columns)
+	}
+
+	/// Returns the layer (0-2) of the obstacle's bottom edge, or `None` if it's not in a whole layer on the grid.
+	pub fn layer(&self) -> Option<u8> {
+		grid_index(self.y, GridSpec::STANDARD.rows)
+	}
+
+	/// Returns the lanes (0-3) which the obstacle overlaps, including those it only partly covers.
+	pub fn covered_lanes(&self) -> Range<u8> {
+		let lanes = GridSpec::STANDARD.columns as f32;
+		let start = self.x.floor().clamp(0., lanes) as u8;
+		let end = (self.x + self.width).ceil().clamp(0., lanes) as u8;
+		start..end.max(start)
+	}
+
+	/// Classifies the obstacle by how it affects the player; see [`ObstacleKind`].
+	pub fn kind(&self) -> ObstacleKind {
+		let lanes = self.covered_lanes();
+		// the bottom of the third layer, roughly where the player's head is
+		let head_height = self.y <= 2. && self.y + self.height > 2.;
+		if !head_height {
+			ObstacleKind::Other
+		} else if self.y > 0. && lanes.contains(&1) && lanes.contains(&2) {
+			ObstacleKind::Crouch
+		} else if (lanes.contains(&1) || lanes.contains(&2)) && self.duration > ObstacleKind::VISION_BLOCK_MIN_DURATION {
+			ObstacleKind::VisionBlock
+		} else if self.y <= 0. {
+			ObstacleKind::Full
+		} else {
+			ObstacleKind::Other
+		}
+	}
+
+	/// Converts this obstacle back into a v2 obstacle, encoding its vertical position & height into the wall type.
+	///
+	/// See [`util::encode_wall_type`]; walls other than the vanilla full height & crouch walls are encoded
+	/// lossily.
+	pub fn to_v2(&self) -> v2::Obstacle {
+		v2::Obstacle {
+			beat: self.beat,
+			wall_type: util::encode_wall_type(self.y, self.height),
+			x: self.x,
+			duration: self.duration_beats,
+			width: self.width,
+			custom_data: None,
+			extra: HashMap::new()
+		}
+	}
+}
+
+/// How an obstacle affects the player, as returned by [`Obstacle::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObstacleKind {
+	/// Hangs over both center lanes at head height without reaching the ground, so the player has to duck under it.
+	Crouch,
+	/// Stands on the ground & reaches head height, so the player has to dodge around it.
+	Full,
+	/// Covers a center lane at head height for longer than [`Self::VISION_BLOCK_MIN_DURATION`], hiding the notes
+	/// behind it.
+	VisionBlock,
+	/// Anything else, such as low or high walls which don't reach head height.
+	Other
+}
+
+impl ObstacleKind {
+	/// The shortest time in seconds a center wall has to last to count as a vision block.
+	pub const VISION_BLOCK_MIN_DURATION: f32 = 0.25;
+}
+
+impl From<v2::Obstacle> for Obstacle {
+	fn from(value: v2::Obstacle) -> Self {
+		let (y, height) = util::decode_wall_type(value.wall_type);
+		Self {
+			beat: value.beat,
+			time: 0.,
+			x: value.x,
+			y,
+			duration_beats: value.duration,
+			duration: 0.0,
+			end_time: 0.0,
+			height,
+			width: value.width,
+			fake: false
+		}
+	}
+}
+
+impl From<v3::Obstacle> for Obstacle {
+	fn from(value: v3::Obstacle) -> Self {
+		Self {
+			beat: value.beat,
+			time: 0.0,
+			x: value.x,
+			y: value.y,
+			duration_beats: value.duration,
+			duration: 0.0,
+			end_time: 0.0,
+			height: value.height,
+			width: value.width,
+			fake: false
+		}
+	}
+}
+
+impl From<(&v4::Obstacle, &v4::ObstacleData)> for Obstacle {
+	fn from((obstacle, data): (&v4::Obstacle, &v4::ObstacleData)) -> Self {
+		Self {
+			beat: obstacle.beat,
+			time: 0.0,
+			x: data.x,
+			y: data.y,
+			duration_beats: data.duration,
+			duration: 0.0,
+			end_time: 0.0,
+			height: data.height,
+			width: data.width,
+			fake: false
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Chain {
+	beat: f32,
+	pub time: f32,
+	pub x: f32,
+	pub y: f32,
+	pub color: NoteColor,
+	pub direction: NoteDirection,
+	tail_beat: f32,
+	pub tail_time: f32,
+	pub tail_x: f32,
+	pub tail_y: f32,
+	pub num_slices: u8,
+	pub squish_factor: f32,
+	/// Whether this is a fake (non-interactable) chain.
+	pub fake: bool
+}
+
+impl Chain {
+	pub fn beat(&self) -> f32 {
+		self.beat
+	}
+
+	pub fn tail_beat(&self) -> f32 {
+		self.tail_beat
+	}
+
+	/// Returns the position of the chain's head.
+	pub fn position(&self) -> GridPosition {
+		GridPosition::new(self.x, self.y)
+	}
+
+	pub fn tail_position(&self) -> GridPosition {
+		GridPosition::new(self.tail_x, self.tail_y)
+	}
+
+	/// Returns the lane (0-3) of the chain's head, or `None` if it's not in a whole lane on the grid.
+	pub fn lane(&self) -> Option<u8> {
+		grid_index(self.x, GridSpec::STANDARD.columns)
+	}
+
+	/// Returns the layer (0-2) of the chain's head, or `None` if it's not in a whole layer on the grid.
+	pub fn layer(&self) -> Option<u8> {
+		grid_index(self.y, GridSpec::STANDARD.rows)
+	}
+
+	/// Returns the chain's links, i.e. every slice but the head. Like the game, the links are spread evenly in time
+	/// between the head & tail, and evenly along the head to tail segment scaled by `squish_factor`, so a squish factor
+	/// of `0.5` ends the links halfway to the tail.
+	pub fn links(&self) -> Vec<ChainLink> {
+		let segments = self.num_slices.saturating_sub(1);
+		(1..=segments)
+			.map(|i| {
+				let t = i as f32 / segments as f32;
+				ChainLink {
+					time: self.time + (self.tail_time - self.time) * t,
+					x: self.x + (self.tail_x - self.x) * t * self.squish_factor,
+					y: self.y + (self.tail_y - self.y) * t * self.squish_factor
+				}
+			})
+			.collect()
+	}
+
+	/// Checks the chain's slice parameters, returning the first problem found.
+	pub fn validate(&self) -> Option<ChainWarning> {
+		if self.num_slices < 2 {
+			Some(ChainWarning::TooFewSlices)
+		} else if !(self.squish_factor > 0. && self.squish_factor <= 1.) {
+			Some(ChainWarning::InvalidSquish)
+		} else if self.tail_beat == self.beat && self.tail_x == self.x && self.tail_y == self.y {
+			Some(ChainWarning::Degenerate)
+		} else {
+			None
+		}
+	}
+}
+
+/// A single link of a [`Chain`], following its head note.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChainLink {
+	pub time: f32,
+	pub x: f32,
+	pub y: f32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChainWarning {
+	/// The chain has fewer than 2 slices (including the head).
+	TooFewSlices,
+	/// The squish factor is outside of `(0, 1]`.
+	InvalidSquish,
+	/// The tail is at the same position & beat as the head.
+	Degenerate
+}
+
+impl From<v3::BurstSlider> for Chain {
+	fn from(value: v3::BurstSlider) -> Self {
+		Self {
+			beat: value.beat,
+			time: 0.0,
+			x: value.x,
+			y: value.y,
+			color: value.color.into(),
+			direction: value.direction.into(),
+			tail_beat: value.tail_beat,
+			tail_time: 0.0,
+			tail_x: value.tail_x,
+			tail_y: value.tail_y,
+			num_slices: value.num_slices,
+			squish_factor: value.squish_amount,
+			fake: false
+		}
+	}
+}
+
+impl From<(&v4::Chain, &v4::ColorNoteData, &v4::ChainData)> for Chain {
+	fn from((chain, head, data): (&v4::Chain, &v4::ColorNoteData, &v4::ChainData)) -> Self {
+		Self {
+			beat: chain.beat,
+			time: 0.0,
+			x: head.x,
+			y: head.y,
+			color: head.color.into(),
+			direction: head.direction.into(),
+			tail_beat: chain.tail_beat,
+			tail_time: 0.0,
+			tail_x: data.tail_x,
+			tail_y: data.tail_y,
+			num_slices: data.num_slices,
+			squish_factor: data.squish_amount,
+			fake: false
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Arc {
+	beat: f32,
+	pub time: f32,
+	pub x: f32,
+	pub y: f32,
+	pub color: NoteColor,
+	pub direction: NoteDirection,
+	/// How far the arc's control point extends from the head.
+	pub head_multiplier: f32,
+	tail_beat: f32,
+	pub tail_time: f32,
+	pub tail_x: f32,
+	pub tail_y: f32,
+	pub tail_direction: NoteDirection,
+	/// How far the arc's control point extends from the tail.
+	pub tail_multiplier: f32,
+	pub mid_anchor_mode: MidAnchorMode
+}
+
+impl Arc {
+	pub fn beat(&self) -> f32 {
+		self.beat
+	}
+
+	pub fn tail_beat(&self) -> f32 {
+		self.tail_beat
+	}
+}
+
+impl From<v3::Slider> for Arc {
+	fn from(value: v3::Slider) -> Self {
+		Self {
+			beat: value.beat,
+			time: 0.0,
+			x: value.x,
+			y: value.y,
+			color: value.color.into(),
+			direction: value.direction.into(),
+			head_multiplier: value.head_multiplier,
+			tail_beat: value.tail_beat,
+			tail_time: 0.0,
+			tail_x: value.tail_x,
+			tail_y: value.tail_y,
+			tail_direction: value.tail_direction.into(),
+			tail_multiplier: value.tail_multiplier,
+			mid_anchor_mode: value.mid_anchor_mode
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct LaneRotation {
+	beat: f32,
+	pub time: f32,
+	/// The rotation in degrees, relative to the previous rotation.
+	pub rotation: f32,
+	/// Whether the rotation applies to objects at the same time, rather than only to objects after it.
+	pub early: bool
+}
+
+impl LaneRotation {
+	pub fn beat(&self) -> f32 {
+		self.beat
+	}
+}
+
+/// A basic lighting event, in the v2 event type & value space. Color boosts are represented as v2 type `5` events.
+#[derive(Debug, Clone)]
+pub struct LightEvent {
+	beat: f32,
+	pub time: f32,
+	pub event_type: i32,
+	pub value: i32,
+	pub float_value: f32
+}
+
+impl LightEvent {
+	pub fn beat(&self) -> f32 {
+		self.beat
+	}
+}
+
+struct BpmEvent {
+	song_time: f32,
+	beats: f32
+}
+
+impl From<&v2::BpmEvent> for BpmEvent {
+	fn from(value: &v2::BpmEvent) -> Self {
+		Self {
+			song_time: value.song_time,
+			beats: value.beats
+		}
+	}
+}
+
+impl From<&v3::BpmEvent> for BpmEvent {
+	fn from(value: &v3::BpmEvent) -> Self {
+		Self {
+			song_time: value.song_time,
+			beats: value.beats
+		}
+	}
+}
+
+#[derive(Clone)]
+struct BpmChangeEvent {
+	bpm: f32,
+	start_time: f32,
+	start_bpm_time: f32
+}
+
+/// Rounds `beat` to the nearest `1/precision` of a beat, e.g. a precision of `4` snaps to quarter beats.
+pub fn snap_beat(beat: f32, precision: u32) -> f32 {
+	let precision = precision as f32;
+	(beat * precision).round() / precision
+}
+
+/// Converts between beats & song time (in seconds) across a map's BPM changes.
+pub struct BpmTracker {
+	base_bpm: f32,
+	changes: Vec<BpmChangeEvent>
+}
+
+impl BpmTracker {
+	pub fn from_v2(beatmap: &v2::Beatmap, bpm: f32) -> Self {
+		Self::new_from_v2(bpm, &beatmap.bpm_events)
+	}
+
+	pub fn from_v3(beatmap: &v3::Beatmap, bpm: f32) -> Self {
+		Self::new_from_v3(bpm, &beatmap.bpm_events)
+	}
+
+	/// Builds a tracker from v2 BPM events, using `base_bpm` until the first event.
+	pub fn new_from_v2(base_bpm: f32, events: &[v2::BpmEvent]) -> Self {
+		Self::new(base_bpm, events.iter().map(BpmEvent::from))
+	}
+
+	/// Builds a tracker from v3 BPM events, using `base_bpm` until the first event.
+	pub fn new_from_v3(base_bpm: f32, events: &[v3::BpmEvent]) -> Self {
+		Self::new(base_bpm, events.iter().map(BpmEvent::from))
+	}
+
+	fn new(start_bpm: f32, events: impl IntoIterator<Item = BpmEvent>) -> Self {
+		let mut base_bpm = start_bpm;
+		let mut changes = Vec::new();
+		let mut events = events.into_iter().peekable();
+		if let Some(first) = events.peek() {
+			if first.song_time == 0. {
+				base_bpm = first.beats;
+				events.next();
+			}
+			let mut last_change = BpmChangeEvent {
+				bpm: base_bpm,
+				start_time: 0.,
+				start_bpm_time: 0.
+			};
+			changes.push(last_change.clone());
+
+			for event in events {
+				last_change = BpmChangeEvent {
+					bpm: event.beats,
+					start_bpm_time: event.song_time,
+					start_time: last_change.start_time + ((event.song_time - last_change.start_bpm_time) / last_change.bpm) * 60.0
+				};
+				changes.push(last_change.clone());
+			}
+		}
+		Self { base_bpm, changes }
+	}
+
+	pub fn beat_to_song_time(&self, time: f32) -> f32 {
+		if self.changes.is_empty() {
+			return time * (60.0 / self.base_bpm);
+		}
+
+		// the last change starting at or before `time`, so that of several changes at the same beat the latest one wins
+		let i = self.changes[1..].partition_point(|x| x.start_bpm_time <= time);
+		let prev_bpm_change = &self.changes[i];
+		prev_bpm_change.start_time + ((time - prev_bpm_change.start_bpm_time) / prev_bpm_change.bpm) * 60.0
+	}
+
+	/// The inverse of [`Self::beat_to_song_time`].
+	pub fn song_time_to_beat(&self, time: f32) -> f32 {
+		if self.changes.is_empty() {
+			return time * (self.base_bpm / 60.0);
+		}
+
+		let i = self.changes[1..].partition_point(|x| x.start_time <= time);
+		let prev_bpm_change = &self.changes[i];
+		prev_bpm_change.start_bpm_time + ((time - prev_bpm_change.start_time) / 60.0) * prev_bpm_change.bpm
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn beat(x: f32, y: f32, color: NoteColor, direction: NoteDirection) -> Beat {
+		Beat {
+			beat: 0.0,
+			time: 0.0,
+			x,
+			y,
+			angle_offset: None,
+			color,
+			direction,
+			fake: false
+		}
+	}
+
+	fn beatmap(beats: Vec<Beat>) -> Beatmap {
+		Beatmap {
+			beats,
+			bombs: Vec::new(),
+			obstacles: Vec::new(),
+			chains: Vec::new(),
+			arcs: Vec::new(),
+			rotations: Vec::new(),
+			lights: Vec::new(),
+			mapping_extensions: false
+		}
+	}
+
+	#[test]
+	fn test_mapping_extensions_ok() {
+		let map = Beatmap::from_file("tests/data/maps/1579c_ExpertPlusStandard.dat", 222.0).unwrap();
+		let walls: Vec<_> = map.obstacles.iter().map(|o| (o.x, o.width, o.y, o.height)).collect();
+		// types 783174 & 263727 decode to (h 779, sh 173) & (h 259, sh 726)
+		let expected = [(4.5, 2., 1.1533, 3.895), (-2.5, 9., 4.84, 1.295), (-2.5, 2., 1.1533, 3.895)];
+		for ((x, width, y, height), expected) in walls.into_iter().zip(expected) {
+			assert_eq!((x, width), (expected.0, expected.1));
+			assert!((y - expected.2).abs() < 1e-3 && (height - expected.3).abs() < 1e-3);
+		}
+	}
+
+	#[test]
+	fn test_validate_mapping_extensions_whole_lanes() {
+		// extra lanes on whole numbers can't be told apart from mistakes, so this relies on the difficulty's requirement
+		let mut map = Beatmap::from_v2(
+			v2::Beatmap::from_string(
+				r#"{
+					"_version": "2.2.0",
+					"_notes": [
+						{ "_time": 1, "_lineIndex": 5000, "_lineLayer": 0, "_type": 0, "_cutDirection": 1 },
+						{ "_time": 2, "_lineIndex": -1, "_lineLayer": 1, "_type": 1, "_cutDirection": 1 }
+					],
+					"_obstacles": [],
+					"_events": []
+				}"#
+			)
+			.unwrap(),
+			60.
+		)
+		.unwrap();
+		assert_eq!(map.beats.iter().map(|x| x.x).collect::<Vec<_>>(), [4., -1.]);
+		assert!(!map.mapping_extensions);
+		assert_eq!(map.validate().iter().filter(|x| x.kind == IssueKind::OutOfGrid).count(), 2);
+
+		map.mapping_extensions = true;
+		assert!(map.validate().is_empty());
+	}
+
+	#[test]
+	fn test_validate_mapping_extensions_precise_walls_only() {
+		let v2 = r#"{
+			"_version": "2.2.0",
+			"_notes": [{ "_time": 1, "_lineIndex": 1, "_lineLayer": 0, "_type": 0, "_cutDirection": 1 }],
+			"_obstacles": [{ "_time": 2, "_type": 1600, "_lineIndex": -2000, "_duration": 1, "_width": 1 }],
+			"_events": []
+		}"#;
+		let v3 = r#"{
+			"version": "3.2.0",
+			"colorNotes": [{ "b": 1.0, "x": 1, "y": 0, "c": 0, "d": 1 }],
+			"bombNotes": [],
+			"obstacles": [{ "b": 2.0, "x": -1500, "y": 0, "d": 1.0, "w": 1, "h": 5 }],
+			"burstSliders": [],
+			"bpmEvents": []
+		}"#;
+
+		// the v2 wall is on a whole lane, but its type is a mapping extensions height
+		for (map, x) in [
+			(Beatmap::from_v2(v2::Beatmap::from_string(v2).unwrap(), 60.).unwrap(), -1.),
+			(Beatmap::from_v3(v3::Beatmap::from_string(v3).unwrap(), 60.).unwrap(), -0.5)
+		] {
+			assert!(map.mapping_extensions);
+			assert_eq!(map.obstacles[0].x, x);
+			assert!(map.validate().is_empty());
+		}
+	}
+
+	#[test]
+	fn test_mirror_one_saber_keeps_colors() {
+		let mut map = beatmap(vec![beat(0., 0., NoteColor::Blue, NoteDirection::DownLeft)]);
+		map.mirror_with(GridSpec::STANDARD, &BeatmapCharacteristic::OneSaber);
+		assert_eq!(map.beats[0].x, 3.);
+		assert_eq!(map.beats[0].color, NoteColor::Blue);
+		assert_eq!(map.beats[0].direction, NoteDirection::DownRight);
+
+		map.mirror_with(GridSpec::STANDARD, &BeatmapCharacteristic::Standard);
+		assert_eq!(map.beats[0].x, 0.);
+		assert_eq!(map.beats[0].color, NoteColor::Red);
+	}
+
+	#[test]
+	fn test_validate_no_arrows() {
+		let map = beatmap(vec![beat(1., 1., NoteColor::Red, NoteDirection::Any), beat(2., 1., NoteColor::Blue, NoteDirection::Down)]);
+		assert!(map.validate_with(GridSpec::STANDARD, &BeatmapCharacteristic::Standard).is_empty());
+
+		let issues = map.validate_with(GridSpec::STANDARD, &BeatmapCharacteristic::NoArrows);
+		assert_eq!(issues.len(), 1);
+		assert_eq!(issues[0].kind, IssueKind::DirectionalNote);
+	}
+
+	#[test]
+	fn test_validate_grid_size() {
+		let map = beatmap(vec![beat(4., 0., NoteColor::Red, NoteDirection::Any)]);
+		assert_eq!(map.validate_with(GridSpec::STANDARD, &BeatmapCharacteristic::Standard)[0].kind, IssueKind::OutOfGrid);
+		assert!(
+			map.validate_with(GridSpec { columns: 6, rows: 3 }, &BeatmapCharacteristic::Standard)
+				.is_empty()
+		);
+	}
+
+	#[test]
+	fn test_obstacle_v2_round_trip() {
+		for wall_type in [0, 1, 4001 + 300 * 1000 + 150] {
+			let obstacle = v2::Obstacle {
+				beat: 4.,
+				wall_type,
+				x: 1.,
+				duration: 2.,
+				width: 1.,
+				custom_data: None,
+				extra: Default::default()
+			};
+			assert_eq!(Obstacle::from(obstacle).to_v2().wall_type, wall_type);
+		}
+	}
+
+	#[test]
+	fn test_from_v3_with_fakes() {
+		let map = r#"{
+			"version": "3.2.0",
+			"colorNotes": [{ "b": 1.0, "x": 1, "y": 0, "c": 0, "d": 1 }],
+			"bombNotes": [],
+			"obstacles": [],
+			"burstSliders": [],
+			"bpmEvents": [],
+			"customData": {
+				"fakeColorNotes": [{ "b": 2.0, "x": 2, "y": 0, "c": 1, "d": 1 }],
+				"fakeBombNotes": [{ "b": 3.0, "x": 0, "y": 2 }],
+				"fakeObstacles": [{ "b": 4.0, "x": 0, "y": 0, "d": 1.0, "w": 1, "h": 5 }]
+			}
+		}"#;
+
+		let without = Beatmap::from_v3(v3::Beatmap::from_string(map).unwrap(), 60.).unwrap();
+		assert_eq!(without.beats.len(), 1);
+		assert!(without.bombs.is_empty() && without.obstacles.is_empty());
+
+		let with = Beatmap::from_v3_with_fakes(v3::Beatmap::from_string(map).unwrap(), 60., true).unwrap();
+		assert_eq!(with.beats.iter().map(|x| (x.time, x.fake)).collect::<Vec<_>>(), [(1., false), (2., true)]);
+		assert!(with.bombs[0].fake);
+		assert_eq!(with.bombs[0].time, 3.);
+		assert!(with.obstacles[0].fake);
+	}
+
+	#[test]
+	fn test_semantically_eq_across_versions() {
+		let v3 = v3::Beatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [{ "b": 2.0, "x": 2, "y": 0, "c": 1, "d": 3 }, { "b": 1.0, "x": 1, "y": 0, "c": 0, "d": 1 }],
+				"bombNotes": [{ "b": 1.5, "x": 3, "y": 2 }],
+				"obstacles": [],
+				"burstSliders": [],
+				"bpmEvents": []
+			}"#
+		)
+		.unwrap();
+		let v2 = v2::Beatmap {
+			version: String::from("2.6.0"),
+			notes: v3
+				.color_notes
+				.iter()
+				.cloned()
+				.map(v2::Note::from)
+				.chain(v3.bomb_notes.iter().cloned().map(v2::Note::from))
+				.collect(),
+			..Default::default()
+		};
+
+		let from_v3 = Beatmap::from_v3(v3, 120.).unwrap();
+		let mut from_v2 = Beatmap::from_v2(v2, 120.).unwrap();
+		assert_eq!(from_v3.semantically_eq(&from_v2, 1e-6), Ok(()));
+
+		from_v2.beats[1].direction = NoteDirection::Up;
+		from_v2.bombs.clear();
+		let differences = from_v3.semantically_eq(&from_v2, 1e-6).unwrap_err();
+		assert_eq!(differences.len(), 2);
+		assert!(matches!(
+			differences[0],
+			Difference::Field {
+				object: ObjectIndex::Beat(1),
+				field: "direction",
+				..
+			}
+		));
+		assert!(matches!(differences[1], Difference::Count { left: 1, right: 0, .. }));
+	}
+
+	#[test]
+	fn test_bpm_tracker_inverse() {
+		let map = v3::Beatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [],
+				"bombNotes": [],
+				"obstacles": [],
+				"burstSliders": [],
+				"bpmEvents": [{ "b": 4.0, "m": 60.0 }, { "b": 8.0, "m": 240.0 }]
+			}"#
+		)
+		.unwrap();
+		let tracker = BpmTracker::from_v3(&map, 120.);
+
+		// 4 beats at 120 BPM, then 4 beats at 60 BPM, then 4 beats at 240 BPM
+		assert_eq!(tracker.beat_to_song_time(2.), 1.);
+		assert_eq!(tracker.beat_to_song_time(8.), 6.);
+		assert_eq!(tracker.beat_to_song_time(12.), 7.);
+		for beat in [0., 1.5, 4., 6.25, 8., 11.] {
+			assert!((tracker.song_time_to_beat(tracker.beat_to_song_time(beat)) - beat).abs() < 1e-5);
+		}
+		for time in [0., 1.5, 2., 4.75, 6., 6.5] {
+			assert!((tracker.beat_to_song_time(tracker.song_time_to_beat(time)) - time).abs() < 1e-5);
+		}
+	}
+
+	#[test]
+	fn test_snap_all() {
+		assert_eq!(snap_beat(1.13, 8), 1.125);
+		assert_eq!(snap_beat(2.32, 12), 2. + 4. / 12.);
+
+		let map = v3::Beatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [{ "b": 1.26, "x": 1, "y": 0, "c": 0, "d": 1 }, { "b": 2.49, "x": 2, "y": 0, "c": 1, "d": 1 }],
+				"bombNotes": [],
+				"obstacles": [{ "b": 0.9, "x": 0, "y": 0, "d": 1.2, "w": 1, "h": 5 }],
+				"burstSliders": [],
+				"bpmEvents": []
+			}"#
+		)
+		.unwrap();
+		let tracker = BpmTracker::from_v3(&map, 120.);
+		let mut map = Beatmap::from_v3(map, 120.).unwrap();
+		map.snap_all(4, &tracker);
+
+		assert_eq!(map.beats.iter().map(|x| (x.beat(), x.time)).collect::<Vec<_>>(), [(1.25, 0.625), (2.5, 1.25)]);
+		assert_eq!((map.obstacles[0].beat(), map.obstacles[0].duration_beats()), (1., 1.));
+		assert_eq!(map.obstacles[0].end_time, 1.);
+	}
+
+	#[test]
+	fn test_bpm_tracker_duplicate_times() {
+		let map = v3::Beatmap {
+			bpm_events: [(0., 120.), (4., 60.), (4., 240.)]
+				.into_iter()
+				.map(|(song_time, beats)| v3::BpmEvent {
+					song_time,
+					beats,
+					extra: Default::default()
+				})
+				.collect(),
+			..Default::default()
+		};
+		let tracker = BpmTracker::from_v3(&map, 100.);
+
+		assert_eq!(tracker.beat_to_song_time(4.), 2.);
+		// the later event at beat 4 takes effect, so the next 4 beats take 1 second
+		assert_eq!(tracker.beat_to_song_time(8.), 3.);
+		assert_eq!(tracker.song_time_to_beat(3.), 8.);
+		assert_eq!(tracker.song_time_to_beat(2.), 4.);
+	}
+
+	#[test]
+	fn test_bpm_tracker_from_events() {
+		let v2_events = [v2::BpmEvent {
+			song_time: 4.,
+			beats: 60.,
+			extra: Default::default()
+		}];
+		let v3_events = [v3::BpmEvent {
+			song_time: 4.,
+			beats: 60.,
+			extra: Default::default()
+		}];
+		for tracker in [BpmTracker::new_from_v2(120., &v2_events), BpmTracker::new_from_v3(120., &v3_events)] {
+			assert_eq!(tracker.beat_to_song_time(4.), 2.);
+			assert_eq!(tracker.beat_to_song_time(6.), 4.);
+		}
+		assert_eq!(BpmTracker::new_from_v3(120., &[]).beat_to_song_time(6.), 3.);
+	}
+
+	#[test]
+	fn test_v3_note_song_time() {
+		let map = v3::Beatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [{ "b": 8, "x": 1, "y": 0, "c": 0, "d": 1 }],
+				"bombNotes": [{ "b": 2, "x": 0, "y": 2 }],
+				"obstacles": [],
+				"burstSliders": [],
+				"bpmEvents": [{ "b": 4, "m": 60 }]
+			}"#
+		)
+		.unwrap();
+		let tracker = BpmTracker::from_v3(&map, 120.);
+		assert_eq!(map.bomb_notes[0].song_time(&tracker), 1.);
+		// 4 beats at 120 BPM, then 4 beats at 60 BPM
+		assert_eq!(map.color_notes[0].song_time(&tracker), 6.);
+	}
+
+	#[test]
+	fn test_bpm_tracker_many_changes() {
+		fn linear_beat_to_song_time(tracker: &BpmTracker, time: f32) -> f32 {
+			let mut i = 0;
+			while i < tracker.changes.len() - 1 && tracker.changes[i + 1].start_bpm_time < time {
+				i += 1;
+			}
+			let prev_bpm_change = &tracker.changes[i];
+			prev_bpm_change.start_time + ((time - prev_bpm_change.start_bpm_time) / prev_bpm_change.bpm) * 60.0
+		}
+
+		let map = v3::Beatmap {
+			bpm_events: (0..10_000)
+				.map(|i| v3::BpmEvent {
+					song_time: i as f32,
+					beats: 100. + (i % 7) as f32 * 10.,
+					extra: Default::default()
+				})
+				.collect(),
+			..Default::default()
+		};
+		let tracker = BpmTracker::from_v3(&map, 120.);
+
+		let beats: Vec<f32> = (0..40_000).map(|i| i as f32 * 0.25 + 0.1).collect();
+		for &beat in &beats {
+			assert_eq!(tracker.beat_to_song_time(beat), linear_beat_to_song_time(&tracker, beat));
+		}
+
+		let start = std::time::Instant::now();
+		for &beat in &beats {
+			std::hint::black_box(tracker.beat_to_song_time(beat));
+		}
+		assert!(start.elapsed() < std::time::Duration::from_secs(1));
+	}
+
+	#[test]
+	fn test_into_iter() {
+		let map = v3::Beatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [{ "b": 2.0, "x": 1, "y": 0, "c": 0, "d": 1 }, { "b": 1.0, "x": 2, "y": 0, "c": 1, "d": 1 }],
+				"bombNotes": [{ "b": 1.5, "x": 0, "y": 2 }],
+				"obstacles": [{ "b": 0.5, "x": 0, "y": 0, "d": 1.0, "w": 1, "h": 5 }],
+				"burstSliders": [],
+				"bpmEvents": []
+			}"#
+		)
+		.unwrap();
+		let map = Beatmap::from_v3(map, 60.).unwrap();
+
+		assert_eq!(map.beats[0].time, 1.);
+		assert_eq!(map.objects().len(), 4);
+		let times: Vec<f32> = map.objects().map(|x| x.time()).collect();
+		assert_eq!(times, [0.5, 1., 1.5, 2.]);
+		assert!(matches!((&map).into_iter().nth(2), Some(BeatmapObjectRef::Bomb(_))));
+
+		let notes: Vec<_> = map.iter_notes().map(|x| (x.time(), x.position())).collect();
+		assert_eq!(notes, [(1., (2., 0.)), (1.5, (0., 2.)), (2., (1., 0.))]);
+		assert!(matches!(map.iter_notes().nth(1), Some(AnyNote::Bomb(_))));
+
+		let objects: Vec<BeatmapObject> = map.into_iter().collect();
+		assert_eq!(objects.len(), 4);
+		assert!(matches!(objects[0], BeatmapObject::Obstacle(_)));
+	}
+
+	#[test]
+	fn test_arcs_from_v3() {
+		let map = v3::Beatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [],
+				"bombNotes": [],
+				"obstacles": [],
+				"burstSliders": [],
+				"sliders": [{ "b": 2.0, "c": 0, "x": 1, "y": 0, "d": 1, "mu": 1.0, "tb": 4.0, "tx": 2, "ty": 2, "tc": 0, "tmu": 0.5, "m": 1 }],
+				"bpmEvents": []
+			}"#
+		)
+		.unwrap();
+		let mut map = Beatmap::from_v3(map, 120.).unwrap();
+		assert_eq!(map.arcs.len(), 1);
+		assert_eq!((map.arcs[0].time, map.arcs[0].tail_time), (1., 2.));
+		assert_eq!(map.arcs[0].tail_direction, NoteDirection::Up);
+		assert_eq!(map.arcs[0].mid_anchor_mode, MidAnchorMode::Clockwise);
+
+		map.mirror_with(GridSpec::STANDARD, &BeatmapCharacteristic::Standard);
+		assert_eq!((map.arcs[0].x, map.arcs[0].tail_x), (2., 1.));
+		assert_eq!(map.arcs[0].color, NoteColor::Blue);
+		assert_eq!(map.arcs[0].mid_anchor_mode, MidAnchorMode::CounterClockwise);
+	}
+
+	#[test]
+	fn test_lane_rotations() {
+		let v2 = v2::Beatmap::from_string(
+			r#"{
+				"_version": "2.2.0",
+				"_notes": [],
+				"_obstacles": [],
+				"_events": [
+					{ "_time": 1.0, "_type": 14, "_value": 7 },
+					{ "_time": 2.0, "_type": 15, "_value": 1 },
+					{ "_time": 2.0, "_type": 1, "_value": 3 },
+					{ "_time": 3.0, "_type": 14, "_value": 1450 }
+				]
+			}"#
+		)
+		.unwrap();
+		let v3 = v3::Beatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [],
+				"bombNotes": [],
+				"obstacles": [],
+				"burstSliders": [],
+				"bpmEvents": [],
+				"rotationEvents": [{ "b": 1.0, "e": 0, "r": 60 }, { "b": 2.0, "e": 1, "r": -45 }, { "b": 3.0, "e": 0, "r": 90 }]
+			}"#
+		)
+		.unwrap();
+
+		for map in [Beatmap::from_v2(v2, 60.).unwrap(), Beatmap::from_v3(v3, 60.).unwrap()] {
+			assert_eq!(map.rotations.len(), 3);
+			assert_eq!(map.rotation_at(0.5), 0.);
+			assert_eq!(map.rotation_at(1.), 60.);
+			// late rotations only apply after their time
+			assert_eq!(map.rotation_at(2.), 60.);
+			assert_eq!(map.rotation_at(2.5), 15.);
+			assert_eq!(map.rotation_at(3.), 105.);
+		}
+	}
+
+	#[test]
+	fn test_light_events() {
+		let v2 = v2::Beatmap::from_string(
+			r#"{
+				"_version": "2.2.0",
+				"_notes": [],
+				"_obstacles": [],
+				"_events": [
+					{ "_time": 1.0, "_type": 0, "_value": 1 },
+					{ "_time": 2.0, "_type": 14, "_value": 1 },
+					{ "_time": 3.0, "_type": 5, "_value": 1 },
+					{ "_time": 4.0, "_type": 4, "_value": 5, "_floatValue": 0.5 }
+				]
+			}"#
+		)
+		.unwrap();
+		let v3 = v3::Beatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [],
+				"bombNotes": [],
+				"obstacles": [],
+				"burstSliders": [],
+				"bpmEvents": [],
+				"basicBeatmapEvents": [{ "b": 1.0, "et": 0, "i": 1, "f": 1.0 }, { "b": 4.0, "et": 4, "i": 5, "f": 0.5 }],
+				"colorBoostBeatmapEvents": [{ "b": 3.0, "o": true }]
+			}"#
+		)
+		.unwrap();
+
+		for map in [Beatmap::from_v2(v2, 120.).unwrap(), Beatmap::from_v3(v3, 120.).unwrap()] {
+			let lights: Vec<_> = map.lights.iter().map(|x| (x.time, x.event_type, x.value, x.float_value)).collect();
+			assert_eq!(lights, [(0.5, 0, 1, 1.), (1.5, 5, 1, 1.), (2., 4, 5, 0.5)]);
+		}
+	}
+
+	#[test]
+	fn test_objects_tie_order() {
+		let mut map = beatmap(vec![beat(0., 0., NoteColor::Red, NoteDirection::Down), beat(1., 0., NoteColor::Blue, NoteDirection::Down)]);
+		map.bombs.push(Bomb {
+			beat: 0.,
+			time: 0.,
+			x: 2.,
+			y: 0.,
+			fake: false
+		});
+		map.sort_by_time();
+
+		let objects: Vec<_> = map.objects().collect();
+		assert!(matches!(objects[..], [BeatmapObjectRef::Beat(_), BeatmapObjectRef::Beat(_), BeatmapObjectRef::Bomb(_)]));
+	}
+
+	#[test]
+	fn test_obstacles_covering() {
+		let map = v3::Beatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [],
+				"bombNotes": [],
+				"obstacles": [{ "b": 1.0, "x": 0, "y": 0, "d": 1.0, "w": 2, "h": 5 }, { "b": 2.0, "x": 2, "y": 2, "d": 1.0, "w": 2, "h": 3 }],
+				"burstSliders": [],
+				"bpmEvents": []
+			}"#
+		)
+		.unwrap();
+		let map = Beatmap::from_v3(map, 60.).unwrap();
+
+		let lane = |x| map.obstacles_covering_lane(x).map(|o| o.time).collect::<Vec<_>>();
+		assert_eq!(lane(0.), [1.]);
+		assert_eq!(lane(1.5), [1.]);
+		assert_eq!(lane(2.), [2.]);
+		assert!(lane(4.).is_empty());
+
+		let region = |x, y, w, h| map.obstacles_covering_region(x, y, w, h).map(|o| o.time).collect::<Vec<_>>();
+		assert_eq!(region(1., 0., 2., 1.), [1.]);
+		assert_eq!(region(1., 0., 2., 3.), [1., 2.]);
+		assert!(region(2., 0., 2., 2.).is_empty());
+	}
+
+	#[test]
+	fn test_objects_between() {
+		let map = v3::Beatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [
+					{ "b": 1.0, "x": 1, "y": 0, "c": 0, "d": 1 },
+					{ "b": 2.0, "x": 2, "y": 0, "c": 1, "d": 1 },
+					{ "b": 3.0, "x": 1, "y": 0, "c": 0, "d": 1 },
+					{ "b": 4.0, "x": 2, "y": 0, "c": 1, "d": 1 }
+				],
+				"bombNotes": [{ "b": 5.0, "x": 0, "y": 2 }],
+				"obstacles": [{ "b": 0.0, "x": 0, "y": 0, "d": 2.5, "w": 1, "h": 5 }, { "b": 1.0, "x": 3, "y": 0, "d": 0.5, "w": 1, "h": 5 }],
+				"burstSliders": [],
+				"bpmEvents": []
+			}"#
+		)
+		.unwrap();
+		let map = Beatmap::from_v3(map, 60.).unwrap();
+
+		let window = map.objects_between(2., 4.);
+		assert_eq!(window.beats.iter().map(|x| x.time).collect::<Vec<_>>(), [2., 3.]);
+		assert!(window.bombs.is_empty());
+		// the first obstacle started before the window but is still active, the second ended before it
+		assert_eq!(window.obstacles.map(|x| x.time).collect::<Vec<_>>(), [0.]);
+
+		assert!(map.objects_between(4., 2.).beats.is_empty());
+		assert_eq!(map.objects_between(4.5, 10.).bombs.len(), 1);
+	}
+
+	#[test]
+	fn test_time_index() {
+		let obstacle = |time: f32, duration: f32| Obstacle {
+			beat: time,
+			time,
+			x: 0.,
+			y: 0.,
+			duration_beats: duration,
+			duration,
+			end_time: time + duration,
+			width: 1.,
+			height: 5.,
+			fake: false
+		};
+		let mut map = beatmap(vec![beat(1., 0., NoteColor::Red, NoteDirection::Down)]);
+		// a long wall which starts well before the window, followed by short walls which end before it
+		map.obstacles = vec![obstacle(0., 20.), obstacle(1., 1.), obstacle(2., 1.), obstacle(3., 1.), obstacle(11., 1.), obstacle(30., 1.)];
+
+		let index = map.time_index();
+		for (start, end) in [(10., 15.), (0., 1.), (2.5, 3.5), (19.9, 30.), (21., 29.), (40., 50.), (15., 10.)] {
+			let indexed: Vec<_> = index.objects_between(start, end).obstacles.map(|x| x.time).collect();
+			let scanned: Vec<_> = map.objects_between(start, end).obstacles.map(|x| x.time).collect();
+			assert_eq!(indexed, scanned, "{start}..{end}");
+		}
+		assert_eq!(index.objects_between(10., 15.).obstacles.map(|x| x.time).collect::<Vec<_>>(), [0., 11.]);
+		assert_eq!(index.objects_between(0., 2.).beats.len(), 1);
+		// only the long wall & the walls overlapping the window are visited
+		assert_eq!(index.objects_between(10., 15.).obstacles.candidates.len(), 5);
+		assert_eq!(index.objects_between(21., 29.).obstacles.candidates.len(), 0);
+	}
+
+	#[test]
+	fn test_obstacle_kind() {
+		let obstacle = |x: f32, y: f32, width: f32, height: f32, duration: f32| Obstacle {
+			beat: 0.,
+			time: 0.,
+			x,
+			y,
+			duration_beats: duration,
+			duration,
+			end_time: duration,
+			width,
+			height,
+			fake: false
+		};
+		assert_eq!(obstacle(0., 2., 4., 3., 1.).kind(), ObstacleKind::Crouch);
+		assert_eq!(obstacle(1., 2., 2., 3., 1.).kind(), ObstacleKind::Crouch);
+		assert_eq!(obstacle(0., 0., 1., 5., 5.).kind(), ObstacleKind::Full);
+		assert_eq!(obstacle(3., 0., 1., 5., 5.).kind(), ObstacleKind::Full);
+		// a top wall over a single center lane can be leaned around, but still blocks the view
+		assert_eq!(obstacle(1., 2., 1., 3., 1.).kind(), ObstacleKind::VisionBlock);
+		assert_eq!(obstacle(1., 0., 1., 5., 1.).kind(), ObstacleKind::VisionBlock);
+		assert_eq!(obstacle(1., 0., 1., 5., 0.1).kind(), ObstacleKind::Full);
+		assert_eq!(obstacle(0., 0., 4., 1., 5.).kind(), ObstacleKind::Other);
+		assert_eq!(obstacle(0., 3., 4., 2., 5.).kind(), ObstacleKind::Other);
+
+		let mut map = beatmap(Vec::new());
+		map.obstacles = vec![obstacle(0., 0., 1., 5., 1.), obstacle(2., 0., 1., 5., 1.), obstacle(0., 2., 4., 3., 1.)];
+		assert_eq!(map.vision_blocks().map(|x| x.x).collect::<Vec<_>>(), [2.]);
+	}
+
+	#[test]
+	fn test_cells_at() {
+		let map = beatmap(
+			[0.5, 1., 1.5, 2., 3.]
+				.into_iter()
+				.map(|time| Beat {
+					time,
+					..beat(1., 0., NoteColor::Red, NoteDirection::Down)
+				})
+				.collect()
+		);
+		let times = |time, lookahead| map.cells_at(time, lookahead).iter().map(|x| x.time).collect::<Vec<_>>();
+		assert_eq!(times(1., 1.), [1., 1.5, 2.]);
+		assert_eq!(times(1.25, 0.5), [1.5]);
+		assert_eq!(times(2.1, 0.5), [] as [f32; 0]);
+		assert_eq!(times(0., 0.), [] as [f32; 0]);
+		assert_eq!(times(3., 10.), [3.]);
+	}
+
+	#[test]
+	fn test_swing_pairs() {
+		let map = v3::Beatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [
+					{ "b": 1.0, "x": 1, "y": 0, "c": 0, "d": 1 },
+					{ "b": 1.25, "x": 2, "y": 0, "c": 1, "d": 1 },
+					{ "b": 1.5, "x": 1, "y": 0, "c": 0, "d": 0 },
+					{ "b": 2.0, "x": 1, "y": 0, "c": 0, "d": 1 },
+					{ "b": 3.0, "x": 2, "y": 0, "c": 1, "d": 0 }
+				],
+				"bombNotes": [],
+				"obstacles": [],
+				"burstSliders": [],
+				"bpmEvents": []
+			}"#
+		)
+		.unwrap();
+		let map = Beatmap::from_v3(map, 60.).unwrap();
+
+		let pairs: Vec<_> = map.swing_pairs().into_iter().map(|(a, b)| (a.time, b.time)).collect();
+		assert_eq!(pairs, [(1., 1.5), (1.5, 2.)]);
+		assert_eq!(map.swing_pairs_within(2.).len(), 3);
+		assert!(map.swing_pairs_within(0.25).is_empty());
+	}
+
+	#[test]
+	fn test_chain_validate() {
+		let chain = Chain {
+			beat: 1.,
+			time: 1.,
+			x: 1.,
+			y: 0.,
+			color: NoteColor::Red,
+			direction: NoteDirection::Down,
+			tail_beat: 1.5,
+			tail_time: 1.5,
+			tail_x: 1.,
+			tail_y: 1.,
+			num_slices: 4,
+			squish_factor: 0.5,
+			fake: false
+		};
+		assert_eq!(chain.validate(), None);
+		assert_eq!(Chain { num_slices: 1, ..chain.clone() }.validate(), Some(ChainWarning::TooFewSlices));
+		assert_eq!(Chain { squish_factor: 0., ..chain.clone() }.validate(), Some(ChainWarning::InvalidSquish));
+		assert_eq!(Chain { squish_factor: 1.5, ..chain.clone() }.validate(), Some(ChainWarning::InvalidSquish));
+		assert_eq!(
+			Chain {
+				squish_factor: f32::NAN,
+				..chain.clone()
+			}
+			.validate(),
+			Some(ChainWarning::InvalidSquish)
+		);
+		assert_eq!(Chain { tail_beat: 1., tail_y: 0., ..chain }.validate(), Some(ChainWarning::Degenerate));
+	}
+
+	#[test]
+	fn test_stats() {
+		let map = Beatmap::from_file("tests/data/maps/1579c_ExpertPlusStandard.dat", 222.0).unwrap();
+		let stats = map.stats();
+		assert_eq!((stats.notes, stats.red_notes, stats.blue_notes), (7743, 3866, 3877));
+		assert_eq!((stats.bombs, stats.obstacles, stats.chains), (4, 3, 0));
+		assert!((stats.nps - 20.659).abs() < 0.01);
+		assert_eq!(stats.density.len(), 376);
+		assert_eq!(stats.density.iter().sum::<u32>(), 7743);
+		assert_eq!(stats.peak_nps(), 112.);
+	}
+
+	#[test]
+	fn test_peak_nps() {
+		let mut map = beatmap(Vec::new());
+		assert_eq!(map.peak_nps(1.), (0., 0.));
+		assert!(map.nps_curve(1., 0.5).is_empty());
+
+		for time in [0.5, 2., 2.25, 2.5, 2.75, 4.] {
+			map.beats.push(Beat {
+				time,
+				..beat(0., 0., NoteColor::Red, NoteDirection::Down)
+			});
+		}
+		assert_eq!(map.peak_nps(1.), (4., 2.));
+		// the whole map fits in the window, so it's averaged over the full window length
+		assert_eq!(map.peak_nps(10.), (0.6, 0.5));
+		assert_eq!(map.peak_nps(0.), (0., 0.));
+
+		let curve = map.nps_curve(1., 1.);
+		assert_eq!(curve, [(0., 1.), (1., 0.), (2., 4.), (3., 0.), (4., 1.)]);
+	}
+
+	#[test]
+	fn test_direction_degrees() {
+		let directions = [
+			NoteDirection::Up,
+			NoteDirection::Down,
+			NoteDirection::Left,
+			NoteDirection::Right,
+			NoteDirection::UpLeft,
+			NoteDirection::UpRight,
+			NoteDirection::DownLeft,
+			NoteDirection::DownRight
+		];
+		for direction in directions {
+			let degrees = direction.to_degrees().unwrap();
+			assert_eq!(NoteDirection::from_degrees(degrees), direction);
+			assert_eq!(NoteDirection::from_degrees(degrees + 360. * 2.), direction);
+			assert_eq!(NoteDirection::from_degrees(degrees - 360.), direction);
+
+			let vector = direction.to_unit_vector().unwrap();
+			assert!((vector.length() - 1.).abs() < 1e-6);
+			let radians = degrees.to_radians();
+			assert!(vector.abs_diff_eq(Vec2::new(radians.sin(), radians.cos()), 1e-6), "{direction:?}");
+		}
+
+		assert_eq!(NoteDirection::Right.to_degrees(), Some(90.));
+		assert_eq!(NoteDirection::from_degrees(-20.), NoteDirection::Up);
+		assert_eq!(NoteDirection::from_degrees(160.), NoteDirection::Down);
+		assert_eq!(NoteDirection::from_degrees(f32::NAN), NoteDirection::Any);
+		assert_eq!(NoteDirection::Any.to_degrees(), None);
+		assert_eq!(NoteDirection::Any.to_unit_vector(), None);
+		assert_eq!(v3::NoteDirection::from_degrees(270.), v3::NoteDirection::Left);
+		assert_eq!(v2::NoteDirection::DownLeft.to_degrees(), Some(225.));
+
+		assert_eq!(v3::NoteDirection::precise(1090), Some(v3::NoteDirection::Precise(1090)));
+		assert_eq!(v3::NoteDirection::Precise(1090).to_degrees(), Some(270.));
+		assert_eq!(v2::NoteDirection::precise(1360).and_then(|x| x.to_degrees()), Some(180.));
+		assert_eq!(v2::NoteDirection::precise(5), None);
+		assert_eq!(v3::NoteDirection::precise(1361), None);
+		// unchecked out of range values are clamped rather than overflowing
+		assert_eq!(v2::NoteDirection::Precise(5).to_degrees(), Some(180.));
+		assert_eq!(v3::NoteDirection::Precise(u16::MAX).to_degrees(), Some(180.));
+	}
+
+	#[test]
+	fn test_cut_angle() {
+		let mut note = beat(1., 0., NoteColor::Red, NoteDirection::DownRight);
+		assert_eq!(note.cut_angle(), Some(45.));
+		note.angle_offset = Some(-15.);
+		assert_eq!(note.cut_angle(), Some(30.));
+
+		note.direction = NoteDirection::Any;
+		assert_eq!(note.cut_angle(), None);
+		assert_eq!(note.effective_cut_degrees(), Some(195.));
+
+		// both conventions describe the same direction
+		for direction in [NoteDirection::Up, NoteDirection::Left, NoteDirection::DownLeft, NoteDirection::UpRight] {
+			for offset in [-120., 0., 45.] {
+				note.direction = direction;
+				note.angle_offset = Some(offset);
+				assert_eq!((note.cut_angle().unwrap() - direction.angle().unwrap() - offset).rem_euclid(360.), 0.);
+			}
+		}
+	}
+
+	#[test]
+	fn test_effective_cut() {
+		let mut note = beat(1., 0., NoteColor::Red, NoteDirection::Right);
+		assert_eq!(note.effective_cut_degrees(), Some(90.));
+		assert!(note.effective_cut_vector().unwrap().abs_diff_eq(Vec2::X, 1e-6));
+
+		// a positive offset rotates counterclockwise, agreeing with the game's rotation in `cut_angle`
+		note.angle_offset = Some(45.);
+		assert_eq!(note.effective_cut_degrees(), Some(45.));
+		assert_eq!(NoteDirection::from_degrees(note.effective_cut_degrees().unwrap()), NoteDirection::UpRight);
+		assert_eq!(note.cut_angle(), Some(135.));
+		assert_eq!(NoteDirection::UpRight.angle(), Some(135.));
+
+		note.angle_offset = Some(-120.);
+		assert_eq!(note.effective_cut_degrees(), Some(210.));
+		assert!(
+			note.effective_cut_vector()
+				.unwrap()
+				.abs_diff_eq(Vec2::new(-0.5, -(3f32.sqrt()) / 2.), 1e-6)
+		);
+
+		note.direction = NoteDirection::Any;
+		assert_eq!(note.effective_cut_degrees(), Some(300.));
+		note.angle_offset = None;
+		assert_eq!(note.effective_cut_degrees(), None);
+		assert_eq!(note.effective_cut_vector(), None);
+	}
+
+	#[test]
+	fn test_at_position() {
+		let mut map = beatmap(vec![
+			Beat {
+				time: 1.,
+				..beat(1., 0., NoteColor::Red, NoteDirection::Down)
+			},
+			Beat {
+				time: 2.,
+				..beat(2., 0., NoteColor::Blue, NoteDirection::Down)
+			},
+			Beat {
+				time: 2.,
+				..beat(1., 0., NoteColor::Red, NoteDirection::Up)
+			},
+		]);
+		map.bombs.push(Bomb {
+			beat: 3.,
+			time: 3.,
+			x: 0.,
+			y: 2.,
+			fake: false
+		});
+
+		assert_eq!(map.note_at_position(1., 0., 2.05, 0.1).unwrap().direction, NoteDirection::Up);
+		assert_eq!(map.note_at_position(1., 0., 1.05, 0.1).unwrap().direction, NoteDirection::Down);
+		assert!(map.note_at_position(1., 0., 1.5, 0.1).is_none());
+		assert!(map.note_at_position(3., 0., 2., 0.1).is_none());
+		assert!(map.bomb_at_position(0., 2., 3., 0.).is_some());
+		assert!(map.bomb_at_position(0., 1., 3., 0.).is_none());
+	}
+
+	#[test]
+	fn test_mirror_twice_is_identity() {
+		let map = Beatmap::from_file("tests/data/maps/1579c_ExpertPlusStandard.dat", 222.0).unwrap();
+		let mut mirrored = map.clone();
+		mirrored.mirror();
+		assert!(map.semantically_eq(&mirrored, 1e-4).is_err());
+		mirrored.mirror();
+		assert_eq!(map.semantically_eq(&mirrored, 1e-4), Ok(()));
+
+		let map = v3::Beatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [],
+				"bombNotes": [],
+				"obstacles": [{ "b": 1.0, "x": 0, "y": 0, "d": 1.0, "w": 2, "h": 5 }],
+				"burstSliders": [{ "b": 1.0, "x": 0, "y": 0, "c": 0, "d": 6, "tb": 1.5, "tx": 1, "ty": 2, "sc": 3, "s": 0.5 }],
+				"bpmEvents": []
+			}"#
+		)
+		.unwrap();
+		let mut map = Beatmap::from_v3(map, 60.).unwrap();
+		map.mirror();
+		assert_eq!((map.obstacles[0].x, map.obstacles[0].width), (2., 2.));
+		let chain = &map.chains[0];
+		assert_eq!((chain.x, chain.tail_x, chain.color, chain.direction), (3., 2., NoteColor::Blue, NoteDirection::DownRight));
+	}
+
+	#[test]
+	fn test_validate_across_versions() {
+		let v2 = r#"{
+			"_version": "2.6.0",
+			"_notes": [
+				{ "_time": 1.0, "_lineIndex": 1, "_lineLayer": 0, "_type": 0, "_cutDirection": 1 },
+				{ "_time": 1.0, "_lineIndex": 1, "_lineLayer": 0, "_type": 1, "_cutDirection": 1 },
+				{ "_time": 2.0, "_lineIndex": 2, "_lineLayer": 1, "_type": 3, "_cutDirection": 0 },
+				{ "_time": 2.0, "_lineIndex": 2, "_lineLayer": 1, "_type": 1, "_cutDirection": 1 },
+				{ "_time": 3.5, "_lineIndex": 0, "_lineLayer": 0, "_type": 0, "_cutDirection": 1 },
+				{ "_time": 5.0, "_lineIndex": 4, "_lineLayer": 0, "_type": 1, "_cutDirection": 1 }
+			],
+			"_obstacles": [{ "_time": 3.0, "_lineIndex": 0, "_type": 0, "_duration": 1.0, "_width": 1 }],
+			"_events": []
+		}"#;
+		let v3 = r#"{
+			"version": "3.2.0",
+			"colorNotes": [
+				{ "b": 1.0, "x": 1, "y": 0, "c": 0, "d": 1 },
+				{ "b": 1.0, "x": 1, "y": 0, "c": 1, "d": 1 },
+				{ "b": 2.0, "x": 2, "y": 1, "c": 1, "d": 1 },
+				{ "b": 3.5, "x": 0, "y": 0, "c": 0, "d": 1 },
+				{ "b": 5.0, "x": 4, "y": 0, "c": 1, "d": 1 }
+			],
+			"bombNotes": [{ "b": 2.0, "x": 2, "y": 1 }],
+			"obstacles": [{ "b": 3.0, "x": 0, "y": 0, "d": 1.0, "w": 1, "h": 5 }],
+			"burstSliders": [{ "b": 6.0, "x": 1, "y": 0, "c": 0, "d": 1, "tb": 5.5, "tx": 1, "ty": 1, "sc": 3, "s": 1 }],
+			"bpmEvents": []
+		}"#;
+
+		let kinds = |map: Beatmap| map.validate().into_iter().map(|i| (i.time, i.object, i.kind)).collect::<Vec<_>>();
+		let expected = vec![
+			(5., ObjectIndex::Beat(4), IssueKind::OutOfGrid),
+			(1., ObjectIndex::Beat(0), IssueKind::OverlappingNotes),
+			(3.5, ObjectIndex::Beat(3), IssueKind::NoteInObstacle(0)),
+			(2., ObjectIndex::Bomb(0), IssueKind::BombOnNote),
+		];
+		assert_eq!(kinds(Beatmap::from_v2(v2::Beatmap::from_string(v2).unwrap(), 60.).unwrap()), expected);
+
+		let mut v3 = kinds(Beatmap::from_v3(v3::Beatmap::from_string(v3).unwrap(), 60.).unwrap());
+		assert_eq!(v3.pop(), Some((6., ObjectIndex::Chain(0), IssueKind::ReversedChain)));
+		assert_eq!(v3, expected);
+	}
+
+	#[test]
+	fn test_v4_missing_data() {
+		let map = v4::Beatmap::from_string(
+			r#"{
+				"version": "4.0.0",
+				"colorNotes": [{ "b": 1.0, "i": 0 }, { "b": 2.0, "i": 3 }],
+				"colorNotesData": [{ "x": 1, "y": 0, "c": 0, "d": 1 }],
+				"bombNotes": [],
+				"bombNotesData": [],
+				"obstacles": [],
+				"obstaclesData": [],
+				"chains": [],
+				"chainsData": []
+			}"#
+		)
+		.unwrap();
+		assert!(matches!(Beatmap::from_v4(map, 60.), Err(ConversionError::MissingData(ObjectIndex::Beat(1)))));
+	}
+
+	#[test]
+	fn test_coalesce_obstacles() {
+		let map = v3::Beatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [],
+				"bombNotes": [],
+				"obstacles": [
+					{ "b": 1.0, "x": 0, "y": 0, "d": 1.0, "w": 1, "h": 5 },
+					{ "b": 1.5, "x": 3, "y": 0, "d": 0.5, "w": 1, "h": 5 },
+					{ "b": 2.0, "x": 0, "y": 0, "d": 1.0, "w": 1, "h": 5 },
+					{ "b": 3.05, "x": 0, "y": 0, "d": 0.95, "w": 1, "h": 5 },
+					{ "b": 4.0, "x": 0, "y": 0, "d": 1.0, "w": 2, "h": 5 },
+					{ "b": 6.0, "x": 0, "y": 0, "d": 1.0, "w": 1, "h": 5 }
+				],
+				"burstSliders": [],
+				"bpmEvents": []
+			}"#
+		)
+		.unwrap();
+		let mut map = Beatmap::from_v3(map, 60.).unwrap();
+		map.coalesce_obstacles(0.1);
+
+		// the first three walls become one, but the wider & later walls are kept
+		let obstacles: Vec<_> = map.obstacles.iter().map(|o| (o.time, o.end_time, o.width)).collect();
+		assert_eq!(obstacles, [(1., 4., 1.), (1.5, 2., 1.), (4., 5., 2.), (6., 7., 1.)]);
+		assert_eq!((map.obstacles[0].duration, map.obstacles[0].duration_beats()), (3., 3.));
+	}
+
+	#[test]
+	fn test_most_common_direction_per_color() {
+		let mut map = beatmap(vec![
+			beat(0., 0., NoteColor::Red, NoteDirection::Down),
+			beat(1., 0., NoteColor::Red, NoteDirection::Up),
+			beat(1., 1., NoteColor::Red, NoteDirection::Up),
+			beat(2., 0., NoteColor::Blue, NoteDirection::Left),
+			beat(3., 0., NoteColor::Blue, NoteDirection::Right),
+		]);
+		let counts = map.count_by_color_and_direction();
+		assert_eq!(counts[&(NoteColor::Red, NoteDirection::Up)], 2);
+		assert_eq!(counts.values().sum::<usize>(), 5);
+		// blue is tied, so the earlier direction wins
+		assert_eq!(map.most_common_direction_per_color(), (NoteDirection::Up, NoteDirection::Left));
+
+		map.beats.retain(|b| b.color == NoteColor::Red);
+		assert_eq!(map.most_common_direction_per_color(), (NoteDirection::Up, NoteDirection::Any));
+	}
+
+	#[test]
+	fn test_iter_events_sorted() {
+		let mut map = beatmap(vec![
+			Beat {
+				time: 1.,
+				..beat(1., 0., NoteColor::Red, NoteDirection::Down)
+			},
+			Beat {
+				time: 3.,
+				..beat(2., 0., NoteColor::Blue, NoteDirection::Down)
+			},
+		]);
+		map.chains.push(Chain {
+			beat: 1.,
+			time: 1.,
+			x: 1.,
+			y: 0.,
+			color: NoteColor::Red,
+			direction: NoteDirection::Down,
+			tail_beat: 1.5,
+			tail_time: 1.5,
+			tail_x: 1.,
+			tail_y: 2.,
+			num_slices: 4,
+			squish_factor: 1.,
+			fake: false
+		});
+		map.obstacles.push(Obstacle {
+			beat: 0.5,
+			time: 0.5,
+			x: 0.,
+			y: 0.,
+			duration_beats: 1.5,
+			duration: 1.5,
+			end_time: 2.,
+			width: 1.,
+			height: 5.,
+			fake: false
+		});
+
+		let events: Vec<_> = map
+			.iter_events_sorted()
+			.map(|e| {
+				let kind = match e {
+					TimedEvent::Beat(_) => "beat",
+					TimedEvent::Bomb(_) => "bomb",
+					TimedEvent::ObstacleStart(_) => "obstacle start",
+					TimedEvent::ObstacleEnd(_) => "obstacle end",
+					TimedEvent::ChainHead(_) => "chain head",
+					TimedEvent::ChainTail(_) => "chain tail"
+				};
+				(e.time(), kind)
+			})
+			.collect();
+		assert_eq!(events, [(0.5, "obstacle start"), (1., "beat"), (1., "chain head"), (1.5, "chain tail"), (2., "obstacle end"), (3., "beat")]);
+	}
+
+	#[test]
+	fn test_color_stats() {
+		let mut map = beatmap(vec![
+			Beat {
+				time: 1.,
+				..beat(1., 0., NoteColor::Red, NoteDirection::Down)
+			},
+			Beat {
+				time: 2.,
+				..beat(1., 0., NoteColor::Red, NoteDirection::Any)
+			},
+			Beat {
+				time: 2.,
+				..beat(2., 0., NoteColor::Blue, NoteDirection::Down)
+			},
+			Beat {
+				time: 3.,
+				..beat(2., 0., NoteColor::Red, NoteDirection::Up)
+			},
+		]);
+		map.chains.push(Chain {
+			beat: 2.,
+			time: 2.,
+			x: 2.,
+			y: 0.,
+			color: NoteColor::Blue,
+			direction: NoteDirection::Down,
+			tail_beat: 2.5,
+			tail_time: 2.5,
+			tail_x: 2.,
+			tail_y: 2.,
+			num_slices: 5,
+			squish_factor: 1.,
+			fake: false
+		});
+
+		let stats = map.color_stats();
+		assert_eq!((stats.red.notes, stats.red.chain_links, stats.red.nps), (3, 0, 1.5));
+		assert_eq!((stats.blue.notes, stats.blue.chain_links, stats.blue.nps), (1, 4, 0.5));
+		assert_eq!(stats.red.directions, [1, 1, 0, 0, 0, 0, 0, 0, 1]);
+		assert_eq!(stats.get(NoteColor::Blue).direction_count(NoteDirection::Down), 1);
+		assert_eq!(stats.balance, 0.75);
+
+		assert_eq!(beatmap(Vec::new()).color_stats().balance, 0.5);
+	}
+
+	#[test]
+	fn test_v2_fake_handling() {
+		let map = r#"{
+			"_version": "2.6.0",
+			"_notes": [
+				{ "_time": 1.0, "_lineIndex": 1, "_lineLayer": 0, "_type": 0, "_cutDirection": 1 },
+				{ "_time": 2.0, "_lineIndex": 2, "_lineLayer": 0, "_type": 1, "_cutDirection": 1, "_customData": { "_fake": true } },
+				{ "_time": 3.0, "_lineIndex": 0, "_lineLayer": 2, "_type": 3, "_cutDirection": 0, "_customData": { "_fake": true } }
+			],
+			"_obstacles": [{ "_time": 4.0, "_lineIndex": 0, "_type": 0, "_duration": 1.0, "_width": 1, "_customData": { "_fake": true } }],
+			"_events": []
+		}"#;
+		let convert = |include_fake| {
+			let beatmap = AnyverBeatmap::V2(v2::Beatmap::from_string(map).unwrap());
+			Beatmap::from_any_with(beatmap, 60., ConversionOptions { include_fake }).unwrap()
+		};
+
+		let excluded = convert(FakeHandling::Exclude);
+		assert_eq!((excluded.beats.len(), excluded.bombs.len(), excluded.obstacles.len()), (1, 0, 0));
+		let flagged = convert(FakeHandling::IncludeFlagged);
+		assert_eq!(flagged.beats.iter().map(|x| (x.time, x.fake)).collect::<Vec<_>>(), [(1., false), (2., true)]);
+		assert!(flagged.bombs[0].fake && flagged.obstacles[0].fake);
+		let only = convert(FakeHandling::Only);
+		assert_eq!((only.beats.len(), only.bombs.len(), only.obstacles.len()), (1, 1, 1));
+		assert_eq!(only.beats[0].time, 2.);
+	}
+
+	#[test]
+	fn test_grid_position() {
+		let mut note = beat(1., 2., NoteColor::Red, NoteDirection::Down);
+		assert_eq!((note.lane(), note.layer()), (Some(1), Some(2)));
+		assert_eq!(note.position(), GridPosition::Grid { lane: 1, layer: 2 });
+
+		// precise positions aren't truncated to a lane, and neither are positions off the grid
+		note.x = 1.5;
+		assert_eq!(note.lane(), None);
+		assert_eq!(note.position(), GridPosition::Precise { x: 1.5, y: 2. });
+		(note.x, note.y) = (4., -1.);
+		assert_eq!((note.lane(), note.layer()), (None, None));
+	}
+
+	#[test]
+	fn test_obstacle_covered_lanes() {
+		let obstacle = |x, width| {
+			Obstacle::from(v2::Obstacle {
+				beat: 0.,
+				wall_type: 0,
+				x,
+				duration: 1.,
+				width,
+				custom_data: None,
+				extra: HashMap::new()
+			})
+		};
+		assert_eq!(obstacle(1., 2.).covered_lanes(), 1..3);
+		assert_eq!(obstacle(0.5, 1.).covered_lanes(), 0..2);
+		assert_eq!(obstacle(-1., 2.).covered_lanes(), 0..1);
+		assert_eq!(obstacle(3., 2.).covered_lanes(), 3..4);
+		assert_eq!(obstacle(5., 1.).covered_lanes(), 4..4);
+	}
+
+	#[test]
+	fn test_single_color_spans() {
+		let notes = [
+			(0., NoteColor::Red),
+			(0., NoteColor::Blue),
+			(1., NoteColor::Blue),
+			(2., NoteColor::Blue),
+			(6., NoteColor::Blue),
+			(7., NoteColor::Red),
+			(7.5, NoteColor::Blue)
+		];
+		let map = beatmap(
+			notes
+				.into_iter()
+				.map(|(time, color)| Beat {
+					time,
+					..beat(0., 0., color, NoteDirection::Down)
+				})
+				.collect()
+		);
+		// the blue-only section from 0 to 6 seconds, but not the lone red note at 7 seconds
+		assert_eq!(map.single_color_spans(4.), [(0., 6., NoteColor::Blue)]);
+		assert_eq!(map.single_color_spans(6.), []);
+		assert_eq!(map.single_color_spans(-1.).len(), 4);
+	}
+
+	#[test]
+	fn test_chain_links() {
+		let map = v3::Beatmap::from_string(
+			r#"{
+				"version": "3.2.0",
+				"colorNotes": [],
+				"bombNotes": [],
+				"obstacles": [],
+				"burstSliders": [{ "b": 1.0, "x": 0, "y": 1, "c": 0, "d": 3, "tb": 1.5, "tx": 3, "ty": 1, "sc": 4, "s": 1 }],
+				"bpmEvents": []
+			}"#
+		)
+		.unwrap();
+		let mut chain = Beatmap::from_v3(map, 60.).unwrap().chains.remove(0);
+		let positions = |chain: &Chain| chain.links().iter().map(|x| (x.x, x.y)).collect::<Vec<_>>();
+		assert_eq!(positions(&chain), [(1., 1.), (2., 1.), (3., 1.)]);
+		assert_eq!(chain.links()[2].time, 1.5);
+
+		chain.squish_factor = 0.5;
+		assert_eq!(positions(&chain), [(0.5, 1.), (1., 1.), (1.5, 1.)]);
+		assert!((chain.links()[0].time - 7. / 6.).abs() < 1e-6);
+
+		chain.num_slices = 1;
+		assert!(chain.links().is_empty());
+
+		// a zero-length chain stacks its links on the head
+		(chain.num_slices, chain.tail_x, chain.tail_y, chain.tail_time) = (3, chain.x, chain.y, chain.time);
+		assert_eq!(positions(&chain), [(0., 1.), (0., 1.)]);
+	}
+
+	#[test]
+	fn test_append_after() {
+		let map = |obstacles: &str, chains: &str| {
+			let json = format!(
+				r#"{{ "version": "3.2.0", "colorNotes": [{{ "b": 1, "x": 1, "y": 0, "c": 0, "d": 1 }}], "bombNotes": [], "obstacles": [{obstacles}], "burstSliders": [{chains}], "bpmEvents": [] }}"#
+			);
+			Beatmap::from_v3(v3::Beatmap::from_string(json).unwrap(), 60.).unwrap()
+		};
+		// the first map ends with its obstacle, the second with its chain's tail
+		let mut first = map(r#"{ "b": 0.5, "x": 0, "y": 0, "d": 3, "w": 1, "h": 5 }"#, "");
+		let second = map("", r#"{ "b": 2, "x": 1, "y": 2, "c": 1, "d": 1, "tb": 4, "tx": 1, "ty": 0, "sc": 4, "s": 1 }"#);
+		assert_eq!((first.end_time(), second.end_time()), (3.5, 4.));
+
+		first.append_after(second, 0.5);
+		assert_eq!(first.beats.iter().map(|x| x.time).collect::<Vec<_>>(), [1., 5.]);
+		assert_eq!((first.chains[0].time, first.chains[0].tail_time), (6., 8.));
+		assert_eq!(first.end_time(), 8.);
+
+		let mut merged = map("", "");
+		merged.merge(map("", ""));
+		assert_eq!(merged.beats.len(), 2);
+		assert_eq!(beatmap(Vec::new()).end_time(), 0.);
+	}
+
+	#[test]
+	fn test_shift_time() {
+		let json = r#"{
+			"version": "3.2.0",
+			"colorNotes": [{ "b": 1, "x": 1, "y": 0, "c": 0, "d": 1 }],
+			"bombNotes": [{ "b": 2, "x": 0, "y": 2 }],
+			"obstacles": [{ "b": 3, "x": 0, "y": 0, "d": 1, "w": 1, "h": 5 }],
+			"burstSliders": [{ "b": 4, "x": 1, "y": 2, "c": 1, "d": 1, "tb": 5, "tx": 1, "ty": 0, "sc": 4, "s": 1 }],
+			"bpmEvents": []
+		}"#;
+		let original = Beatmap::from_v3(v3::Beatmap::from_string(json).unwrap(), 60.).unwrap();
+		let mut map = original.clone();
+		map.shift_time(-1.5, NegativeTimes::Allow).unwrap();
+
+		let times = |map: &Beatmap| map.objects().map(|x| (x.time(), x.end_time())).collect::<Vec<_>>();
+		let shifted: Vec<_> = times(&original).into_iter().map(|(start, end)| (start - 1.5, end - 1.5)).collect();
+		assert_eq!(times(&map), shifted);
+		assert_eq!(map.beats[0].time, -0.5);
+		assert_eq!(map.beats[0].beat(), 1.);
+
+		let mut map = original.clone();
+		assert!(map.shift_time(-1.5, NegativeTimes::Error).is_err());
+		assert_eq!(map.beats[0].time, 1.);
+		map.shift_time(-3.5, NegativeTimes::Clamp).unwrap();
+		assert_eq!((map.beats[0].time, map.bombs[0].time), (0., 0.));
+		// the obstacle from 3 to 4 seconds is cut down to 0 to 0.5 seconds
+		let obstacle = &map.obstacles[0];
+		assert_eq!((obstacle.time, obstacle.end_time, obstacle.duration), (0., 0.5, 0.5));
 	}
 }
@@ -1,4 +1,6 @@
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serializer};
+
+use super::standard::NoteDirection;
 
 pub fn deserialize_precision<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
 	let original = i32::deserialize(deserializer)?;
@@ -8,3 +10,220 @@ pub fn deserialize_precision<'de, D: Deserializer<'de>>(deserializer: D) -> Resu
 		Ok(original as f32)
 	}
 }
+
+/// The inverse of [`deserialize_precision`]; whole positions are written as-is, and fractional positions are encoded in
+/// the mapping extensions precision format.
+pub fn serialize_precision<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.serialize_i32(encode_precision(*value))
+}
+
+fn encode_precision(value: f32) -> i32 {
+	if value.fract() == 0. {
+		value as i32
+	} else if value.is_sign_negative() {
+		((value - 1.) * 1000.).round() as i32
+	} else {
+		((value + 1.) * 1000.).round() as i32
+	}
+}
+
+/// The rotation of each base direction in mapping extensions' precise direction space, where `1000` points down and
+/// rotation increases towards the left.
+fn precise_rotation(direction: NoteDirection) -> Option<f32> {
+	match direction {
+		NoteDirection::Down => Some(0.),
+		NoteDirection::DownLeft => Some(45.),
+		NoteDirection::Left => Some(90.),
+		NoteDirection::UpLeft => Some(135.),
+		NoteDirection::Up => Some(180.),
+		NoteDirection::UpRight => Some(225.),
+		NoteDirection::Right => Some(270.),
+		NoteDirection::DownRight => Some(315.),
+		NoteDirection::Any => None
+	}
+}
+
+/// Splits a mapping extensions precise direction (`1000..=1360`) into the nearest base direction and the remaining
+/// angle offset in degrees. Plain cut directions (`0..=8`) are returned with no offset.
+pub fn split_precise_direction(value: u32) -> (NoteDirection, f32) {
+	let direction = match value {
+		0 => NoteDirection::Up,
+		1 => NoteDirection::Down,
+		2 => NoteDirection::Left,
+		3 => NoteDirection::Right,
+		4 => NoteDirection::UpLeft,
+		5 => NoteDirection::UpRight,
+		6 => NoteDirection::DownLeft,
+		7 => NoteDirection::DownRight,
+		1000..1023 | 1338..=1360 => NoteDirection::Down,
+		1023..1068 => NoteDirection::DownLeft,
+		1068..1113 => NoteDirection::Left,
+		1113..1158 => NoteDirection::UpLeft,
+		1158..1203 => NoteDirection::Up,
+		1203..1248 => NoteDirection::UpRight,
+		1248..1293 => NoteDirection::Right,
+		1293..1338 => NoteDirection::DownRight,
+		_ => NoteDirection::Any
+	};
+	if !(1000..=1360).contains(&value) {
+		return (direction, 0.);
+	}
+
+	let rotation = (value - 1000) as f32;
+	let base = precise_rotation(direction).unwrap();
+	let offset = rotation - base;
+	// the top bucket wraps around to down
+	(direction, if offset > 180. { offset - 360. } else { offset })
+}
+
+/// The inverse of [`split_precise_direction`]. Directions without an offset are written as plain cut directions.
+pub fn combine(direction: NoteDirection, offset: f32) -> u32 {
+	match precise_rotation(direction) {
+		Some(base) if offset != 0. => 1000 + (base + offset).rem_euclid(360.).round() as u32,
+		_ => direction as u32
+	}
+}
+
+/// Decodes a v2 wall type into a v3-style `(y, height)` in layers, where full height walls are `(0, 5)` and crouch
+/// walls are `(2, 3)`.
+///
+/// Mapping extensions walls are supported in both of their encodings: `1000..=4000` is a ground-level wall with height
+/// `type - 1000`, and `4001..=4100000` is `4001 + height * 1000 + start_height`. In both, a height of `1000` spans the
+/// full 5 layers and a start height of `750` is 5 layers above the ground. Unknown types are treated as full height
+/// walls, matching the game.
+///
+/// Mapping extensions itself turns the start height into a precise line layer, `start_height / 750 * 5 * 1000 + 1334`,
+/// so `1334` is the ground in that space. The offset is taken back out here rather than carried over, so that a
+/// ground-level wall is `(0, 5)` like the vanilla full height wall it looks like in game.
+pub fn decode_wall_type(wall_type: u32) -> (f32, f32) {
+	let (height, start_height) = match wall_type {
+		0 => return (0., 5.),
+		1 => return (2., 3.),
+		1000..=4000 => (wall_type - 1000, 0),
+		4001..=4100000 => ((wall_type - 4001) / 1000, (wall_type - 4001) % 1000),
+		_ => return (0., 5.)
+	};
+	(start_height as f32 / 150., height as f32 / 200.)
+}
+
+/// The inverse of [`decode_wall_type`]. Full height & crouch walls are encoded as types `0` & `1`; anything else is
+/// rounded to the precision of the mapping extensions encoding, and clamped to the range it can represent.
+pub fn encode_wall_type(y: f32, height: f32) -> u32 {
+	if y == 0. && height == 5. {
+		return 0;
+	} else if y == 2. && height == 3. {
+		return 1;
+	}
+
+	let height = (height * 200.).round().clamp(0., 4000.) as u32;
+	let start_height = (y * 150.).round().clamp(0., 999.) as u32;
+	if start_height == 0 && height <= 3000 {
+		1000 + height
+	} else {
+		4001 + height * 1000 + start_height
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde::{Deserialize, Serialize};
+
+	use super::{combine, decode_wall_type, encode_wall_type, split_precise_direction, NoteDirection};
+
+	#[derive(Serialize, Deserialize)]
+	struct Position {
+		#[serde(deserialize_with = "super::deserialize_precision", serialize_with = "super::serialize_precision")]
+		x: f32
+	}
+
+	#[test]
+	fn test_precision_round_trip() {
+		for encoded in [0, 3, -2, 1500, 2250, -1500, -3750] {
+			let mut json = format!(r#"{{"x":{encoded}}}"#);
+			let position: Position = unsafe { simd_json::from_str(&mut json) }.unwrap();
+			assert_eq!(simd_json::to_string(&position).unwrap(), json);
+		}
+	}
+
+	#[test]
+	fn test_precision_whole_positions() {
+		// precise positions which land on a whole lane are written back as plain lanes
+		let mut json = String::from(r#"{"x":3000}"#);
+		let position: Position = unsafe { simd_json::from_str(&mut json) }.unwrap();
+		assert_eq!(position.x, 2.);
+		assert_eq!(simd_json::to_string(&position).unwrap(), r#"{"x":2}"#);
+	}
+
+	#[test]
+	fn test_split_precise_direction_boundaries() {
+		assert_eq!(split_precise_direction(1000), (NoteDirection::Down, 0.));
+		assert_eq!(split_precise_direction(1022), (NoteDirection::Down, 22.));
+		assert_eq!(split_precise_direction(1023), (NoteDirection::DownLeft, -22.));
+		assert_eq!(split_precise_direction(1067), (NoteDirection::DownLeft, 22.));
+		assert_eq!(split_precise_direction(1068), (NoteDirection::Left, -22.));
+		assert_eq!(split_precise_direction(1113), (NoteDirection::UpLeft, -22.));
+		assert_eq!(split_precise_direction(1158), (NoteDirection::Up, -22.));
+		assert_eq!(split_precise_direction(1203), (NoteDirection::UpRight, -22.));
+		assert_eq!(split_precise_direction(1248), (NoteDirection::Right, -22.));
+		assert_eq!(split_precise_direction(1293), (NoteDirection::DownRight, -22.));
+		assert_eq!(split_precise_direction(1337), (NoteDirection::DownRight, 22.));
+		assert_eq!(split_precise_direction(1338), (NoteDirection::Down, -22.));
+		assert_eq!(split_precise_direction(1360), (NoteDirection::Down, 0.));
+		assert_eq!(split_precise_direction(5), (NoteDirection::UpRight, 0.));
+	}
+
+	#[test]
+	fn test_combine_precise_direction() {
+		for value in (1001..1360).step_by(7) {
+			let (direction, offset) = split_precise_direction(value);
+			// precise directions that land exactly on a base direction are written back as that direction
+			let expected = if offset == 0. { direction as u32 } else { value };
+			assert_eq!(combine(direction, offset), expected);
+		}
+		assert_eq!(combine(NoteDirection::Left, 0.), NoteDirection::Left as u32);
+		assert_eq!(combine(NoteDirection::Down, -10.), 1350);
+		assert_eq!(combine(NoteDirection::Any, 30.), NoteDirection::Any as u32);
+	}
+
+	#[test]
+	fn test_decode_wall_type() {
+		assert_eq!(decode_wall_type(0), (0., 5.));
+		assert_eq!(decode_wall_type(1), (2., 3.));
+		assert_eq!(decode_wall_type(42), (0., 5.));
+
+		// the height & line layer mapping extensions computes for each type in its ObstacleController.Init patch, i.e.
+		// `height / 1000 * 5 * 1000 + 1000` & `start_height / 750 * 5 * 1000 + 1334`, worked out by hand
+		let vectors = [
+			// ground-level walls, of full & half height
+			(2000, 6000., 1334.),
+			(1500, 3500., 1334.),
+			// precise walls matching the vanilla full height & crouch walls
+			(4001 + 1000 * 1000, 6000., 1334.),
+			(4001 + 600 * 1000 + 300, 4000., 3334.),
+			// a one layer tall wall floating above the top layer
+			(4001 + 200 * 1000 + 750, 2000., 6334.),
+			// the walls of the 1579c fixture
+			(783174, 4895., 2487.333),
+			(263727, 2295., 6174.),
+			// the largest type mapping extensions decodes
+			(4100000, 21475., 7994.)
+		];
+		for (wall_type, height, layer) in vectors {
+			let (y, h) = decode_wall_type(wall_type);
+			let expected = ((layer - 1334.) / 1000., (height - 1000.) / 1000.);
+			assert!((y - expected.0).abs() < 1e-4 && (h - expected.1).abs() < 1e-4, "{wall_type}: {:?} != {expected:?}", (y, h));
+		}
+		assert_eq!(decode_wall_type(4100001), (0., 5.));
+	}
+
+	#[test]
+	fn test_encode_wall_type() {
+		for wall_type in [0, 1, 1500, 4001 + 200 * 1000 + 750, 4001 + 4000 * 1000 + 999] {
+			let (y, height) = decode_wall_type(wall_type);
+			assert_eq!(encode_wall_type(y, height), wall_type);
+		}
+		assert_eq!(encode_wall_type(0., 2.5), 1500);
+		// below-ground walls are clamped to the ground
+		assert_eq!(encode_wall_type(-1., 2.), 1400);
+	}
+}
@@ -1,22 +1,33 @@
 use std::{
+	collections::HashMap,
 	fs::File,
 	io::{BufReader, BufWriter, Read, Write},
-	path::Path
+	path::Path,
+	str::FromStr
 };
 
+use glam::Vec2;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
+use simd_json::{derived::ValueObjectAccessAsScalar, OwnedValue};
 
-#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+use super::{standard, v3};
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
 pub struct Beatmap {
-	#[serde(rename = "_version")]
+	#[serde(rename = "_version", alias = "version")]
 	pub version: String,
 	#[serde(rename = "_notes")]
 	pub notes: Vec<Note>,
 	#[serde(rename = "_obstacles")]
 	pub obstacles: Vec<Obstacle>,
+	#[serde(rename = "_events", default = "Vec::new")]
+	pub events: Vec<Event>,
 	#[serde(rename = "_bpmEvents", default = "Vec::new")]
-	pub bpm_events: Vec<BpmEvent>
+	pub bpm_events: Vec<BpmEvent>,
+	/// Fields which aren't part of this schema (e.g. ones added by editors), preserved so they survive a round trip.
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
 }
 
 impl Beatmap {
@@ -51,6 +62,92 @@ impl Beatmap {
 	pub fn from_file<P: AsRef<Path>>(path: P) -> simd_json::Result<Self> {
 		Self::from_reader(BufReader::new(File::open(path)?))
 	}
+
+	/// The number of color notes & bombs.
+	pub fn total_note_count(&self) -> usize {
+		self.notes.len()
+	}
+
+	pub fn total_obstacle_count(&self) -> usize {
+		self.obstacles.len()
+	}
+
+	/// Always `0`, as v2 beatmaps can't contain chains.
+	pub fn total_chain_count(&self) -> usize {
+		0
+	}
+
+	/// Splits `notes` into color notes & bombs, in that order.
+	pub fn split_notes_and_bombs(&self) -> (Vec<&Note>, Vec<&Note>) {
+		self.notes.iter().partition(|x| x.note_type != NoteType::Bomb)
+	}
+}
+
+impl FromStr for Beatmap {
+	type Err = simd_json::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::from_string(s)
+	}
+}
+
+/// Converts a v3 beatmap, carrying over custom data as-is. Fake objects are marked `_fake` in their custom data, and
+/// rotation & color boost events are merged into `events`. Chains, arcs, waypoints & lighting event boxes can't be
+/// represented in v2, so they're dropped; [`super::AnyverBeatmap::into_version`] fails instead.
+impl From<v3::Beatmap> for Beatmap {
+	fn from(value: v3::Beatmap) -> Self {
+		let fake = |mut note: Note| {
+			note.custom_data = Some(mark_fake(note.custom_data));
+			note
+		};
+		let mut notes: Vec<Note> = value
+			.color_notes
+			.into_iter()
+			.map(Note::from)
+			.chain(value.bomb_notes.into_iter().map(Note::from))
+			.chain(value.fake_color_notes.into_iter().flatten().map(|x| fake(Note::from(x))))
+			.chain(value.fake_bomb_notes.into_iter().flatten().map(|x| fake(Note::from(x))))
+			.collect();
+		notes.sort_by(|a, b| a.beat.total_cmp(&b.beat));
+
+		let mut obstacles: Vec<Obstacle> = value.obstacles.into_iter().map(Obstacle::from).collect();
+		for obstacle in value.fake_obstacles.into_iter().flatten() {
+			let mut obstacle = Obstacle::from(obstacle);
+			obstacle.custom_data = Some(mark_fake(obstacle.custom_data));
+			obstacles.push(obstacle);
+		}
+		obstacles.sort_by(|a, b| a.beat.total_cmp(&b.beat));
+
+		let mut events: Vec<Event> = value
+			.basic_beatmap_events
+			.into_iter()
+			.map(Event::from)
+			.chain(value.color_boost_beatmap_events.into_iter().map(Event::from))
+			.chain(value.rotation_events.into_iter().map(Event::from))
+			.collect();
+		events.sort_by(|a, b| a.beat.total_cmp(&b.beat));
+
+		let mut extra = value.extra;
+		if let Some(custom_data) = value.custom_data {
+			extra.insert(String::from("_customData"), custom_data);
+		}
+		Self {
+			version: String::from("2.6.0"),
+			notes,
+			obstacles,
+			events,
+			bpm_events: value
+				.bpm_events
+				.into_iter()
+				.map(|x| BpmEvent {
+					song_time: x.song_time,
+					beats: x.beats,
+					extra: x.extra
+				})
+				.collect(),
+			extra
+		}
+	}
 }
 
 #[derive(Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -61,18 +158,82 @@ pub enum NoteType {
 	Bomb = 3
 }
 
-#[derive(Serialize_repr, Debug, Clone, Copy, PartialEq, Eq, Hash)]
-#[repr(u8)]
+impl From<v3::NoteColor> for NoteType {
+	fn from(value: v3::NoteColor) -> Self {
+		match value {
+			v3::NoteColor::Red => NoteType::Red,
+			v3::NoteColor::Blue => NoteType::Blue
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum NoteDirection {
-	Up = 0,
-	Down = 1,
-	Left = 2,
-	Right = 3,
-	UpLeft = 4,
-	UpRight = 5,
-	DownLeft = 6,
-	DownRight = 7,
-	Any = 8
+	Up,
+	Down,
+	Left,
+	Right,
+	UpLeft,
+	UpRight,
+	DownLeft,
+	DownRight,
+	Any,
+	/// A mapping extensions precise direction (`1000..=1360`), kept as-is so it's written back unchanged. See
+	/// [`Self::cardinal`] for the nearest regular direction, and [`Self::precise`] to construct one checked.
+	Precise(u16)
+}
+
+impl NoteDirection {
+	/// Returns a [`Self::Precise`] direction, or `None` if `value` is outside of `1000..=1360`.
+	pub fn precise(value: u16) -> Option<Self> {
+		(1000..=1360).contains(&value).then_some(Self::Precise(value))
+	}
+
+	/// Returns the nearest regular cut direction, which is the direction itself unless it's [`Self::Precise`].
+	pub fn cardinal(&self) -> Self {
+		match self {
+			Self::Precise(value) => super::util::split_precise_direction(*value as u32).0.into(),
+			d => *d
+		}
+	}
+
+	/// See [`standard::NoteDirection::to_degrees`]. Precise directions give their exact angle.
+	pub fn to_degrees(&self) -> Option<f32> {
+		match self {
+			// 1000 points down and the rotation increases clockwise from there. Saturating, since a `Precise` built
+			// without `Self::precise` can be out of range
+			Self::Precise(value) => Some((180. + value.saturating_sub(1000).min(360) as f32) % 360.),
+			d => standard::NoteDirection::from(*d).to_degrees()
+		}
+	}
+
+	/// See [`standard::NoteDirection::from_degrees`].
+	pub fn from_degrees(degrees: f32) -> Self {
+		standard::NoteDirection::from_degrees(degrees).into()
+	}
+
+	/// See [`standard::NoteDirection::to_unit_vector`]. Precise directions give their exact direction.
+	pub fn to_unit_vector(&self) -> Option<Vec2> {
+		let radians = self.to_degrees()?.to_radians();
+		Some(Vec2::new(radians.sin(), radians.cos()))
+	}
+}
+
+impl Serialize for NoteDirection {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_u32(match self {
+			Self::Up => 0,
+			Self::Down => 1,
+			Self::Left => 2,
+			Self::Right => 3,
+			Self::UpLeft => 4,
+			Self::UpRight => 5,
+			Self::DownLeft => 6,
+			Self::DownRight => 7,
+			Self::Any => 8,
+			Self::Precise(value) => *value as u32
+		})
+	}
 }
 
 impl<'de> Deserialize<'de> for NoteDirection {
@@ -91,30 +252,45 @@ impl<'de> Deserialize<'de> for NoteDirection {
 			6 => Ok(NoteDirection::DownLeft),
 			7 => Ok(NoteDirection::DownRight),
 			8 => Ok(NoteDirection::Any),
-
-			// close enough approximation for mapping extensions' 360 degree note rotation
-			1000..1023 => Ok(NoteDirection::Down),
-			1023..1068 => Ok(NoteDirection::DownLeft),
-			1068..1113 => Ok(NoteDirection::Left),
-			1113..1158 => Ok(NoteDirection::UpLeft),
-			1158..1203 => Ok(NoteDirection::Up),
-			1203..1248 => Ok(NoteDirection::UpRight),
-			1248..1293 => Ok(NoteDirection::Right),
-			1293..1338 => Ok(NoteDirection::DownRight),
-			1338..=1360 => Ok(NoteDirection::Down),
+			1000..=1360 => Ok(NoteDirection::Precise(value as u16)),
 
 			other => Err(serde::de::Error::custom(format!("invalid value: {other}")))
 		}
 	}
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl From<v3::NoteDirection> for NoteDirection {
+	fn from(value: v3::NoteDirection) -> Self {
+		match value {
+			v3::NoteDirection::Up => NoteDirection::Up,
+			v3::NoteDirection::Down => NoteDirection::Down,
+			v3::NoteDirection::Left => NoteDirection::Left,
+			v3::NoteDirection::Right => NoteDirection::Right,
+			v3::NoteDirection::UpLeft => NoteDirection::UpLeft,
+			v3::NoteDirection::UpRight => NoteDirection::UpRight,
+			v3::NoteDirection::DownLeft => NoteDirection::DownLeft,
+			v3::NoteDirection::DownRight => NoteDirection::DownRight,
+			v3::NoteDirection::Any => NoteDirection::Any,
+			v3::NoteDirection::Precise(value) => NoteDirection::Precise(value)
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Note {
 	#[serde(rename = "_time")]
 	pub beat: f32,
-	#[serde(rename = "_lineIndex", deserialize_with = "super::util::deserialize_precision")]
+	#[serde(
+		rename = "_lineIndex",
+		deserialize_with = "super::util::deserialize_precision",
+		serialize_with = "super::util::serialize_precision"
+	)]
 	pub x: f32,
-	#[serde(rename = "_lineLayer", deserialize_with = "super::util::deserialize_precision")]
+	#[serde(
+		rename = "_lineLayer",
+		deserialize_with = "super::util::deserialize_precision",
+		serialize_with = "super::util::serialize_precision"
+	)]
 	pub y: f32,
 	#[serde(rename = "_type")]
 	pub note_type: NoteType,
@@ -123,29 +299,209 @@ pub struct Note {
 	#[serde(rename = "_angleOffset")]
 	pub angle_offset: Option<f32>,
 	#[serde(rename = "_customData")]
-	pub custom_data: Option<simd_json::OwnedValue>
+	pub custom_data: Option<simd_json::OwnedValue>,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Whether `_customData` marks an object as a Noodle Extensions fake.
+fn is_fake(custom_data: &Option<OwnedValue>) -> bool {
+	custom_data.as_ref().and_then(|x| x.get_bool("_fake")).unwrap_or(false)
+}
+
+impl Note {
+	/// Whether this note (or bomb) is marked `_fake` in its custom data.
+	pub fn is_fake(&self) -> bool {
+		is_fake(&self.custom_data)
+	}
+}
+
+/// Sets `_fake` in an object's custom data, creating it if needed.
+fn mark_fake(custom_data: Option<OwnedValue>) -> OwnedValue {
+	match custom_data {
+		Some(OwnedValue::Object(mut object)) => {
+			object.insert(String::from("_fake"), OwnedValue::from(true));
+			OwnedValue::Object(object)
+		}
+		_ => simd_json::json!({ "_fake": true })
+	}
+}
+
+impl From<v3::ColorNote> for Note {
+	fn from(mut value: v3::ColorNote) -> Self {
+		Self {
+			beat: value.beat,
+			x: value.x,
+			y: value.y,
+			note_type: value.color.into(),
+			direction: value.direction.into(),
+			angle_offset: value.angle_offset,
+			custom_data: value.extra.remove("customData"),
+			extra: value.extra
+		}
+	}
+}
+
+impl From<v3::BombNote> for Note {
+	fn from(mut value: v3::BombNote) -> Self {
+		Self {
+			beat: value.beat,
+			x: value.x,
+			y: value.y,
+			note_type: NoteType::Bomb,
+			direction: NoteDirection::Down,
+			angle_offset: None,
+			custom_data: value.extra.remove("customData"),
+			extra: value.extra
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Obstacle {
 	#[serde(rename = "_time")]
 	pub beat: f32,
 	#[serde(rename = "_type")]
 	pub wall_type: u32,
-	#[serde(rename = "_lineIndex", deserialize_with = "super::util::deserialize_precision")]
+	#[serde(
+		rename = "_lineIndex",
+		deserialize_with = "super::util::deserialize_precision",
+		serialize_with = "super::util::serialize_precision"
+	)]
 	pub x: f32,
 	#[serde(rename = "_duration")]
 	pub duration: f32,
-	#[serde(rename = "_width", deserialize_with = "super::util::deserialize_precision")]
+	#[serde(
+		rename = "_width",
+		deserialize_with = "super::util::deserialize_precision",
+		serialize_with = "super::util::serialize_precision"
+	)]
 	pub width: f32,
 	#[serde(rename = "_customData")]
-	pub custom_data: Option<simd_json::OwnedValue>
+	pub custom_data: Option<simd_json::OwnedValue>,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+impl Obstacle {
+	/// Whether this obstacle is marked `_fake` in its custom data.
+	pub fn is_fake(&self) -> bool {
+		is_fake(&self.custom_data)
+	}
+}
+
+/// Encodes the obstacle's vertical position & height into the wall type; see [`super::util::encode_wall_type`].
+impl From<v3::Obstacle> for Obstacle {
+	fn from(mut value: v3::Obstacle) -> Self {
+		Self {
+			beat: value.beat,
+			wall_type: super::util::encode_wall_type(value.y, value.height),
+			x: value.x,
+			duration: value.duration,
+			width: value.width,
+			custom_data: value.extra.remove("customData"),
+			extra: value.extra
+		}
+	}
+}
+
+/// A lighting, boost, or lane rotation event, depending on its type.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Event {
+	#[serde(rename = "_time")]
+	pub beat: f32,
+	#[serde(rename = "_type")]
+	pub event_type: i32,
+	#[serde(rename = "_value")]
+	pub value: i32,
+	#[serde(rename = "_floatValue", skip_serializing_if = "Option::is_none")]
+	pub float_value: Option<f32>,
+	#[serde(rename = "_customData", skip_serializing_if = "Option::is_none")]
+	pub custom_data: Option<simd_json::OwnedValue>,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+impl Event {
+	pub const COLOR_BOOST: i32 = 5;
+	pub const EARLY_LANE_ROTATION: i32 = 14;
+	pub const LATE_LANE_ROTATION: i32 = 15;
+	pub const BPM_CHANGE: i32 = 100;
+
+	/// Returns whether this is a lighting or color boost event, as opposed to a lane rotation or BPM change.
+	pub fn is_lighting(&self) -> bool {
+		!matches!(self.event_type, Self::EARLY_LANE_ROTATION | Self::LATE_LANE_ROTATION | Self::BPM_CHANGE)
+	}
+
+	/// Returns the rotation in degrees if this is a lane rotation event. Values `0..=7` map to `-60..=60` degrees in
+	/// steps of 15 (skipping 0), and mapping extensions' `1000..=1720` map to `-360..=360` degrees.
+	pub fn lane_rotation(&self) -> Option<f32> {
+		if self.event_type != Self::EARLY_LANE_ROTATION && self.event_type != Self::LATE_LANE_ROTATION {
+			return None;
+		}
+		match self.value {
+			0..=3 => Some((self.value - 4) as f32 * 15.),
+			4..=7 => Some((self.value - 3) as f32 * 15.),
+			1000..=1720 => Some((self.value - 1360) as f32),
+			_ => None
+		}
+	}
+}
+
+impl From<v3::BasicEvent> for Event {
+	fn from(mut value: v3::BasicEvent) -> Self {
+		Self {
+			beat: value.beat,
+			event_type: value.event_type,
+			value: value.value,
+			float_value: Some(value.float_value),
+			custom_data: value.extra.remove("customData"),
+			extra: value.extra
+		}
+	}
+}
+
+impl From<v3::ColorBoostEvent> for Event {
+	fn from(mut value: v3::ColorBoostEvent) -> Self {
+		Self {
+			beat: value.beat,
+			event_type: Self::COLOR_BOOST,
+			value: value.on as i32,
+			float_value: None,
+			custom_data: value.extra.remove("customData"),
+			extra: value.extra
+		}
+	}
+}
+
+/// The inverse of [`Event::lane_rotation`]. Rotations which aren't one of the vanilla values are written in the
+/// mapping extensions range, rounded to whole degrees.
+impl From<v3::RotationEvent> for Event {
+	fn from(mut value: v3::RotationEvent) -> Self {
+		let steps = value.rotation / 15.;
+		let encoded = match steps as i32 {
+			_ if steps.fract() != 0. => None,
+			x @ -4..=-1 => Some(x + 4),
+			x @ 1..=4 => Some(x + 3),
+			_ => None
+		};
+		Self {
+			beat: value.beat,
+			event_type: if value.execution_time == 0 { Self::EARLY_LANE_ROTATION } else { Self::LATE_LANE_ROTATION },
+			value: encoded.unwrap_or_else(|| 1360 + (value.rotation.round() as i32).clamp(-360, 360)),
+			float_value: None,
+			custom_data: value.extra.remove("customData"),
+			extra: value.extra
+		}
+	}
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BpmEvent {
 	#[serde(rename = "b")]
 	pub song_time: f32,
 	#[serde(rename = "m")]
-	pub beats: f32
+	pub beats: f32,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
 }
@@ -1,28 +1,160 @@
 use std::{
+	collections::HashMap,
 	fs::File,
 	io::{BufReader, BufWriter, Read, Write},
-	path::Path
+	path::Path,
+	str::FromStr
 };
 
+use glam::Vec2;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
-#[derive(Serialize, Deserialize, Default, Debug, Clone)]
-#[serde(rename_all = "camelCase")]
+use super::{
+	standard::{self, BpmTracker},
+	v2
+};
+
+#[derive(Default, Debug, Clone, PartialEq)]
 pub struct Beatmap {
 	pub version: String,
 	pub color_notes: Vec<ColorNote>,
 	pub bomb_notes: Vec<BombNote>,
 	pub obstacles: Vec<Obstacle>,
 	pub burst_sliders: Vec<BurstSlider>,
+	pub sliders: Vec<Slider>,
 	pub bpm_events: Vec<BpmEvent>,
+	pub rotation_events: Vec<RotationEvent>,
+	pub basic_beatmap_events: Vec<BasicEvent>,
+	pub color_boost_beatmap_events: Vec<ColorBoostEvent>,
+	pub waypoints: Vec<Waypoint>,
+	/// Heck's fake objects, stored under `customData` (top-level keys are also read for older tools that put them
+	/// there).
 	pub fake_color_notes: Option<Vec<ColorNote>>,
-	pub fake_bomb_notes: Option<Vec<ColorNote>>,
-	pub fake_obstacles: Option<Vec<ColorNote>>,
-	pub fake_burst_sliders: Option<Vec<ColorNote>>
+	pub fake_bomb_notes: Option<Vec<BombNote>>,
+	pub fake_obstacles: Option<Vec<Obstacle>>,
+	pub fake_burst_sliders: Option<Vec<BurstSlider>>,
+	/// Mod data for the whole map, such as Noodle Extensions' point definitions & custom events.
+	pub custom_data: Option<simd_json::OwnedValue>,
+	/// Fields which aren't part of this schema (e.g. ones added by editors), preserved so they survive a round trip.
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Beatmap", rename_all = "camelCase")]
+struct BeatmapDef {
+	version: String,
+	color_notes: Vec<ColorNote>,
+	bomb_notes: Vec<BombNote>,
+	obstacles: Vec<Obstacle>,
+	burst_sliders: Vec<BurstSlider>,
+	#[serde(default)]
+	sliders: Vec<Slider>,
+	bpm_events: Vec<BpmEvent>,
+	#[serde(default)]
+	rotation_events: Vec<RotationEvent>,
+	#[serde(default)]
+	basic_beatmap_events: Vec<BasicEvent>,
+	#[serde(default)]
+	color_boost_beatmap_events: Vec<ColorBoostEvent>,
+	#[serde(default)]
+	waypoints: Vec<Waypoint>,
+	#[serde(default, skip_serializing)]
+	fake_color_notes: Option<Vec<ColorNote>>,
+	#[serde(default, skip_serializing)]
+	fake_bomb_notes: Option<Vec<BombNote>>,
+	#[serde(default, skip_serializing)]
+	fake_obstacles: Option<Vec<Obstacle>>,
+	#[serde(default, skip_serializing)]
+	fake_burst_sliders: Option<Vec<BurstSlider>>,
+	#[serde(rename = "customData", skip_serializing_if = "Option::is_none")]
+	custom_data: Option<simd_json::OwnedValue>,
+	#[serde(flatten)]
+	extra: HashMap<String, simd_json::OwnedValue>
+}
+
+const FAKE_COLOR_NOTES: &str = "fakeColorNotes";
+const FAKE_BOMB_NOTES: &str = "fakeBombNotes";
+const FAKE_OBSTACLES: &str = "fakeObstacles";
+const FAKE_BURST_SLIDERS: &str = "fakeBurstSliders";
+
+impl<'de> Deserialize<'de> for Beatmap {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		fn take<T: serde::de::DeserializeOwned, E: serde::de::Error>(
+			custom_data: &mut simd_json::owned::Object,
+			key: &str,
+			fakes: &mut Option<Vec<T>>
+		) -> Result<(), E> {
+			if let Some(value) = custom_data.remove(key) {
+				let objects: Vec<T> = simd_json::serde::from_owned_value(value).map_err(E::custom)?;
+				fakes.get_or_insert_with(Vec::new).extend(objects);
+			}
+			Ok(())
+		}
+
+		let mut map = BeatmapDef::deserialize(deserializer)?;
+		if let Some(simd_json::OwnedValue::Object(custom_data)) = &mut map.custom_data {
+			take(custom_data, FAKE_COLOR_NOTES, &mut map.fake_color_notes)?;
+			take(custom_data, FAKE_BOMB_NOTES, &mut map.fake_bomb_notes)?;
+			take(custom_data, FAKE_OBSTACLES, &mut map.fake_obstacles)?;
+			take(custom_data, FAKE_BURST_SLIDERS, &mut map.fake_burst_sliders)?;
+		}
+		Ok(map)
+	}
+}
+
+impl Serialize for Beatmap {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		fn put<T: Serialize, E: serde::ser::Error>(custom_data: &mut simd_json::owned::Object, key: &str, fakes: &Option<Vec<T>>) -> Result<(), E> {
+			if let Some(fakes) = fakes {
+				custom_data.insert(key.to_owned(), simd_json::serde::to_owned_value(fakes).map_err(E::custom)?);
+			}
+			Ok(())
+		}
+
+		if self.fake_color_notes.is_none() && self.fake_bomb_notes.is_none() && self.fake_obstacles.is_none() && self.fake_burst_sliders.is_none() {
+			return BeatmapDef::serialize(self, serializer);
+		}
+		let mut custom_data = match &self.custom_data {
+			Some(simd_json::OwnedValue::Object(custom_data)) => custom_data.clone(),
+			None => Default::default(),
+			Some(_) => return Err(serde::ser::Error::custom("customData must be an object to hold fake objects"))
+		};
+		put(&mut custom_data, FAKE_COLOR_NOTES, &self.fake_color_notes)?;
+		put(&mut custom_data, FAKE_BOMB_NOTES, &self.fake_bomb_notes)?;
+		put(&mut custom_data, FAKE_OBSTACLES, &self.fake_obstacles)?;
+		put(&mut custom_data, FAKE_BURST_SLIDERS, &self.fake_burst_sliders)?;
+		// clones the rest of the map; fakes are rare enough that this isn't worth a borrowed representation
+		let map = Beatmap {
+			custom_data: Some(simd_json::OwnedValue::Object(custom_data)),
+			..self.clone()
+		};
+		BeatmapDef::serialize(&map, serializer)
+	}
 }
 
 impl Beatmap {
+	/// Returns a description of the first kind of data in the beatmap which converting it to v2 would drop, if any.
+	pub(crate) fn v2_unrepresentable(&self) -> Option<&'static str> {
+		use simd_json::prelude::ValueAsContainer;
+
+		const EVENT_BOXES: [&str; 4] = ["lightColorEventBoxGroups", "lightRotationEventBoxGroups", "lightTranslationEventBoxGroups", "vfxEventBoxGroups"];
+		if !self.burst_sliders.is_empty() || self.fake_burst_sliders.as_ref().is_some_and(|x| !x.is_empty()) {
+			Some("chains")
+		} else if !self.sliders.is_empty() {
+			Some("arcs")
+		} else if !self.waypoints.is_empty() {
+			Some("waypoints")
+		} else if EVENT_BOXES
+			.iter()
+			.any(|key| self.extra.get(*key).and_then(|x| x.as_array()).is_some_and(|x| !x.is_empty()))
+		{
+			Some("lighting event boxes")
+		} else {
+			None
+		}
+	}
+
 	pub fn serialize_to_string(&self, readable: bool) -> simd_json::Result<String> {
 		if readable { simd_json::to_string_pretty(self) } else { simd_json::to_string(self) }
 	}
@@ -54,6 +186,142 @@ impl Beatmap {
 	pub fn from_file<P: AsRef<Path>>(path: P) -> simd_json::Result<Self> {
 		Self::from_reader(BufReader::new(File::open(path)?))
 	}
+
+	/// The number of color notes & bombs, including fakes.
+	pub fn total_note_count(&self) -> usize {
+		self.color_notes.len() + self.bomb_notes.len() + self.fake_color_notes.as_ref().map_or(0, Vec::len) + self.fake_bomb_notes.as_ref().map_or(0, Vec::len)
+	}
+
+	/// The number of obstacles, including fakes.
+	pub fn total_obstacle_count(&self) -> usize {
+		self.obstacles.len() + self.fake_obstacles.as_ref().map_or(0, Vec::len)
+	}
+
+	/// The number of chains, including fakes.
+	pub fn total_chain_count(&self) -> usize {
+		self.burst_sliders.len() + self.fake_burst_sliders.as_ref().map_or(0, Vec::len)
+	}
+}
+
+impl FromStr for Beatmap {
+	type Err = simd_json::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::from_string(s)
+	}
+}
+
+/// Moves an object's v2 `_customData` into its unknown fields as `customData`, where v3 keeps it.
+fn with_custom_data(mut extra: HashMap<String, simd_json::OwnedValue>, custom_data: Option<simd_json::OwnedValue>) -> HashMap<String, simd_json::OwnedValue> {
+	if let Some(custom_data) = custom_data {
+		extra.insert(String::from("customData"), custom_data);
+	}
+	extra
+}
+
+/// Converts a v2 beatmap, carrying over custom data as-is. Objects marked `_fake` become fake objects, which are
+/// written into `customData` alongside the carried over map custom data, and lane rotation, color boost & BPM change
+/// events are moved into their own arrays.
+impl From<v2::Beatmap> for Beatmap {
+	fn from(value: v2::Beatmap) -> Self {
+		let mut map = Self {
+			version: String::from("3.3.0"),
+			extra: value.extra,
+			..Default::default()
+		};
+		map.custom_data = map.extra.remove("_customData");
+
+		let (mut fake_color_notes, mut fake_bomb_notes, mut fake_obstacles) = (Vec::new(), Vec::new(), Vec::new());
+		for note in value.notes {
+			let fake = note.is_fake();
+			let extra = with_custom_data(note.extra, note.custom_data);
+			let color = match note.note_type {
+				v2::NoteType::Red => NoteColor::Red,
+				v2::NoteType::Blue => NoteColor::Blue,
+				v2::NoteType::Bomb => {
+					let bomb = BombNote {
+						beat: note.beat,
+						x: note.x,
+						y: note.y,
+						extra
+					};
+					if fake { &mut fake_bomb_notes } else { &mut map.bomb_notes }.push(bomb);
+					continue;
+				}
+			};
+			let note = ColorNote {
+				beat: note.beat,
+				x: note.x,
+				y: note.y,
+				angle_offset: note.angle_offset,
+				color,
+				direction: note.direction.into(),
+				extra
+			};
+			if fake { &mut fake_color_notes } else { &mut map.color_notes }.push(note);
+		}
+
+		for obstacle in value.obstacles {
+			let fake = obstacle.is_fake();
+			let (y, height) = super::util::decode_wall_type(obstacle.wall_type);
+			let obstacle = Obstacle {
+				beat: obstacle.beat,
+				x: obstacle.x,
+				y,
+				duration: obstacle.duration,
+				width: obstacle.width,
+				height,
+				extra: with_custom_data(obstacle.extra, obstacle.custom_data)
+			};
+			if fake { &mut fake_obstacles } else { &mut map.obstacles }.push(obstacle);
+		}
+
+		map.bpm_events = value
+			.bpm_events
+			.into_iter()
+			.map(|x| BpmEvent {
+				song_time: x.song_time,
+				beats: x.beats,
+				extra: x.extra
+			})
+			.collect();
+		for event in value.events {
+			let rotation = event.lane_rotation();
+			let extra = with_custom_data(event.extra, event.custom_data);
+			match event.event_type {
+				v2::Event::BPM_CHANGE => map.bpm_events.push(BpmEvent {
+					song_time: event.beat,
+					beats: event.float_value.unwrap_or(event.value as f32),
+					extra
+				}),
+				v2::Event::EARLY_LANE_ROTATION | v2::Event::LATE_LANE_ROTATION => map.rotation_events.push(RotationEvent {
+					beat: event.beat,
+					execution_time: (event.event_type == v2::Event::LATE_LANE_ROTATION) as u8,
+					rotation: rotation.unwrap_or(0.),
+					extra
+				}),
+				v2::Event::COLOR_BOOST => map.color_boost_beatmap_events.push(ColorBoostEvent {
+					beat: event.beat,
+					on: event.value == 1,
+					extra
+				}),
+				_ => map.basic_beatmap_events.push(BasicEvent {
+					beat: event.beat,
+					event_type: event.event_type,
+					value: event.value,
+					// the game treats a missing v2 float value as full brightness
+					float_value: event.float_value.unwrap_or(1.),
+					extra
+				})
+			}
+		}
+		map.bpm_events.sort_by(|a, b| a.song_time.total_cmp(&b.song_time));
+
+		map.fake_color_notes = Some(fake_color_notes).filter(|x| !x.is_empty());
+		map.fake_bomb_notes = Some(fake_bomb_notes).filter(|x| !x.is_empty());
+		map.fake_obstacles = Some(fake_obstacles).filter(|x| !x.is_empty());
+		map
+	}
 }
 
 #[derive(Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -63,18 +331,90 @@ pub enum NoteColor {
 	Blue = 1
 }
 
-#[derive(Serialize_repr, Debug, Clone, Copy, PartialEq, Eq, Hash)]
-#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum NoteDirection {
-	Up = 0,
-	Down = 1,
-	Left = 2,
-	Right = 3,
-	UpLeft = 4,
-	UpRight = 5,
-	DownLeft = 6,
-	DownRight = 7,
-	Any = 8
+	Up,
+	Down,
+	Left,
+	Right,
+	UpLeft,
+	UpRight,
+	DownLeft,
+	DownRight,
+	Any,
+	/// A mapping extensions precise direction (`1000..=1360`), kept as-is so it's written back unchanged. See
+	/// [`Self::cardinal`] for the nearest regular direction, and [`Self::precise`] to construct one checked.
+	Precise(u16)
+}
+
+impl NoteDirection {
+	/// Returns a [`Self::Precise`] direction, or `None` if `value` is outside of `1000..=1360`.
+	pub fn precise(value: u16) -> Option<Self> {
+		(1000..=1360).contains(&value).then_some(Self::Precise(value))
+	}
+
+	/// Returns the nearest regular cut direction, which is the direction itself unless it's [`Self::Precise`].
+	pub fn cardinal(&self) -> Self {
+		match self {
+			Self::Precise(value) => super::util::split_precise_direction(*value as u32).0.into(),
+			d => *d
+		}
+	}
+
+	/// See [`standard::NoteDirection::to_degrees`]. Precise directions give their exact angle.
+	pub fn to_degrees(&self) -> Option<f32> {
+		match self {
+			// 1000 points down and the rotation increases clockwise from there. Saturating, since a `Precise` built
+			// without `Self::precise` can be out of range
+			Self::Precise(value) => Some((180. + value.saturating_sub(1000).min(360) as f32) % 360.),
+			d => standard::NoteDirection::from(*d).to_degrees()
+		}
+	}
+
+	/// See [`standard::NoteDirection::from_degrees`].
+	pub fn from_degrees(degrees: f32) -> Self {
+		standard::NoteDirection::from_degrees(degrees).into()
+	}
+
+	/// See [`standard::NoteDirection::to_unit_vector`]. Precise directions give their exact direction.
+	pub fn to_unit_vector(&self) -> Option<Vec2> {
+		let radians = self.to_degrees()?.to_radians();
+		Some(Vec2::new(radians.sin(), radians.cos()))
+	}
+}
+
+impl From<v2::NoteDirection> for NoteDirection {
+	fn from(value: v2::NoteDirection) -> Self {
+		match value {
+			v2::NoteDirection::Up => NoteDirection::Up,
+			v2::NoteDirection::Down => NoteDirection::Down,
+			v2::NoteDirection::Left => NoteDirection::Left,
+			v2::NoteDirection::Right => NoteDirection::Right,
+			v2::NoteDirection::UpLeft => NoteDirection::UpLeft,
+			v2::NoteDirection::UpRight => NoteDirection::UpRight,
+			v2::NoteDirection::DownLeft => NoteDirection::DownLeft,
+			v2::NoteDirection::DownRight => NoteDirection::DownRight,
+			v2::NoteDirection::Any => NoteDirection::Any,
+			v2::NoteDirection::Precise(value) => NoteDirection::Precise(value)
+		}
+	}
+}
+
+impl Serialize for NoteDirection {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_u32(match self {
+			Self::Up => 0,
+			Self::Down => 1,
+			Self::Left => 2,
+			Self::Right => 3,
+			Self::UpLeft => 4,
+			Self::UpRight => 5,
+			Self::DownLeft => 6,
+			Self::DownRight => 7,
+			Self::Any => 8,
+			Self::Precise(value) => *value as u32
+		})
+	}
 }
 
 impl<'de> Deserialize<'de> for NoteDirection {
@@ -93,86 +433,106 @@ impl<'de> Deserialize<'de> for NoteDirection {
 			6 => Ok(NoteDirection::DownLeft),
 			7 => Ok(NoteDirection::DownRight),
 			8 => Ok(NoteDirection::Any),
-
-			// close enough approximation for mapping extensions' 360 degree note rotation
-			1000..1023 => Ok(NoteDirection::Down),
-			1023..1068 => Ok(NoteDirection::DownLeft),
-			1068..1113 => Ok(NoteDirection::Left),
-			1113..1158 => Ok(NoteDirection::UpLeft),
-			1158..1203 => Ok(NoteDirection::Up),
-			1203..1248 => Ok(NoteDirection::UpRight),
-			1248..1293 => Ok(NoteDirection::Right),
-			1293..1338 => Ok(NoteDirection::DownRight),
-			1338..=1360 => Ok(NoteDirection::Down),
+			1000..=1360 => Ok(NoteDirection::Precise(value as u16)),
 
 			other => Err(serde::de::Error::custom(format!("invalid value: {other}")))
 		}
 	}
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ColorNote {
 	#[serde(rename = "b")]
 	pub beat: f32,
-	#[serde(deserialize_with = "super::util::deserialize_precision")]
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
 	pub x: f32,
-	#[serde(deserialize_with = "super::util::deserialize_precision")]
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
 	pub y: f32,
 	#[serde(rename = "a")]
 	pub angle_offset: Option<f32>,
 	#[serde(rename = "c")]
 	pub color: NoteColor,
 	#[serde(rename = "d")]
-	pub direction: NoteDirection
+	pub direction: NoteDirection,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
 }
 
 impl ColorNote {
 	/// Returns the event time of this note based on the current BPM.
+	#[deprecated(
+		note = "this assumes the map's BPM never changes, so it gives the wrong time for notes after a BPM event; use `song_time` with a `BpmTracker` for the map instead"
+	)]
 	pub fn time(&self, bpm: f32) -> f32 {
 		self.beat * (60. / bpm)
 	}
+
+	/// Returns the song time of this note in seconds, taking the map's BPM changes into account.
+	pub fn song_time(&self, bpm_tracker: &BpmTracker) -> f32 {
+		bpm_tracker.beat_to_song_time(self.beat)
+	}
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BombNote {
 	#[serde(rename = "b")]
 	pub beat: f32,
-	#[serde(deserialize_with = "super::util::deserialize_precision")]
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
 	pub x: f32,
-	#[serde(deserialize_with = "super::util::deserialize_precision")]
-	pub y: f32
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
+	pub y: f32,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
 }
 
 impl BombNote {
 	/// Returns the event time of this note based on the current BPM.
+	#[deprecated(
+		note = "this assumes the map's BPM never changes, so it gives the wrong time for notes after a BPM event; use `song_time` with a `BpmTracker` for the map instead"
+	)]
 	pub fn time(&self, bpm: f32) -> f32 {
 		self.beat * (60. / bpm)
 	}
+
+	/// Returns the song time of this note in seconds, taking the map's BPM changes into account.
+	pub fn song_time(&self, bpm_tracker: &BpmTracker) -> f32 {
+		bpm_tracker.beat_to_song_time(self.beat)
+	}
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Obstacle {
 	#[serde(rename = "b")]
 	pub beat: f32,
-	#[serde(deserialize_with = "super::util::deserialize_precision")]
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
 	pub x: f32,
-	#[serde(deserialize_with = "super::util::deserialize_precision")]
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
 	pub y: f32,
 	#[serde(rename = "d")]
 	pub duration: f32,
-	#[serde(rename = "w", deserialize_with = "super::util::deserialize_precision")]
+	#[serde(
+		rename = "w",
+		deserialize_with = "super::util::deserialize_precision",
+		serialize_with = "super::util::serialize_precision"
+	)]
 	pub width: f32,
-	#[serde(rename = "h", deserialize_with = "super::util::deserialize_precision")]
-	pub height: f32
+	#[serde(
+		rename = "h",
+		deserialize_with = "super::util::deserialize_precision",
+		serialize_with = "super::util::serialize_precision"
+	)]
+	pub height: f32,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BurstSlider {
 	#[serde(rename = "b")]
 	pub beat: f32,
-	#[serde(deserialize_with = "super::util::deserialize_precision")]
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
 	pub x: f32,
-	#[serde(deserialize_with = "super::util::deserialize_precision")]
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
 	pub y: f32,
 	#[serde(rename = "c")]
 	pub color: NoteColor,
@@ -180,20 +540,150 @@ pub struct BurstSlider {
 	pub direction: NoteDirection,
 	#[serde(rename = "tb")]
 	pub tail_beat: f32,
-	#[serde(rename = "tx", deserialize_with = "super::util::deserialize_precision")]
+	#[serde(
+		rename = "tx",
+		deserialize_with = "super::util::deserialize_precision",
+		serialize_with = "super::util::serialize_precision"
+	)]
 	pub tail_x: f32,
-	#[serde(rename = "ty", deserialize_with = "super::util::deserialize_precision")]
+	#[serde(
+		rename = "ty",
+		deserialize_with = "super::util::deserialize_precision",
+		serialize_with = "super::util::serialize_precision"
+	)]
 	pub tail_y: f32,
 	#[serde(rename = "sc")]
 	pub num_slices: u8,
 	#[serde(rename = "s")]
-	pub squish_amount: f32
+	pub squish_amount: f32,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+/// A basic lighting event.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BasicEvent {
+	#[serde(rename = "b")]
+	pub beat: f32,
+	#[serde(rename = "et")]
+	pub event_type: i32,
+	#[serde(rename = "i")]
+	pub value: i32,
+	/// The brightness of the event, which defaults to `1` like a v2 event's missing `_floatValue`.
+	#[serde(rename = "f", default = "one")]
+	pub float_value: f32,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+fn one() -> f32 {
+	1.
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ColorBoostEvent {
+	#[serde(rename = "b")]
+	pub beat: f32,
+	#[serde(rename = "o")]
+	pub on: bool,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+/// A waypoint used by scripted level sequences.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Waypoint {
+	#[serde(rename = "b")]
+	pub beat: f32,
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
+	pub x: f32,
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
+	pub y: f32,
+	#[serde(rename = "d")]
+	pub offset_direction: NoteDirection,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RotationEvent {
+	#[serde(rename = "b")]
+	pub beat: f32,
+	/// `0` if the rotation applies to objects on the same beat, `1` if it only applies after them.
+	#[serde(rename = "e")]
+	pub execution_time: u8,
+	/// The rotation in degrees.
+	#[serde(rename = "r")]
+	pub rotation: f32,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+/// An arc.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Slider {
+	#[serde(rename = "b")]
+	pub beat: f32,
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
+	pub x: f32,
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
+	pub y: f32,
+	#[serde(rename = "c")]
+	pub color: NoteColor,
+	#[serde(rename = "d")]
+	pub direction: NoteDirection,
+	#[serde(rename = "mu")]
+	pub head_multiplier: f32,
+	#[serde(rename = "tb")]
+	pub tail_beat: f32,
+	#[serde(
+		rename = "tx",
+		deserialize_with = "super::util::deserialize_precision",
+		serialize_with = "super::util::serialize_precision"
+	)]
+	pub tail_x: f32,
+	#[serde(
+		rename = "ty",
+		deserialize_with = "super::util::deserialize_precision",
+		serialize_with = "super::util::serialize_precision"
+	)]
+	pub tail_y: f32,
+	#[serde(rename = "tc")]
+	pub tail_direction: NoteDirection,
+	#[serde(rename = "tmu")]
+	pub tail_multiplier: f32,
+	#[serde(rename = "m")]
+	pub mid_anchor_mode: MidAnchorMode,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+/// Which way an arc curves around its midpoint when its head & tail point in the same direction.
+#[derive(Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum MidAnchorMode {
+	Straight = 0,
+	Clockwise = 1,
+	CounterClockwise = 2
+}
+
+impl MidAnchorMode {
+	/// Returns this mode flipped left-to-right.
+	pub fn mirrored(&self) -> Self {
+		match self {
+			Self::Straight => Self::Straight,
+			Self::Clockwise => Self::CounterClockwise,
+			Self::CounterClockwise => Self::Clockwise
+		}
+	}
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BpmEvent {
 	#[serde(rename = "b")]
 	pub song_time: f32,
 	#[serde(rename = "m")]
-	pub beats: f32
+	pub beats: f32,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
 }
@@ -0,0 +1,201 @@
+use std::{
+	collections::HashMap,
+	fs::File,
+	io::{BufReader, BufWriter, Read, Write},
+	path::Path,
+	str::FromStr
+};
+
+use serde::{Deserialize, Serialize};
+
+pub use super::v3::{NoteColor, NoteDirection};
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Beatmap {
+	pub version: String,
+	pub color_notes: Vec<ColorNote>,
+	pub color_notes_data: Vec<ColorNoteData>,
+	pub bomb_notes: Vec<BombNote>,
+	pub bomb_notes_data: Vec<BombNoteData>,
+	pub obstacles: Vec<Obstacle>,
+	pub obstacles_data: Vec<ObstacleData>,
+	pub chains: Vec<Chain>,
+	pub chains_data: Vec<ChainData>,
+	/// Fields which aren't part of this schema (e.g. ones added by editors), preserved so they survive a round trip.
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+impl Beatmap {
+	pub fn serialize_to_string(&self, readable: bool) -> simd_json::Result<String> {
+		if readable { simd_json::to_string_pretty(self) } else { simd_json::to_string(self) }
+	}
+
+	pub fn serialize_to_writer<W: Write>(&self, writer: W, readable: bool) -> simd_json::Result<()> {
+		if readable {
+			simd_json::to_writer_pretty(writer, self)
+		} else {
+			simd_json::to_writer(writer, self)
+		}
+	}
+
+	pub fn serialize_to_file<P: AsRef<Path>>(&self, path: P, readable: bool) -> simd_json::Result<()> {
+		self.serialize_to_writer(&mut BufWriter::new(File::create(path)?), readable)
+	}
+
+	pub fn serialize_to_bytes(&self, readable: bool) -> simd_json::Result<Vec<u8>> {
+		if readable { simd_json::to_vec_pretty(self) } else { simd_json::to_vec(self) }
+	}
+
+	pub fn from_string(s: impl Into<String>) -> simd_json::Result<Self> {
+		unsafe { simd_json::from_str(&mut s.into()) }
+	}
+
+	pub fn from_reader<R: Read>(reader: R) -> simd_json::Result<Self> {
+		simd_json::from_reader(reader)
+	}
+
+	pub fn from_file<P: AsRef<Path>>(path: P) -> simd_json::Result<Self> {
+		Self::from_reader(BufReader::new(File::open(path)?))
+	}
+}
+
+impl FromStr for Beatmap {
+	type Err = simd_json::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::from_string(s)
+	}
+}
+
+/// A color note placed in time. The note's position, color & direction are stored in `color_notes_data` at `index`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ColorNote {
+	#[serde(rename = "b")]
+	pub beat: f32,
+	#[serde(rename = "r", default)]
+	pub rotation_lane: i32,
+	#[serde(rename = "i")]
+	pub index: usize,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ColorNoteData {
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
+	pub x: f32,
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
+	pub y: f32,
+	#[serde(rename = "a")]
+	pub angle_offset: Option<f32>,
+	#[serde(rename = "c")]
+	pub color: NoteColor,
+	#[serde(rename = "d")]
+	pub direction: NoteDirection,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+/// A bomb placed in time. The bomb's position is stored in `bomb_notes_data` at `index`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BombNote {
+	#[serde(rename = "b")]
+	pub beat: f32,
+	#[serde(rename = "r", default)]
+	pub rotation_lane: i32,
+	#[serde(rename = "i")]
+	pub index: usize,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BombNoteData {
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
+	pub x: f32,
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
+	pub y: f32,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+/// An obstacle placed in time. The obstacle's dimensions are stored in `obstacles_data` at `index`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Obstacle {
+	#[serde(rename = "b")]
+	pub beat: f32,
+	#[serde(rename = "r", default)]
+	pub rotation_lane: i32,
+	#[serde(rename = "i")]
+	pub index: usize,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ObstacleData {
+	#[serde(rename = "d")]
+	pub duration: f32,
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
+	pub x: f32,
+	#[serde(deserialize_with = "super::util::deserialize_precision", serialize_with = "super::util::serialize_precision")]
+	pub y: f32,
+	#[serde(
+		rename = "w",
+		deserialize_with = "super::util::deserialize_precision",
+		serialize_with = "super::util::serialize_precision"
+	)]
+	pub width: f32,
+	#[serde(
+		rename = "h",
+		deserialize_with = "super::util::deserialize_precision",
+		serialize_with = "super::util::serialize_precision"
+	)]
+	pub height: f32,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+/// A chain placed in time. The head note is stored in `color_notes_data` at `index`, and the chain's tail & slice
+/// parameters are stored in `chains_data` at `chain_index`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Chain {
+	#[serde(rename = "hb")]
+	pub beat: f32,
+	#[serde(rename = "tb")]
+	pub tail_beat: f32,
+	#[serde(rename = "hr", default)]
+	pub rotation_lane: i32,
+	#[serde(rename = "tr", default)]
+	pub tail_rotation_lane: i32,
+	#[serde(rename = "i")]
+	pub index: usize,
+	#[serde(rename = "ci")]
+	pub chain_index: usize,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChainData {
+	#[serde(
+		rename = "tx",
+		deserialize_with = "super::util::deserialize_precision",
+		serialize_with = "super::util::serialize_precision"
+	)]
+	pub tail_x: f32,
+	#[serde(
+		rename = "ty",
+		deserialize_with = "super::util::deserialize_precision",
+		serialize_with = "super::util::serialize_precision"
+	)]
+	pub tail_y: f32,
+	#[serde(rename = "c")]
+	pub num_slices: u8,
+	#[serde(rename = "s")]
+	pub squish_amount: f32,
+	#[serde(flatten)]
+	pub extra: HashMap<String, simd_json::OwnedValue>
+}
@@ -7,12 +7,44 @@ use std::{
 use glam::{Quat, Vec3};
 use thiserror::Error;
 
+use super::hash::{InvalidSongHash, SongHash};
+
+/// The magic number at the start of every BSOR file, read as a little-endian `u32`.
+pub const BSOR_MAGIC: u32 = 0x442d3d69;
+/// The BSOR format version this module reads & writes.
+pub const BSOR_VERSION: u8 = 1;
+
+/// The magic number of the custom delta-compressed replay format written by [`Replay::serialize_delta_to_writer`].
+#[cfg(feature = "delta")]
+pub const DELTA_MAGIC: u32 = 0x442d3d64;
+#[cfg(feature = "delta")]
+const DELTA_VERSION: u8 = 2;
+/// The quantization step of positions (in meters) & rotation components in the delta-compressed format.
+#[cfg(feature = "delta")]
+pub const DELTA_POSE_STEP: f32 = 1e-4;
+/// The quantization step of frame times in the delta-compressed format, in seconds.
+#[cfg(feature = "delta")]
+pub const DELTA_TIME_STEP: f32 = 1e-5;
+
+/// Modifiers which disqualify a score from ranking.
+pub const DISQUALIFYING_MODIFIERS: &[&str] = &["NF", "NB", "NO", "NA", "GN", "DA"];
+
 #[derive(Debug, Error)]
 pub enum ParseError {
 	#[error("I/O error: {0}")]
 	IoError(#[from] io::Error),
 	#[error("Failed to parse string as UTF-8: {0}")]
-	UTF8Error(#[from] std::string::FromUtf8Error)
+	UTF8Error(#[from] std::string::FromUtf8Error),
+	#[error("Invalid BSOR magic number: {0:#x}")]
+	InvalidMagic(i32),
+	#[error("Unsupported BSOR version: {0}")]
+	UnsupportedVersion(u8),
+	#[error("Expected section {expected}, got section {got}")]
+	UnexpectedSection { expected: u8, got: u8 },
+	#[error("Invalid note event type: {0}")]
+	InvalidNoteEventType(i32),
+	#[error("Invalid length prefix: {0}")]
+	InvalidLength(i32)
 }
 
 fn read_byte<R: Read>(r: &mut R) -> Result<u8, ParseError> {
@@ -39,21 +71,112 @@ fn read_f32<R: Read>(r: &mut R) -> Result<f32, ParseError> {
 	Ok(f32::from_le_bytes(x))
 }
 
-fn read_str<R: Read>(r: &mut R) -> Result<String, ParseError> {
+fn read_vec3<R: Read>(r: &mut R) -> Result<Vec3, ParseError> {
+	Ok(Vec3::new(read_f32(r)?, read_f32(r)?, read_f32(r)?))
+}
+
+fn write_vec3<W: Write>(w: &mut W, v: Vec3) -> Result<(), io::Error> {
+	for x in v.to_array() {
+		w.write_all(&x.to_le_bytes())?;
+	}
+	Ok(())
+}
+
+/// Reads a string length or an item count, which can't be negative.
+fn read_len<R: Read>(r: &mut R) -> Result<usize, ParseError> {
 	let len = read_i32(r)?;
-	let mut out = vec![0u8; len as usize];
-	r.read_exact(&mut out)?;
-	let s = String::from_utf8(out)?;
+	usize::try_from(len).map_err(|_| ParseError::InvalidLength(len))
+}
+
+/// Reads a length-prefixed string. In `lossy` mode, invalid UTF-8 is replaced with `U+FFFD` instead of failing.
+fn read_str<R: Read>(r: &mut R, lossy: bool) -> Result<String, ParseError> {
+	let len = read_len(r)?;
+	// the buffer only grows as bytes are read, so a bogus length can't allocate more than the input holds
+	let mut out = Vec::new();
+	r.take(len as u64).read_to_end(&mut out)?;
+	if out.len() < len {
+		return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+	}
+	let s = if lossy { String::from_utf8_lossy(&out).into_owned() } else { String::from_utf8(out)? };
 	Ok(s)
 }
 
+fn read_notes<R: Read>(r: &mut R) -> Result<Vec<NoteEvent>, ParseError> {
+	read_section(r, 2)?;
+	let n_notes = read_len(r)?;
+	(0..n_notes).map(|_| NoteEvent::from_reader(r)).collect()
+}
+
+fn write_notes<W: Write>(w: &mut W, notes: &[NoteEvent]) -> Result<(), io::Error> {
+	w.write_all(&[2])?;
+	w.write_all(&(notes.len() as i32).to_le_bytes())?;
+	for note in notes {
+		note.serialize_to_writer(w)?;
+	}
+	Ok(())
+}
+
+fn read_header<R: Read>(r: &mut R) -> Result<(), ParseError> {
+	let magic = read_i32(r)?;
+	if magic as u32 != BSOR_MAGIC {
+		return Err(ParseError::InvalidMagic(magic));
+	}
+	let version = read_byte(r)?;
+	if version != BSOR_VERSION {
+		return Err(ParseError::UnsupportedVersion(version));
+	}
+	Ok(())
+}
+
+fn read_section<R: Read>(r: &mut R, expected: u8) -> Result<(), ParseError> {
+	let got = read_byte(r)?;
+	if got != expected {
+		return Err(ParseError::UnexpectedSection { expected, got });
+	}
+	Ok(())
+}
+
 fn write_str<W: Write>(w: &mut W, s: &str) -> Result<(), io::Error> {
 	w.write_all(&(s.len() as i32).to_le_bytes())?;
 	w.write_all(s.as_bytes())?;
 	Ok(())
 }
 
+/// Writes a zigzag-encoded LEB128 varint, so that small negative values are as short as small positive ones.
+#[cfg(feature = "delta")]
+fn write_varint<W: Write>(w: &mut W, value: i64) -> Result<(), io::Error> {
+	let mut value = ((value << 1) ^ (value >> 63)) as u64;
+	while value >= 0x80 {
+		w.write_all(&[value as u8 | 0x80])?;
+		value >>= 7;
+	}
+	w.write_all(&[value as u8])
+}
+
+#[cfg(feature = "delta")]
+fn read_varint<R: Read>(r: &mut R) -> Result<i64, ParseError> {
+	let mut value = 0u64;
+	for shift in (0..64).step_by(7) {
+		let byte = read_byte(r)?;
+		value |= ((byte & 0x7f) as u64) << shift;
+		if byte & 0x80 == 0 {
+			return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+		}
+	}
+	Err(io::Error::new(io::ErrorKind::InvalidData, "varint is too long").into())
+}
+
+/// Quotes a CSV field if it contains a delimiter, quote or line break, doubling any quotes inside it.
+fn csv_escape(s: &str) -> String {
+	if s.contains([',', '"', '\n', '\r']) {
+		format!("\"{}\"", s.replace('"', "\"\""))
+	} else {
+		s.to_owned()
+	}
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ReplayInfo {
 	pub version: String,
 	pub game_version: String,
@@ -67,6 +190,8 @@ pub struct ReplayInfo {
 	pub hmd: String,
 	pub controller: String,
 
+	/// The song hash as written by the game. OST & WIP levels store their level ID here instead, so it's kept as-is;
+	/// see [`Self::song_hash`] for the validated hash.
 	pub song_hash: String,
 	pub song_name: String,
 	pub mapper: String,
@@ -80,36 +205,49 @@ pub struct ReplayInfo {
 	pub left_handed: bool,
 	pub height: f32,
 
+	/// The song time the replay started at, in seconds. Non-zero for practice sessions started part way through.
 	pub start_time: f32,
+	/// The time the player failed at, in seconds, or `0` if they didn't fail.
 	pub fail_time: f32,
+	/// The practice mode song speed, or `0` if the replay was played at normal speed outside of practice mode.
 	pub speed: f32
 }
 
 impl ReplayInfo {
 	pub fn from_reader<R: Read>(r: &mut R) -> Result<Self, ParseError> {
-		assert_eq!(read_byte(r)?, 0);
+		Self::read(r, false)
+	}
+
+	/// Like [`Self::from_reader`], but replaces invalid UTF-8 in strings (e.g. Latin-1 player names) instead of
+	/// failing.
+	pub fn from_reader_lossy<R: Read>(r: &mut R) -> Result<Self, ParseError> {
+		Self::read(r, true)
+	}
+
+	fn read<R: Read>(r: &mut R, lossy: bool) -> Result<Self, ParseError> {
+		read_section(r, 0)?;
 		Ok(Self {
-			version: read_str(r)?,
-			game_version: read_str(r)?,
-			timestamp: read_str(r)?,
+			version: read_str(r, lossy)?,
+			game_version: read_str(r, lossy)?,
+			timestamp: read_str(r, lossy)?,
 
-			player_id: read_str(r)?,
-			player_name: read_str(r)?,
-			platform: read_str(r)?,
+			player_id: read_str(r, lossy)?,
+			player_name: read_str(r, lossy)?,
+			platform: read_str(r, lossy)?,
 
-			tracking_system: read_str(r)?,
-			hmd: read_str(r)?,
-			controller: read_str(r)?,
+			tracking_system: read_str(r, lossy)?,
+			hmd: read_str(r, lossy)?,
+			controller: read_str(r, lossy)?,
 
-			song_hash: read_str(r)?,
-			song_name: read_str(r)?,
-			mapper: read_str(r)?,
-			difficulty: read_str(r)?,
+			song_hash: read_str(r, lossy)?,
+			song_name: read_str(r, lossy)?,
+			mapper: read_str(r, lossy)?,
+			difficulty: read_str(r, lossy)?,
 
 			score: read_i32(r)?,
-			mode: read_str(r)?,
-			environment: read_str(r)?,
-			modifiers: match read_str(r)?.as_str() {
+			mode: read_str(r, lossy)?,
+			environment: read_str(r, lossy)?,
+			modifiers: match read_str(r, lossy)?.as_str() {
 				"" => Vec::new(),
 				v => v.split(',').map(String::from).collect()
 			},
@@ -123,8 +261,72 @@ impl ReplayInfo {
 		})
 	}
 
+	/// Returns the song hash if it's a valid custom map hash, which it isn't for OST & WIP levels.
+	pub fn song_hash(&self) -> Result<SongHash, InvalidSongHash> {
+		SongHash::new(&self.song_hash)
+	}
+
+	/// Returns the combined score multiplier of the replay's modifiers. Like the game, multipliers are additive, e.g.
+	/// Faster Song (+8%) with No Bombs (-10%) gives `0.98`.
+	pub fn modifier_multiplier(&self) -> f32 {
+		let bonus: f32 = self
+			.modifiers
+			.iter()
+			.map(|m| match m.as_str() {
+				"NF" => -0.5,
+				"NB" => -0.1,
+				"NO" => -0.05,
+				"SS" => -0.3,
+				"NA" => -0.3,
+				"GN" => 0.11,
+				"DA" => 0.07,
+				"FS" => 0.08,
+				"SF" => 0.1,
+				_ => 0.
+			})
+			.sum();
+		(1. + bonus).max(0.)
+	}
+
+	/// The header row matching [`Self::to_csv_row`].
+	pub fn csv_header() -> &'static str {
+		"version,game_version,timestamp,player_id,player_name,platform,tracking_system,hmd,controller,song_hash,song_name,mapper,\
+		 difficulty,score,mode,environment,modifiers,jump_distance,left_handed,height,start_time,fail_time,speed"
+	}
+
+	/// Formats the replay's metadata as a CSV row, without a trailing line break. Modifiers are joined with commas
+	/// into a single field.
+	pub fn to_csv_row(&self) -> String {
+		[
+			csv_escape(&self.version),
+			csv_escape(&self.game_version),
+			csv_escape(&self.timestamp),
+			csv_escape(&self.player_id),
+			csv_escape(&self.player_name),
+			csv_escape(&self.platform),
+			csv_escape(&self.tracking_system),
+			csv_escape(&self.hmd),
+			csv_escape(&self.controller),
+			csv_escape(&self.song_hash),
+			csv_escape(&self.song_name),
+			csv_escape(&self.mapper),
+			csv_escape(&self.difficulty),
+			self.score.to_string(),
+			csv_escape(&self.mode),
+			csv_escape(&self.environment),
+			csv_escape(&self.modifiers.join(",")),
+			self.jump_distance.to_string(),
+			self.left_handed.to_string(),
+			self.height.to_string(),
+			self.start_time.to_string(),
+			self.fail_time.to_string(),
+			self.speed.to_string()
+		]
+		.join(",")
+	}
+
 	pub fn is_same_map(&self, other: &Self) -> bool {
-		self.song_hash == other.song_hash && self.mode == other.mode && self.difficulty == other.difficulty
+		self.song_hash.trim().eq_ignore_ascii_case(other.song_hash.trim()) && self.mode == other.mode && self.difficulty == other.difficulty
 	}
 
 	pub fn serialize_to_writer<W: Write>(&self, w: &mut W) -> Result<(), io::Error> {
@@ -167,10 +369,25 @@ impl ReplayInfo {
 		self.serialize_to_writer(&mut out).unwrap();
 		out
 	}
+
+	/// Parses [`Self::timestamp`], a Unix timestamp in seconds. Returns `None` if it's malformed or out of range.
+	#[cfg(feature = "chrono")]
+	pub fn parse_timestamp(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+		chrono::DateTime::from_timestamp(self.timestamp.trim().parse().ok()?, 0)
+	}
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hand {
+	Left,
+	Right
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ReplayFrame {
+	/// The real (wall-clock) time of this frame, in seconds. See [`Replay::song_time`] to convert it to the time of
+	/// beatmap objects.
 	pub time: f32,
 	pub fps: i32,
 	pub head: (Vec3, Quat),
@@ -204,6 +421,58 @@ impl ReplayFrame {
 		Ok(())
 	}
 
+	pub fn hand(&self, hand: Hand) -> &(Vec3, Quat) {
+		match hand {
+			Hand::Left => &self.left_hand,
+			Hand::Right => &self.right_hand
+		}
+	}
+
+	/// Returns whether two frames are equal within `epsilon`. Positions & times are compared per component, and
+	/// rotations are compared as quaternions (treating `q` and `-q` as the same rotation).
+	pub fn approx_eq(&self, other: &Self, epsilon: f32) -> bool {
+		fn pose_eq((a_pos, a_rot): &(Vec3, Quat), (b_pos, b_rot): &(Vec3, Quat), epsilon: f32) -> bool {
+			a_pos.abs_diff_eq(*b_pos, epsilon) && (a_rot.abs_diff_eq(*b_rot, epsilon) || a_rot.abs_diff_eq(-*b_rot, epsilon))
+		}
+
+		(self.time - other.time).abs() <= epsilon
+			&& self.fps == other.fps
+			&& pose_eq(&self.head, &other.head, epsilon)
+			&& pose_eq(&self.left_hand, &other.left_hand, epsilon)
+			&& pose_eq(&self.right_hand, &other.right_hand, epsilon)
+	}
+
+	/// Quantizes the frame for the delta-compressed format: the fps, the time, then the position & rotation of the
+	/// head, left & right hand.
+	#[cfg(feature = "delta")]
+	fn quantize(&self) -> [i64; 23] {
+		let mut out = [0; 23];
+		out[0] = self.fps as i64;
+		out[1] = (self.time as f64 / DELTA_TIME_STEP as f64).round() as i64;
+		for (i, (pos, rot)) in [self.head, self.left_hand, self.right_hand].into_iter().enumerate() {
+			for (j, x) in pos.to_array().into_iter().chain(rot.to_array()).enumerate() {
+				out[2 + i * 7 + j] = (x as f64 / DELTA_POSE_STEP as f64).round() as i64;
+			}
+		}
+		out
+	}
+
+	/// The inverse of [`Self::quantize`].
+	#[cfg(feature = "delta")]
+	fn dequantize(q: &[i64; 23]) -> Self {
+		let pose = |i: usize| {
+			let x = |j: usize| (q[2 + i * 7 + j] as f64 * DELTA_POSE_STEP as f64) as f32;
+			(Vec3::new(x(0), x(1), x(2)), Quat::from_xyzw(x(3), x(4), x(5), x(6)))
+		};
+		Self {
+			time: (q[1] as f64 * DELTA_TIME_STEP as f64) as f32,
+			fps: q[0] as i32,
+			head: pose(0),
+			left_hand: pose(1),
+			right_hand: pose(2)
+		}
+	}
+
 	pub fn serialize_to_vector(&self) -> Vec<u8> {
 		let mut out = Vec::with_capacity(4 + 4 + ((3 + 4) * 4 * 3));
 		self.serialize_to_writer(&mut out).unwrap();
@@ -211,41 +480,508 @@ impl ReplayFrame {
 	}
 }
 
+/// What happened to a note, see [`NoteEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NoteEventType {
+	Good,
+	/// Cut with the wrong saber, in the wrong direction, or too slowly.
+	Bad,
+	Miss,
+	/// A bomb was cut.
+	Bomb
+}
+
+/// How a note is scored, which BeatLeader encodes in the note's ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NoteScoringType {
+	Normal,
+	Ignore,
+	NoScore,
+	/// The head of an arc, whose post-swing is always full.
+	ArcHead,
+	/// The tail of an arc, whose pre-swing is always full.
+	ArcTail,
+	/// The head of a chain, which has no post-swing.
+	ChainHead,
+	/// A link of a chain, which is worth a fixed score.
+	ChainLink
+}
+
+/// How a note was cut, for good & bad cuts.
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoteCutInfo {
+	pub speed_ok: bool,
+	pub direction_ok: bool,
+	pub saber_type_ok: bool,
+	pub was_cut_too_soon: bool,
+	pub saber_speed: f32,
+	pub saber_direction: Vec3,
+	pub saber_type: i32,
+	pub time_deviation: f32,
+	pub cut_direction_deviation: f32,
+	pub cut_point: Vec3,
+	pub cut_normal: Vec3,
+	pub cut_distance_to_center: f32,
+	pub cut_angle: f32,
+	/// The pre-swing rating, where `1` is a full swing. Over-swings can exceed `1`.
+	pub before_cut_rating: f32,
+	/// The post-swing rating, where `1` is a full swing. Over-swings can exceed `1`.
+	pub after_cut_rating: f32
+}
+
+impl NoteCutInfo {
+	pub fn from_reader<R: Read>(r: &mut R) -> Result<Self, ParseError> {
+		Ok(Self {
+			speed_ok: read_bool(r)?,
+			direction_ok: read_bool(r)?,
+			saber_type_ok: read_bool(r)?,
+			was_cut_too_soon: read_bool(r)?,
+			saber_speed: read_f32(r)?,
+			saber_direction: read_vec3(r)?,
+			saber_type: read_i32(r)?,
+			time_deviation: read_f32(r)?,
+			cut_direction_deviation: read_f32(r)?,
+			cut_point: read_vec3(r)?,
+			cut_normal: read_vec3(r)?,
+			cut_distance_to_center: read_f32(r)?,
+			cut_angle: read_f32(r)?,
+			before_cut_rating: read_f32(r)?,
+			after_cut_rating: read_f32(r)?
+		})
+	}
+
+	pub fn serialize_to_writer<W: Write>(&self, w: &mut W) -> Result<(), io::Error> {
+		w.write_all(&[self.speed_ok as u8, self.direction_ok as u8, self.saber_type_ok as u8, self.was_cut_too_soon as u8])?;
+		w.write_all(&self.saber_speed.to_le_bytes())?;
+		write_vec3(w, self.saber_direction)?;
+		w.write_all(&self.saber_type.to_le_bytes())?;
+		w.write_all(&self.time_deviation.to_le_bytes())?;
+		w.write_all(&self.cut_direction_deviation.to_le_bytes())?;
+		write_vec3(w, self.cut_point)?;
+		write_vec3(w, self.cut_normal)?;
+		w.write_all(&self.cut_distance_to_center.to_le_bytes())?;
+		w.write_all(&self.cut_angle.to_le_bytes())?;
+		w.write_all(&self.before_cut_rating.to_le_bytes())?;
+		w.write_all(&self.after_cut_rating.to_le_bytes())?;
+		Ok(())
+	}
+}
+
+/// A note being cut, missed, or a bomb being hit.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoteEvent {
+	/// Identifies the note by its scoring type, position, color & direction. See [`Self::scoring_type`].
+	pub note_id: i32,
+	/// The real time of the event, in seconds.
+	pub event_time: f32,
+	/// The real time the note spawned at, in seconds.
+	pub spawn_time: f32,
+	pub event_type: NoteEventType,
+	/// How the note was cut, for [`NoteEventType::Good`] & [`NoteEventType::Bad`] events.
+	pub cut: Option<NoteCutInfo>
+}
+
+impl NoteEvent {
+	pub fn from_reader<R: Read>(r: &mut R) -> Result<Self, ParseError> {
+		let note_id = read_i32(r)?;
+		let event_time = read_f32(r)?;
+		let spawn_time = read_f32(r)?;
+		let event_type = match read_i32(r)? {
+			0 => NoteEventType::Good,
+			1 => NoteEventType::Bad,
+			2 => NoteEventType::Miss,
+			3 => NoteEventType::Bomb,
+			other => return Err(ParseError::InvalidNoteEventType(other))
+		};
+		let cut = match event_type {
+			NoteEventType::Good | NoteEventType::Bad => Some(NoteCutInfo::from_reader(r)?),
+			_ => None
+		};
+		Ok(Self {
+			note_id,
+			event_time,
+			spawn_time,
+			event_type,
+			cut
+		})
+	}
+
+	pub fn serialize_to_writer<W: Write>(&self, w: &mut W) -> Result<(), io::Error> {
+		w.write_all(&self.note_id.to_le_bytes())?;
+		w.write_all(&self.event_time.to_le_bytes())?;
+		w.write_all(&self.spawn_time.to_le_bytes())?;
+		let event_type: i32 = match self.event_type {
+			NoteEventType::Good => 0,
+			NoteEventType::Bad => 1,
+			NoteEventType::Miss => 2,
+			NoteEventType::Bomb => 3
+		};
+		w.write_all(&event_type.to_le_bytes())?;
+		if matches!(self.event_type, NoteEventType::Good | NoteEventType::Bad) {
+			self.cut.clone().unwrap_or_default().serialize_to_writer(w)?;
+		}
+		Ok(())
+	}
+
+	/// Returns how the note is scored. Replays from before BeatLeader recorded it are always
+	/// [`NoteScoringType::Normal`].
+	pub fn scoring_type(&self) -> NoteScoringType {
+		match self.note_id / 10000 {
+			1 => NoteScoringType::Ignore,
+			2 => NoteScoringType::NoScore,
+			4 => NoteScoringType::ArcHead,
+			5 => NoteScoringType::ArcTail,
+			6 => NoteScoringType::ChainHead,
+			7 => NoteScoringType::ChainLink,
+			_ => NoteScoringType::Normal
+		}
+	}
+
+	/// Returns the most the note can score, before the combo multiplier.
+	pub fn max_score(&self) -> i32 {
+		match self.scoring_type() {
+			NoteScoringType::Normal | NoteScoringType::ArcHead | NoteScoringType::ArcTail => 115,
+			NoteScoringType::ChainHead => 85,
+			NoteScoringType::ChainLink => 20,
+			NoteScoringType::Ignore | NoteScoringType::NoScore => 0
+		}
+	}
+
+	/// Returns the note's score before the combo multiplier: up to 70 for the pre-swing, 30 for the post-swing & 15
+	/// for accuracy. Anything but a good cut scores `0`.
+	pub fn score(&self) -> i32 {
+		let (NoteEventType::Good, Some(cut)) = (self.event_type, &self.cut) else {
+			return 0;
+		};
+		let before = (70. * cut.before_cut_rating.clamp(0., 1.)).round() as i32;
+		let after = (30. * cut.after_cut_rating.clamp(0., 1.)).round() as i32;
+		let accuracy = (15. * (1. - (cut.cut_distance_to_center / 0.3).clamp(0., 1.))).round() as i32;
+		match self.scoring_type() {
+			NoteScoringType::Normal => before + after + accuracy,
+			NoteScoringType::ArcHead => before + 30 + accuracy,
+			NoteScoringType::ArcTail => 70 + after + accuracy,
+			NoteScoringType::ChainHead => before + accuracy,
+			NoteScoringType::ChainLink => 20,
+			NoteScoringType::Ignore | NoteScoringType::NoScore => 0
+		}
+	}
+}
+
+/// The game's combo multiplier, which doubles up to 8x after `2x` notes at each level & halves when the combo breaks.
+struct ComboMultiplier {
+	multiplier: i64,
+	progress: i64
+}
+
+impl ComboMultiplier {
+	fn new() -> Self {
+		Self { multiplier: 1, progress: 0 }
+	}
+
+	fn hit(&mut self) {
+		if self.multiplier < 8 {
+			self.progress += 1;
+			if self.progress >= self.multiplier * 2 {
+				self.multiplier *= 2;
+				self.progress = 0;
+			}
+		}
+	}
+
+	fn miss(&mut self) {
+		self.multiplier = (self.multiplier / 2).max(1);
+		self.progress = 0;
+	}
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Replay {
 	pub info: ReplayInfo,
-	pub frames: Vec<ReplayFrame>
+	pub frames: Vec<ReplayFrame>,
+	/// The note events, in the order they happened.
+	pub notes: Vec<NoteEvent>
 }
 
 impl Replay {
+	/// Returns whether `bytes` start with a BSOR header of a supported version, without parsing the rest of the replay.
+	pub fn is_valid_bsor(bytes: &[u8]) -> bool {
+		bytes.len() >= 5 && bytes[0..4] == BSOR_MAGIC.to_le_bytes() && bytes[4] == BSOR_VERSION
+	}
+
+	/// Builds a replay from `(time, frame)` pairs, overwriting each frame's time and estimating its fps from the time
+	/// until the next frame. The last frame inherits the fps of the one before it.
+	pub fn from_frames(info: ReplayInfo, frames: Vec<(f32, ReplayFrame)>) -> Self {
+		let times: Vec<f32> = frames.iter().map(|(time, _)| *time).collect();
+		let mut fps = 0;
+		let frames = frames
+			.into_iter()
+			.enumerate()
+			.map(|(i, (time, frame))| {
+				if let Some(dt) = times.get(i + 1).map(|next| next - time).filter(|dt| *dt > 0.) {
+					fps = (1. / dt).round() as i32;
+				}
+				ReplayFrame { time, fps, ..frame }
+			})
+			.collect();
+		Self { info, frames, notes: Vec::new() }
+	}
+
 	pub fn from_reader<R: Read>(r: &mut R) -> Result<Self, ParseError> {
-		assert_eq!(read_i32(r)?, 0x442d3d69);
-		assert_eq!(read_byte(r)?, 1);
-		let info = ReplayInfo::from_reader(r)?;
-		assert_eq!(read_byte(r)?, 1);
-		let n_frames = read_i32(r)? as usize;
+		Self::read(r, false)
+	}
+
+	/// Like [`Self::from_reader`], but replaces invalid UTF-8 in strings (e.g. Latin-1 player names) instead of
+	/// failing.
+	pub fn from_reader_lossy<R: Read>(r: &mut R) -> Result<Self, ParseError> {
+		Self::read(r, true)
+	}
+
+	fn read<R: Read>(r: &mut R, lossy: bool) -> Result<Self, ParseError> {
+		read_header(r)?;
+		let info = ReplayInfo::read(r, lossy)?;
+		read_section(r, 1)?;
+		let n_frames = read_len(r)?;
 		let mut frames = vec![ReplayFrame::default(); n_frames];
 		for frame in frames.iter_mut() {
 			*frame = ReplayFrame::from_reader(r)?;
 		}
-		Ok(Self { info, frames })
+		let notes = read_notes(r)?;
+		Ok(Self { info, frames, notes })
+	}
+
+	/// Returns the replay's score with modifier multipliers applied.
+	pub fn effective_score(&self) -> i32 {
+		(self.info.score as f32 * self.info.modifier_multiplier()) as i32
+	}
+
+	/// Returns whether the replay was played with any of the [`DISQUALIFYING_MODIFIERS`].
+	pub fn is_modded(&self) -> bool {
+		self.info.modifiers.iter().any(|m| DISQUALIFYING_MODIFIERS.contains(&m.as_str()))
+	}
+
+	/// Returns the accuracy of [`ReplayInfo::score`] (`0..=1`), relative to the max score of the notes that were
+	/// played. Returns `0` if no scoring notes were played.
+	pub fn accuracy(&self) -> f32 {
+		match self.score_totals().last() {
+			Some(&(_, _, max_score)) => self.info.score as f32 / max_score as f32,
+			None => 0.
+		}
+	}
+
+	/// Returns the cumulative accuracy (`0..=1`) over time, sampled at `buckets` evenly spaced times from the first to
+	/// the last scoring note, as in BeatLeader's score graphs. Each sample's time is a note event time (real time), and
+	/// its accuracy is the score so far over the max score so far, both with the combo multiplier applied. Wall hits
+	/// also break the combo in game, but aren't in [`Self::notes`], so they're not accounted for.
+	pub fn score_graph(&self, buckets: usize) -> Vec<(f32, f32)> {
+		let totals = self.score_totals();
+		let (Some(first), Some(last)) = (totals.first(), totals.last()) else {
+			return Vec::new();
+		};
+		(1..=buckets)
+			.map(|i| {
+				let time = if i == buckets { last.0 } else { first.0 + (last.0 - first.0) * i as f32 / buckets as f32 };
+				let (_, score, max_score) = totals[totals.partition_point(|x| x.0 <= time) - 1];
+				(time, score as f32 / max_score as f32)
+			})
+			.collect()
+	}
+
+	/// Returns the time, total score & total max score after each scoring note, in chronological order.
+	fn score_totals(&self) -> Vec<(f32, i64, i64)> {
+		let mut notes: Vec<&NoteEvent> = self.notes.iter().collect();
+		notes.sort_by(|a, b| a.event_time.total_cmp(&b.event_time));
+
+		let (mut multiplier, mut max_multiplier) = (ComboMultiplier::new(), ComboMultiplier::new());
+		let (mut score, mut max_score) = (0, 0);
+		let mut totals = Vec::new();
+		for note in notes {
+			if note.event_type == NoteEventType::Bomb {
+				multiplier.miss();
+				continue;
+			}
+			if note.max_score() == 0 {
+				continue;
+			}
+			if note.event_type == NoteEventType::Good {
+				score += note.score() as i64 * multiplier.multiplier;
+				multiplier.hit();
+			} else {
+				multiplier.miss();
+			}
+			max_score += note.max_score() as i64 * max_multiplier.multiplier;
+			max_multiplier.hit();
+			totals.push((note.event_time, score, max_score));
+		}
+		totals
+	}
+
+	/// Returns whether the replay was played with a practice mode speed other than normal.
+	pub fn is_practice_mode(&self) -> bool {
+		self.info.speed != 0. && self.info.speed != 1.
+	}
+
+	/// Returns the speed the song was played at, where `1` is normal speed. This combines the practice mode speed with
+	/// the Faster (`FS`), Super Fast (`SF`) & Slower (`SS`) Song modifiers.
+	pub fn speed(&self) -> f32 {
+		let practice = if self.info.speed == 0. { 1. } else { self.info.speed };
+		let modifier = self
+			.info
+			.modifiers
+			.iter()
+			.map(|m| match m.as_str() {
+				"FS" => 1.2,
+				"SF" => 1.5,
+				"SS" => 0.85,
+				_ => 1.
+			})
+			.product::<f32>();
+		practice * modifier
+	}
+
+	/// Converts a frame time, which is in real (wall-clock) time, into the time domain of beatmap object times by
+	/// dividing it by [`Self::speed`].
+	pub fn song_time(&self, frame_time: f32) -> f32 {
+		frame_time / self.speed()
+	}
+
+	/// Returns the reaction time (half jump duration) in seconds for a map with the given note jump speed, i.e. how
+	/// long notes were visible before reaching the player. The replay only stores the jump distance, so the NJS has to
+	/// come from the map. Returns `0` for a non-positive NJS.
+	pub fn reaction_time(&self, njs: f32) -> f32 {
+		if njs <= 0. {
+			return 0.;
+		}
+		self.info.jump_distance / 2. / njs
+	}
+
+	/// Returns the fastest the given hand moved between two consecutive frames, in meters per second.
+	pub fn max_hand_velocity(&self, hand: Hand) -> f32 {
+		self.hand_velocities(hand).map(|(_, velocity)| velocity).fold(0., f32::max)
+	}
+
+	/// Returns the fastest the head turned between two consecutive frames, in radians per second. Unusually high values
+	/// can point to tracking issues. Frames which don't advance in time are skipped, and replays with fewer than 2
+	/// frames give `0`.
+	pub fn max_head_rotation_speed(&self) -> f32 {
+		self.frames
+			.windows(2)
+			.filter_map(|frames| {
+				let delta = frames[1].time - frames[0].time;
+				(delta > 0.).then(|| frames[0].head.1.normalize().angle_between(frames[1].head.1.normalize()) / delta)
+			})
+			.fold(0., f32::max)
+	}
+
+	/// Returns the times of frames where either hand moved faster than `threshold` meters per second since the
+	/// previous frame, which is a sign of a tampered replay when the threshold is physically implausible.
+	pub fn suspicious_movement(&self, threshold: f32) -> Vec<f32> {
+		let mut times: Vec<f32> = self
+			.hand_velocities(Hand::Left)
+			.chain(self.hand_velocities(Hand::Right))
+			.filter(|(_, velocity)| *velocity > threshold)
+			.map(|(time, _)| time)
+			.collect();
+		times.sort_by(f32::total_cmp);
+		times.dedup();
+		times
+	}
+
+	/// Returns the head's distance from the origin in each frame, in meters. Replays with almost no change in this are
+	/// likely AFK or seated players, and an unusual average can point to height calibration issues.
+	pub fn per_frame_distance_from_origin(&self) -> Vec<f32> {
+		self.frames.iter().map(|frame| frame.head.0.length()).collect()
+	}
+
+	/// Returns the total distance the head moved across all frames, in meters. This is a rough measure of how much the
+	/// player moved around during the song.
+	pub fn head_travel_distance(&self) -> f32 {
+		self.frames.windows(2).map(|frames| frames[0].head.0.distance(frames[1].head.0)).sum()
+	}
+
+	/// Yields the time of each frame & the hand's velocity since the previous frame. Frames which don't advance in
+	/// time are skipped, as no velocity can be derived from them.
+	fn hand_velocities(&self, hand: Hand) -> impl Iterator<Item = (f32, f32)> + '_ {
+		self.frames.windows(2).filter_map(move |frames| {
+			let delta = frames[1].time - frames[0].time;
+			(delta > 0.).then(|| (frames[1].time, frames[0].hand(hand).0.distance(frames[1].hand(hand).0) / delta))
+		})
 	}
 
 	pub fn serialize_to_writer<W: Write>(&self, w: &mut W) -> Result<(), io::Error> {
-		w.write_all(&[0x69, 0x3d, 0x2d, 0x44, 1])?;
+		w.write_all(&BSOR_MAGIC.to_le_bytes())?;
+		w.write_all(&[BSOR_VERSION])?;
 		self.info.serialize_to_writer(w)?;
 		w.write_all(&[1])?;
 		w.write_all(&(self.frames.len() as i32).to_le_bytes())?;
 		for frame in &self.frames {
 			frame.serialize_to_writer(w)?;
 		}
-		Ok(())
+		write_notes(w, &self.notes)
 	}
 
 	pub fn serialize_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
 		self.serialize_to_writer(&mut BufWriter::new(File::create(path)?))
 	}
 
+	/// Writes the replay in a custom delta-compressed format, which is **not** BSOR and can't be read by the game or
+	/// other replay tools, only by [`Self::from_reader_delta`]. It's meant for archiving large numbers of replays.
+	///
+	/// The format is the BSOR header & info with [`DELTA_MAGIC`] in place of the BSOR magic number, followed by the
+	/// frames. Each frame's time & pose is quantized to [`DELTA_TIME_STEP`] and [`DELTA_POSE_STEP`], and stored as
+	/// varint deltas from the previous frame (the first frame from zero). Since the deltas are between quantized
+	/// values, the error doesn't accumulate: every value is read back within half a step of the original. Values too
+	/// large to quantize (including infinities) saturate, and NaNs are read back as zero. The note events follow as in
+	/// BSOR, since they're small next to the frames.
+	#[cfg(feature = "delta")]
+	pub fn serialize_delta_to_writer<W: Write>(&self, w: &mut W) -> Result<(), io::Error> {
+		w.write_all(&DELTA_MAGIC.to_le_bytes())?;
+		w.write_all(&[DELTA_VERSION])?;
+		self.info.serialize_to_writer(w)?;
+		w.write_all(&[1])?;
+		w.write_all(&(self.frames.len() as i32).to_le_bytes())?;
+		let mut last = [0; 23];
+		for frame in &self.frames {
+			let q = frame.quantize();
+			for (x, last) in q.iter().zip(&last) {
+				write_varint(w, x.wrapping_sub(*last))?;
+			}
+			last = q;
+		}
+		write_notes(w, &self.notes)
+	}
+
+	/// Reads a replay written by [`Self::serialize_delta_to_writer`].
+	#[cfg(feature = "delta")]
+	pub fn from_reader_delta<R: Read>(r: &mut R) -> Result<Self, ParseError> {
+		let magic = read_i32(r)?;
+		if magic as u32 != DELTA_MAGIC {
+			return Err(ParseError::InvalidMagic(magic));
+		}
+		let version = read_byte(r)?;
+		if !(1..=DELTA_VERSION).contains(&version) {
+			return Err(ParseError::UnsupportedVersion(version));
+		}
+		let info = ReplayInfo::read(r, false)?;
+		read_section(r, 1)?;
+		let n_frames = read_len(r)?;
+		let mut frames = Vec::with_capacity(n_frames);
+		let mut q = [0i64; 23];
+		for _ in 0..n_frames {
+			for x in q.iter_mut() {
+				// wrapping, as the deltas between saturated values of a non-finite frame wrap when written
+				*x = x.wrapping_add(read_varint(r)?);
+			}
+			frames.push(ReplayFrame::dequantize(&q));
+		}
+		// version 1 didn't store the note events
+		let notes = if version >= 2 { read_notes(r)? } else { Vec::new() };
+		Ok(Self { info, frames, notes })
+	}
+
 	pub fn serialize_to_bytes(&self) -> Vec<u8> {
 		let mut out = Vec::new();
 		self.serialize_to_writer(&mut out).unwrap();
@@ -256,11 +992,161 @@ impl Replay {
 		Self::from_reader(&mut BufReader::new(File::open(path)?))
 	}
 
+	/// Reads the replay file asynchronously, then parses it in memory.
+	#[cfg(feature = "tokio")]
+	pub async fn from_file_async<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+		Self::from_bytes(tokio::fs::read(path).await?)
+	}
+
+	/// Memory-maps the replay file and parses it directly from the mapped region, avoiding a copy into a buffer.
+	///
+	/// # Safety
+	///
+	/// The file must not be modified or truncated by this or any other process while it's being parsed. Doing so is
+	/// undefined behavior (and on most platforms raises `SIGBUS`), so only use this for files which are known to be
+	/// complete and immutable.
+	#[cfg(feature = "mmap")]
+	pub unsafe fn from_mmap<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+		let file = File::open(path)?;
+		// SAFETY: the caller guarantees the file isn't modified while mapped
+		let mmap = unsafe { memmap2::Mmap::map(&file)? };
+		Self::from_bytes(&mmap[..])
+	}
+
 	pub fn from_bytes<B: AsRef<[u8]>>(bytes: B) -> Result<Self, ParseError> {
 		Self::from_reader(&mut bytes.as_ref())
 	}
 }
 
+impl TryFrom<&[u8]> for Replay {
+	type Error = ParseError;
+
+	fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+		Self::from_bytes(bytes)
+	}
+}
+
+/// A section of a replay, emitted by [`ReplayParser`] once it has been fully received.
+#[derive(Debug, Clone)]
+pub enum ReplayEvent {
+	Info(Box<ReplayInfo>),
+	Frame(ReplayFrame)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParserState {
+	Header,
+	Info,
+	FramesHeader,
+	Frames(usize),
+	Done
+}
+
+/// Incrementally parses a replay as its bytes arrive, e.g. from a network stream. Bytes are fed in with
+/// [`ReplayParser::push`], and completed sections are retrieved with [`ReplayParser::poll`]; first the
+/// [`ReplayEvent::Info`], then one [`ReplayEvent::Frame`] per frame.
+#[derive(Debug)]
+pub struct ReplayParser {
+	buffer: Vec<u8>,
+	position: usize,
+	/// How many unparsed bytes the current section needs at least, so it isn't re-parsed before they've arrived.
+	needed: usize,
+	state: ParserState
+}
+
+/// The unparsed bytes of a [`ReplayParser`]. Records how many more bytes a read which ran out of them needed.
+struct Pending<'a> {
+	bytes: &'a [u8],
+	missing: usize
+}
+
+impl Read for Pending<'_> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if self.bytes.is_empty() {
+			self.missing = buf.len();
+		}
+		self.bytes.read(buf)
+	}
+
+	fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+		if buf.len() > self.bytes.len() {
+			self.missing = buf.len() - self.bytes.len();
+			self.bytes = &[];
+			return Err(io::ErrorKind::UnexpectedEof.into());
+		}
+		self.bytes.read_exact(buf)
+	}
+}
+
+impl Default for ReplayParser {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl ReplayParser {
+	pub fn new() -> Self {
+		Self {
+			buffer: Vec::new(),
+			position: 0,
+			needed: 0,
+			state: ParserState::Header
+		}
+	}
+
+	/// Feeds more bytes of the replay into the parser.
+	pub fn push(&mut self, bytes: &[u8]) {
+		self.buffer.drain(..self.position);
+		self.position = 0;
+		self.buffer.extend_from_slice(bytes);
+	}
+
+	/// Returns whether all frames have been parsed. Any sections following the frames are ignored.
+	pub fn is_done(&self) -> bool {
+		self.state == ParserState::Done
+	}
+
+	/// Returns the next completed section, or `None` if more bytes are needed (or parsing is done).
+	pub fn poll(&mut self) -> Result<Option<ReplayEvent>, ParseError> {
+		loop {
+			let available = self.buffer.len() - self.position;
+			if available < self.needed {
+				return Ok(None);
+			}
+			let mut r = Pending {
+				bytes: &self.buffer[self.position..],
+				missing: 0
+			};
+			let result = match self.state {
+				ParserState::Header => read_header(&mut r).map(|_| (None, ParserState::Info)),
+				ParserState::Info => ReplayInfo::from_reader(&mut r).map(|info| (Some(ReplayEvent::Info(Box::new(info))), ParserState::FramesHeader)),
+				ParserState::FramesHeader => read_section(&mut r, 1).and_then(|_| read_i32(&mut r)).map(|n_frames| match n_frames {
+					n if n > 0 => (None, ParserState::Frames(n as usize)),
+					_ => (None, ParserState::Done)
+				}),
+				ParserState::Frames(remaining) => ReplayFrame::from_reader(&mut r)
+					.map(|frame| (Some(ReplayEvent::Frame(frame)), if remaining > 1 { ParserState::Frames(remaining - 1) } else { ParserState::Done })),
+				ParserState::Done => return Ok(None)
+			};
+			match result {
+				Ok((event, next)) => {
+					self.position += available - r.bytes.len();
+					self.needed = 0;
+					self.state = next;
+					if event.is_some() {
+						return Ok(event);
+					}
+				}
+				Err(ParseError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+					self.needed = available + r.missing.max(1);
+					return Ok(None);
+				}
+				Err(e) => return Err(e)
+			}
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use std::f32::consts::PI;
@@ -278,11 +1164,22 @@ mod tests {
 		};
 		let serialized_frame = frame.serialize_to_vector();
 		let deserialized_frame = ReplayFrame::from_reader(&mut serialized_frame.as_slice()).unwrap();
-		assert_eq!(deserialized_frame.time, frame.time);
-		assert_eq!(deserialized_frame.fps, frame.fps);
-		assert_eq!(deserialized_frame.head, frame.head);
-		assert_eq!(deserialized_frame.left_hand, frame.left_hand);
-		assert_eq!(deserialized_frame.right_hand, frame.right_hand);
+		assert!(deserialized_frame.approx_eq(&frame, 1e-6));
+	}
+
+	#[test]
+	fn test_replay_frame_approx_eq() {
+		let frame = ReplayFrame {
+			time: 0.3,
+			fps: 60,
+			head: (Vec3::new(0.0, 1.8, 0.0), Quat::from_rotation_y(PI / 4.)),
+			..Default::default()
+		};
+		let mut other = frame.clone();
+		other.head.0.y += 0.001;
+		other.head.1 = -other.head.1;
+		assert!(frame.approx_eq(&other, 0.01));
+		assert!(!frame.approx_eq(&other, 0.0001));
 	}
 
 	#[test]
@@ -306,11 +1203,396 @@ mod tests {
 		assert_eq!(replay.info.difficulty, "ExpertPlus");
 	}
 
+	#[test]
+	#[cfg(feature = "chrono")]
+	fn test_parse_timestamp() {
+		let mut replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		let timestamp = replay.info.parse_timestamp().unwrap();
+		assert_eq!(timestamp.timestamp().to_string(), replay.info.timestamp);
+
+		replay.info.timestamp = String::from("yesterday");
+		assert_eq!(replay.info.parse_timestamp(), None);
+	}
+
+	#[test]
+	#[cfg(feature = "tokio")]
+	fn test_replay_parse_async() {
+		let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+		let replay = runtime.block_on(Replay::from_file_async("tests/data/replays/replay1.bsor")).unwrap();
+		let expected = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		assert_eq!(replay.info.player_name, expected.info.player_name);
+		assert_eq!(replay.frames.len(), expected.frames.len());
+	}
+
+	#[test]
+	#[cfg(feature = "mmap")]
+	fn test_replay_parse_mmap() {
+		// SAFETY: the test data isn't modified while the tests run
+		let replay = unsafe { Replay::from_mmap("tests/data/replays/replay1.bsor") }.unwrap();
+		let expected = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		assert_eq!(replay.info.player_name, expected.info.player_name);
+		assert_eq!(replay.frames.len(), expected.frames.len());
+	}
+
+	#[test]
+	fn test_from_frames() {
+		let Replay { info, .. } = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		let frames = (0..5).map(|i| (i as f32 / 90., ReplayFrame::default())).collect();
+		let replay = Replay::from_frames(info.clone(), frames);
+		assert!(replay.frames.iter().all(|f| f.fps == 90));
+		assert_eq!(replay.frames[4].time, 4. / 90.);
+
+		let replay = Replay::from_frames(info, vec![(0., ReplayFrame::default())]);
+		assert_eq!(replay.frames[0].fps, 0);
+	}
+
+	#[test]
+	fn test_per_frame_distance_from_origin() {
+		let mut replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		replay.frames[0].head.0 = Vec3::new(0., 1.6, -0.3);
+		let distances = replay.per_frame_distance_from_origin();
+		assert_eq!(distances.len(), replay.frames.len());
+		assert!((distances[0] - 1.6279).abs() < 1e-3);
+		// a standing player's head stays roughly at their height
+		assert!(distances.iter().all(|d| (1. ..2.5).contains(d)));
+	}
+
+	#[test]
+	fn test_head_travel_distance() {
+		let mut replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		assert!(replay.head_travel_distance() > 0.);
+
+		replay.frames.truncate(3);
+		replay.frames[0].head.0 = Vec3::new(0., 1.5, 0.);
+		replay.frames[1].head.0 = Vec3::new(0.3, 1.9, 0.);
+		replay.frames[2].head.0 = Vec3::new(0.3, 1.9, 1.);
+		assert!((replay.head_travel_distance() - 1.5).abs() < 1e-6);
+
+		replay.frames.truncate(1);
+		assert_eq!(replay.head_travel_distance(), 0.);
+	}
+
+	#[test]
+	fn test_max_head_rotation_speed() {
+		let mut replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		let speed = replay.max_head_rotation_speed();
+		assert!(speed > 0. && speed.is_finite());
+
+		replay.frames.truncate(3);
+		for (i, frame) in replay.frames.iter_mut().enumerate() {
+			frame.time = i as f32 * 0.5;
+			frame.head.1 = Quat::IDENTITY;
+		}
+		replay.frames[1].head.1 = Quat::from_rotation_y(0.5);
+		replay.frames[2].head.1 = Quat::from_rotation_y(0.25);
+		assert!((replay.max_head_rotation_speed() - 1.).abs() < 1e-4);
+
+		replay.frames.truncate(1);
+		assert_eq!(replay.max_head_rotation_speed(), 0.);
+	}
+
+	#[test]
+	fn test_suspicious_movement() {
+		let mut replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		assert!(replay.max_hand_velocity(Hand::Left) < 50.);
+		assert!(replay.suspicious_movement(50.).is_empty());
+
+		let frame = &mut replay.frames[1000];
+		let time = frame.time;
+		frame.right_hand.0 += Vec3::new(10., 0., 0.);
+		assert!(replay.max_hand_velocity(Hand::Right) > 50.);
+		// teleporting away & back again
+		assert_eq!(replay.suspicious_movement(50.), [time, replay.frames[1001].time]);
+
+		// frames which don't advance in time are ignored
+		replay.frames[1000].time = replay.frames[999].time;
+		assert_eq!(replay.suspicious_movement(50.), [replay.frames[1001].time]);
+	}
+
+	#[test]
+	fn test_replay_song_time() {
+		let mut replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		assert!(!replay.is_practice_mode());
+		assert_eq!(replay.song_time(2.), 2.);
+
+		replay.info.speed = 1.5;
+		assert!(replay.is_practice_mode());
+		assert_eq!(replay.song_time(3.), 2.);
+
+		// speed modifiers aren't practice mode, but still change the song speed
+		replay.info.speed = 0.;
+		replay.info.modifiers = vec![String::from("FS"), String::from("NF")];
+		assert!(!replay.is_practice_mode());
+		assert_eq!(replay.speed(), 1.2);
+		assert_eq!(replay.song_time(2.4), 2.);
+	}
+
+	#[test]
+	fn test_reaction_time() {
+		let mut replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		replay.info.jump_distance = 24.;
+		assert_eq!(replay.reaction_time(16.), 0.75);
+		assert_eq!(replay.reaction_time(0.), 0.);
+	}
+
+	#[test]
+	fn test_csv_row() {
+		let Replay { mut info, .. } = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		info.player_name = String::from(r#"Doe, "Jane""#);
+		info.modifiers = vec![String::from("FS"), String::from("NF")];
+		let row = info.to_csv_row();
+		assert!(row.contains(r#","Doe, ""Jane""","#));
+		assert!(row.contains(r#","FS,NF","#));
+		assert!(ReplayInfo::csv_header().starts_with("version,game_version,timestamp,player_id,player_name,"));
+		assert_eq!(ReplayInfo::csv_header().split(',').count(), 23);
+	}
+
+	#[test]
+	fn test_replay_modifiers() {
+		let mut replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		assert!(!replay.is_modded());
+
+		replay.info.modifiers = vec![String::from("FS"), String::from("NF")];
+		assert!(replay.is_modded());
+		replay.info.modifiers = vec![String::from("FS")];
+		assert!(!replay.is_modded());
+	}
+
+	#[test]
+	fn test_replay_effective_score() {
+		let mut replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		assert_eq!(replay.effective_score(), replay.info.score);
+
+		replay.info.score = 1000;
+		replay.info.modifiers = vec![String::from("FS"), String::from("NB")];
+		assert!((replay.info.modifier_multiplier() - 0.98).abs() < 1e-6);
+		assert_eq!(replay.effective_score(), 980);
+	}
+
 	#[test]
 	fn test_replay_ser() {
 		let replay = std::fs::read("tests/data/replays/replay1.bsor").unwrap();
+		assert_eq!(u32::from_le_bytes(replay[..4].try_into().unwrap()), BSOR_MAGIC);
+		assert_eq!(replay[4], BSOR_VERSION);
 		let parsed_replay = Replay::from_bytes(&replay).unwrap();
 		let serialized_replay = parsed_replay.serialize_to_bytes();
 		assert_eq!(serialized_replay, replay[..serialized_replay.len()]); // slice is temporary until the other fields are finished
 	}
+
+	#[test]
+	fn test_replay_notes() {
+		let replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		assert_eq!(replay.notes.len(), 2882);
+		let note = &replay.notes[0];
+		assert_eq!((note.note_id, note.event_type, note.scoring_type()), (31111, NoteEventType::Good, NoteScoringType::Normal));
+		assert_eq!((note.score(), note.max_score()), (114, 115));
+		assert!(
+			replay
+				.notes
+				.iter()
+				.any(|x| x.event_type == NoteEventType::Miss && x.cut.is_none() && x.score() == 0)
+		);
+		assert_eq!(
+			replay
+				.notes
+				.iter()
+				.filter(|x| x.scoring_type() == NoteScoringType::ChainLink)
+				.map(|x| x.max_score())
+				.max(),
+			Some(20)
+		);
+	}
+
+	#[test]
+	fn test_score_graph() {
+		let replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		let graph = replay.score_graph(100);
+		assert_eq!(graph.len(), 100);
+		assert!(graph.windows(2).all(|x| x[0].0 <= x[1].0));
+		assert!(graph.iter().all(|&(_, accuracy)| (0. ..=1.).contains(&accuracy)));
+
+		// the raw score accumulated by each sample never decreases
+		let mut notes: Vec<_> = replay.notes.iter().collect();
+		notes.sort_by(|a, b| a.event_time.total_cmp(&b.event_time));
+		let raw: Vec<i32> = graph
+			.iter()
+			.map(|&(time, _)| notes.iter().take_while(|x| x.event_time <= time).map(|x| x.score()).sum())
+			.collect();
+		assert!(raw.windows(2).all(|x| x[0] <= x[1]));
+
+		// the simulated score ends up near the recorded one
+		let accuracy = replay.accuracy();
+		assert!((0.95..0.97).contains(&accuracy), "{accuracy}");
+		assert!((graph.last().unwrap().1 - accuracy).abs() < 1e-3, "{:?} vs {accuracy}", graph.last());
+
+		assert!(replay.score_graph(0).is_empty());
+		assert!(Replay { notes: Vec::new(), ..replay }.score_graph(10).is_empty());
+	}
+
+	#[test]
+	fn test_unvalidated_song_hash() {
+		let mut replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		for hash in ["Origins", "6e9498f81bbf26fa47b1691557b22486a720254d"] {
+			replay.info.song_hash = String::from(hash);
+			let bytes = replay.serialize_to_bytes();
+			let parsed = Replay::from_bytes(&bytes).unwrap();
+			assert_eq!(parsed.info.song_hash, hash);
+			assert_eq!(parsed.serialize_to_bytes(), bytes);
+		}
+		assert_eq!(replay.info.song_hash().unwrap().as_str(), "6E9498F81BBF26FA47B1691557B22486A720254D");
+		replay.info.song_hash = String::from("Origins");
+		assert!(replay.info.song_hash().is_err());
+	}
+
+	#[test]
+	#[cfg(feature = "delta")]
+	fn test_delta_round_trip() {
+		let replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		let mut bytes = Vec::new();
+		replay.serialize_delta_to_writer(&mut bytes).unwrap();
+		assert!(bytes.len() < replay.serialize_to_bytes().len() / 2);
+
+		let decoded = Replay::from_reader_delta(&mut bytes.as_slice()).unwrap();
+		assert_eq!(decoded.info.player_id, replay.info.player_id);
+		assert_eq!(decoded.frames.len(), replay.frames.len());
+		assert_eq!(decoded.notes, replay.notes);
+		// lossy, but every value is within half a quantization step
+		for (a, b) in decoded.frames.iter().zip(&replay.frames) {
+			assert!(a.approx_eq(b, DELTA_POSE_STEP), "{a:?} != {b:?}");
+		}
+
+		assert!(matches!(Replay::from_reader_delta(&mut replay.serialize_to_bytes().as_slice()), Err(ParseError::InvalidMagic(_))));
+	}
+
+	#[test]
+	#[cfg(feature = "delta")]
+	fn test_delta_non_finite() {
+		let mut replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		replay.frames.truncate(4);
+		replay.frames[1].head.0.x = f32::NEG_INFINITY;
+		replay.frames[2].head.0.x = f32::INFINITY;
+		replay.frames[2].left_hand.0.y = f32::MAX;
+		let mut bytes = Vec::new();
+		replay.serialize_delta_to_writer(&mut bytes).unwrap();
+
+		let decoded = Replay::from_reader_delta(&mut bytes.as_slice()).unwrap();
+		// the extremes saturate, while the rest of the frames are unaffected
+		assert!(decoded.frames[1].head.0.x < -1e14);
+		assert!(decoded.frames[2].head.0.x > 1e14 && decoded.frames[2].left_hand.0.y > 1e14);
+		for (a, b) in decoded.frames.iter().zip(&replay.frames) {
+			assert!((a.time - b.time).abs() <= DELTA_TIME_STEP && a.fps == b.fps);
+			assert!(a.right_hand.0.abs_diff_eq(b.right_hand.0, DELTA_POSE_STEP));
+		}
+		assert!(decoded.frames[3].approx_eq(&replay.frames[3], DELTA_POSE_STEP));
+	}
+
+	#[test]
+	#[cfg(feature = "serde")]
+	fn test_serde_json() {
+		let replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		let json = serde_json::to_string(&replay.frames[0]).unwrap();
+		assert_eq!(serde_json::from_str::<ReplayFrame>(&json).unwrap(), replay.frames[0]);
+
+		let json = serde_json::to_string(&replay).unwrap();
+		let decoded: Replay = serde_json::from_str(&json).unwrap();
+		assert_eq!(decoded.info.song_hash, replay.info.song_hash);
+		assert_eq!(decoded.frames, replay.frames);
+		// the raw song hash is kept, so OST level IDs survive as well
+		let json = serde_json::to_string(&replay.info).unwrap();
+		assert!(serde_json::from_str::<ReplayInfo>(&json).is_ok());
+		assert!(serde_json::from_str::<ReplayInfo>(&json.replace(&replay.info.song_hash, "nope")).is_ok());
+	}
+
+	#[test]
+	fn test_replay_try_from_bytes() {
+		let bytes = std::fs::read("tests/data/replays/replay1.bsor").unwrap();
+		let replay = Replay::try_from(bytes.as_slice()).unwrap();
+		assert_eq!(replay.frames.len(), Replay::from_bytes(&bytes).unwrap().frames.len());
+		assert!(Replay::try_from(&bytes[..3]).is_err());
+	}
+
+	#[test]
+	fn test_replay_parser_bytewise() {
+		let bytes = std::fs::read("tests/data/replays/replay1.bsor").unwrap();
+		let replay = Replay::from_bytes(&bytes).unwrap();
+
+		let mut parser = ReplayParser::new();
+		let mut info = None;
+		let mut frames = Vec::new();
+		for byte in &bytes {
+			parser.push(std::slice::from_ref(byte));
+			while let Some(event) = parser.poll().unwrap() {
+				match event {
+					ReplayEvent::Info(i) => info = Some(i),
+					ReplayEvent::Frame(f) => frames.push(f)
+				}
+			}
+			if parser.is_done() {
+				break;
+			}
+		}
+
+		assert!(parser.is_done());
+		assert_eq!(info.unwrap().serialize_to_vector(), replay.info.serialize_to_vector());
+		assert_eq!(frames.len(), replay.frames.len());
+		for (parsed, expected) in frames.iter().zip(&replay.frames) {
+			assert_eq!(parsed.serialize_to_vector(), expected.serialize_to_vector());
+		}
+	}
+
+	#[test]
+	fn test_replay_parse_lossy() {
+		let replay = Replay::from_file("tests/data/replays/replay1.bsor").unwrap();
+		let mut info = replay.info.clone();
+		info.player_name = String::from("Ren\u{e9}");
+		let mut bytes = Vec::new();
+		Replay { info, ..replay.clone() }.serialize_to_writer(&mut bytes).unwrap();
+
+		// re-encode the name as Latin-1, keeping its length
+		let name = bytes.windows(5).position(|w| w == "Ren\u{e9}".as_bytes()).unwrap();
+		bytes.splice(name..name + 5, *b"Ren\xe9\xe9");
+
+		assert!(matches!(Replay::from_bytes(&bytes), Err(ParseError::UTF8Error(_))));
+		let lossy = Replay::from_reader_lossy(&mut bytes.as_slice()).unwrap();
+		assert_eq!(lossy.info.player_name, "Ren\u{fffd}\u{fffd}");
+		assert_eq!(lossy.frames.len(), replay.frames.len());
+	}
+
+	#[test]
+	fn test_is_valid_bsor() {
+		let mut bytes = std::fs::read("tests/data/replays/replay1.bsor").unwrap();
+		assert!(Replay::is_valid_bsor(&bytes));
+		assert!(!Replay::is_valid_bsor(&bytes[..4]));
+		bytes[4] = BSOR_VERSION + 1;
+		assert!(!Replay::is_valid_bsor(&bytes));
+		assert!(!Replay::is_valid_bsor(b"PK\x03\x04\x14"));
+	}
+
+	#[test]
+	fn test_replay_parser_bad_length() {
+		let mut header = BSOR_MAGIC.to_le_bytes().to_vec();
+		header.extend([BSOR_VERSION, 0]);
+
+		let mut parser = ReplayParser::new();
+		parser.push(&header);
+		parser.push(&(-1i32).to_le_bytes());
+		assert!(matches!(parser.poll(), Err(ParseError::InvalidLength(-1))));
+
+		// a huge length only waits for the bytes, rather than allocating them up front
+		let mut parser = ReplayParser::new();
+		parser.push(&header);
+		parser.push(&i32::MAX.to_le_bytes());
+		parser.push(b"1.0");
+		assert!(parser.poll().unwrap().is_none());
+		assert!(parser.poll().unwrap().is_none());
+		header.extend(i32::MAX.to_le_bytes());
+		assert!(matches!(Replay::from_bytes(&header), Err(ParseError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof));
+	}
+
+	#[test]
+	fn test_replay_parser_bad_magic() {
+		let mut parser = ReplayParser::new();
+		parser.push(&[0, 0, 0, 0, 1]);
+		assert!(matches!(parser.poll(), Err(ParseError::InvalidMagic(0))));
+	}
 }
@@ -0,0 +1,87 @@
+use std::{fmt::Display, str::FromStr};
+
+use sha1_smol::Digest;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("Invalid song hash '{0}'; expected 40 hexadecimal characters")]
+pub struct InvalidSongHash(pub String);
+
+/// The SHA-1 hash identifying a custom map, normalized to uppercase hexadecimal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(try_from = "String", into = "String"))]
+pub struct SongHash(String);
+
+impl SongHash {
+	pub fn new(hash: &str) -> Result<Self, InvalidSongHash> {
+		let trimmed = hash.trim();
+		if trimmed.len() != 40 || !trimmed.chars().all(|c| c.is_ascii_hexdigit()) {
+			return Err(InvalidSongHash(hash.to_string()));
+		}
+		Ok(Self(trimmed.to_ascii_uppercase()))
+	}
+
+	pub fn as_str(&self) -> &str {
+		&self.0
+	}
+}
+
+impl From<Digest> for SongHash {
+	fn from(value: Digest) -> Self {
+		Self(value.to_string().to_ascii_uppercase())
+	}
+}
+
+impl TryFrom<String> for SongHash {
+	type Error = InvalidSongHash;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		Self::new(&value)
+	}
+}
+
+impl From<SongHash> for String {
+	fn from(value: SongHash) -> Self {
+		value.0
+	}
+}
+
+impl AsRef<str> for SongHash {
+	fn as_ref(&self) -> &str {
+		&self.0
+	}
+}
+
+impl Display for SongHash {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+
+impl FromStr for SongHash {
+	type Err = InvalidSongHash;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::new(s)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::SongHash;
+
+	#[test]
+	fn test_valid_hash() {
+		let hash: SongHash = " 6e9498f81bbf26fa47b1691557b22486a720254d\n".parse().unwrap();
+		assert_eq!(hash.as_str(), "6E9498F81BBF26FA47B1691557B22486A720254D");
+		assert_eq!(hash, SongHash::new("6E9498F81BBF26FA47B1691557B22486A720254D").unwrap());
+	}
+
+	#[test]
+	fn test_invalid_hash() {
+		assert!(SongHash::new("6E9498F81BBF26FA47B1691557B22486A720254").is_err());
+		assert!(SongHash::new("6E9498F81BBF26FA47B1691557B22486A720254DA").is_err());
+		assert!(SongHash::new("XE9498F81BBF26FA47B1691557B22486A720254D").is_err());
+		assert!(SongHash::new("").is_err());
+	}
+}
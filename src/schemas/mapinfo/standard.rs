@@ -1,21 +1,39 @@
 use std::{
 	convert::Infallible,
 	fmt::Display,
+	hash::{Hash, Hasher},
 	io,
 	path::{Path, PathBuf},
 	str::FromStr
 };
 
 use sha1_smol::Sha1;
+use simd_json::{
+	derived::{ValueObjectAccess, ValueObjectAccessAsScalar},
+	OwnedValue
+};
 use thiserror::Error;
 
 use super::v2;
+#[cfg(feature = "watch")]
+use crate::util::watch::WatchHandle;
 use crate::{
-	schemas::beatmap::{self, AnyverBeatmap, AnyverParseError},
-	util::fs::{FileSystem, NativeFileSystem}
+	schemas::{
+		beatmap::{
+			self,
+			standard::{GridSpec, Issue, NegativeTimes},
+			AnyverBeatmap,
+			AnyverParseError
+		},
+		hash::SongHash
+	},
+	util::{
+		fs::{BoxedError, FileError, FileSystem, NativeFileSystem},
+		jump::{self, JumpValues}
+	}
 };
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Eq)]
 pub enum BeatmapCharacteristic {
 	Standard,
 	NoArrows,
@@ -26,20 +44,47 @@ pub enum BeatmapCharacteristic {
 	Other(String)
 }
 
-impl Display for BeatmapCharacteristic {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl BeatmapCharacteristic {
+	pub fn as_str(&self) -> &str {
 		match self {
-			Self::Standard => f.write_str("Standard"),
-			Self::NoArrows => f.write_str("NoArrows"),
-			Self::OneSaber => f.write_str("OneSaber"),
-			Self::Degree360 => f.write_str("360Degree"),
-			Self::Degree90 => f.write_str("90Degree"),
-			Self::Legacy => f.write_str("Legacy"),
-			Self::Other(s) => f.write_str(&s)
+			Self::Standard => "Standard",
+			Self::NoArrows => "NoArrows",
+			Self::OneSaber => "OneSaber",
+			Self::Degree360 => "360Degree",
+			Self::Degree90 => "90Degree",
+			Self::Legacy => "Legacy",
+			Self::Other(s) => s
+		}
+	}
+
+	/// The name of a characteristic which isn't one of the known variants.
+	pub fn other_str(&self) -> Option<&str> {
+		match self {
+			Self::Other(s) => Some(s),
+			_ => None
 		}
 	}
 }
 
+/// Characteristics are compared by name, so `Other("Standard")` is equal to `Standard`.
+impl PartialEq for BeatmapCharacteristic {
+	fn eq(&self, other: &Self) -> bool {
+		self.as_str() == other.as_str()
+	}
+}
+
+impl Hash for BeatmapCharacteristic {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.as_str().hash(state);
+	}
+}
+
+impl Display for BeatmapCharacteristic {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
 impl FromStr for BeatmapCharacteristic {
 	type Err = Infallible;
 
@@ -56,6 +101,158 @@ impl FromStr for BeatmapCharacteristic {
 	}
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Environment {
+	Default,
+	Triangle,
+	Nice,
+	BigMirror,
+	Kda,
+	Monstercat,
+	CrabRave,
+	Dragons,
+	Origins,
+	Panic,
+	Rocket,
+	GreenDay,
+	GreenDayGrenade,
+	Timbaland,
+	FitBeat,
+	LinkinPark,
+	Bts,
+	Kaleidoscope,
+	Interscope,
+	Skrillex,
+	Billie,
+	Halloween,
+	Gaga,
+	GlassDesert,
+	Weave,
+	Pyro,
+	Edm,
+	TheSecond,
+	Lizzo,
+	TheWeeknd,
+	RockMixtape,
+	Dragons2,
+	Panic2,
+	Queen,
+	LinkinPark2,
+	TheRollingStones,
+	Lattice,
+	DaftPunk,
+	HipHop,
+	Collider,
+	Britney,
+	Monstercat2,
+	Metallica,
+	Custom(String)
+}
+
+impl Display for Environment {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Default => f.write_str("DefaultEnvironment"),
+			Self::Triangle => f.write_str("TriangleEnvironment"),
+			Self::Nice => f.write_str("NiceEnvironment"),
+			Self::BigMirror => f.write_str("BigMirrorEnvironment"),
+			Self::Kda => f.write_str("KDAEnvironment"),
+			Self::Monstercat => f.write_str("MonstercatEnvironment"),
+			Self::CrabRave => f.write_str("CrabRaveEnvironment"),
+			Self::Dragons => f.write_str("DragonsEnvironment"),
+			Self::Origins => f.write_str("OriginsEnvironment"),
+			Self::Panic => f.write_str("PanicEnvironment"),
+			Self::Rocket => f.write_str("RocketEnvironment"),
+			Self::GreenDay => f.write_str("GreenDayEnvironment"),
+			Self::GreenDayGrenade => f.write_str("GreenDayGrenadeEnvironment"),
+			Self::Timbaland => f.write_str("TimbalandEnvironment"),
+			Self::FitBeat => f.write_str("FitBeatEnvironment"),
+			Self::LinkinPark => f.write_str("LinkinParkEnvironment"),
+			Self::Bts => f.write_str("BTSEnvironment"),
+			Self::Kaleidoscope => f.write_str("KaleidoscopeEnvironment"),
+			Self::Interscope => f.write_str("InterscopeEnvironment"),
+			Self::Skrillex => f.write_str("SkrillexEnvironment"),
+			Self::Billie => f.write_str("BillieEnvironment"),
+			Self::Halloween => f.write_str("HalloweenEnvironment"),
+			Self::Gaga => f.write_str("GagaEnvironment"),
+			Self::GlassDesert => f.write_str("GlassDesertEnvironment"),
+			Self::Weave => f.write_str("WeaveEnvironment"),
+			Self::Pyro => f.write_str("PyroEnvironment"),
+			Self::Edm => f.write_str("EDMEnvironment"),
+			Self::TheSecond => f.write_str("TheSecondEnvironment"),
+			Self::Lizzo => f.write_str("LizzoEnvironment"),
+			Self::TheWeeknd => f.write_str("TheWeekndEnvironment"),
+			Self::RockMixtape => f.write_str("RockMixtapeEnvironment"),
+			Self::Dragons2 => f.write_str("Dragons2Environment"),
+			Self::Panic2 => f.write_str("Panic2Environment"),
+			Self::Queen => f.write_str("QueenEnvironment"),
+			Self::LinkinPark2 => f.write_str("LinkinPark2Environment"),
+			Self::TheRollingStones => f.write_str("TheRollingStonesEnvironment"),
+			Self::Lattice => f.write_str("LatticeEnvironment"),
+			Self::DaftPunk => f.write_str("DaftPunkEnvironment"),
+			Self::HipHop => f.write_str("HipHopEnvironment"),
+			Self::Collider => f.write_str("ColliderEnvironment"),
+			Self::Britney => f.write_str("BritneyEnvironment"),
+			Self::Monstercat2 => f.write_str("Monstercat2Environment"),
+			Self::Metallica => f.write_str("MetallicaEnvironment"),
+			Self::Custom(s) => f.write_str(s)
+		}
+	}
+}
+
+impl FromStr for Environment {
+	type Err = Infallible;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s {
+			"DefaultEnvironment" => Self::Default,
+			"TriangleEnvironment" => Self::Triangle,
+			"NiceEnvironment" => Self::Nice,
+			"BigMirrorEnvironment" => Self::BigMirror,
+			"KDAEnvironment" => Self::Kda,
+			"MonstercatEnvironment" => Self::Monstercat,
+			"CrabRaveEnvironment" => Self::CrabRave,
+			"DragonsEnvironment" => Self::Dragons,
+			"OriginsEnvironment" => Self::Origins,
+			"PanicEnvironment" => Self::Panic,
+			"RocketEnvironment" => Self::Rocket,
+			"GreenDayEnvironment" => Self::GreenDay,
+			"GreenDayGrenadeEnvironment" => Self::GreenDayGrenade,
+			"TimbalandEnvironment" => Self::Timbaland,
+			"FitBeatEnvironment" => Self::FitBeat,
+			"LinkinParkEnvironment" => Self::LinkinPark,
+			"BTSEnvironment" => Self::Bts,
+			"KaleidoscopeEnvironment" => Self::Kaleidoscope,
+			"InterscopeEnvironment" => Self::Interscope,
+			"SkrillexEnvironment" => Self::Skrillex,
+			"BillieEnvironment" => Self::Billie,
+			"HalloweenEnvironment" => Self::Halloween,
+			"GagaEnvironment" => Self::Gaga,
+			"GlassDesertEnvironment" => Self::GlassDesert,
+			"WeaveEnvironment" => Self::Weave,
+			"PyroEnvironment" => Self::Pyro,
+			"EDMEnvironment" => Self::Edm,
+			"TheSecondEnvironment" => Self::TheSecond,
+			"LizzoEnvironment" => Self::Lizzo,
+			"TheWeekndEnvironment" => Self::TheWeeknd,
+			"RockMixtapeEnvironment" => Self::RockMixtape,
+			"Dragons2Environment" => Self::Dragons2,
+			"Panic2Environment" => Self::Panic2,
+			"QueenEnvironment" => Self::Queen,
+			"LinkinPark2Environment" => Self::LinkinPark2,
+			"TheRollingStonesEnvironment" => Self::TheRollingStones,
+			"LatticeEnvironment" => Self::Lattice,
+			"DaftPunkEnvironment" => Self::DaftPunk,
+			"HipHopEnvironment" => Self::HipHop,
+			"ColliderEnvironment" => Self::Collider,
+			"BritneyEnvironment" => Self::Britney,
+			"Monstercat2Environment" => Self::Monstercat2,
+			"MetallicaEnvironment" => Self::Metallica,
+			s => Self::Custom(s.to_string())
+		})
+	}
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(u8)]
 pub enum Difficulty {
@@ -122,7 +319,83 @@ pub struct SongMeta {
 pub struct AudioMeta {
 	pub bpm: f32,
 	pub song_time_offset: f32,
-	pub audio_path: PathBuf
+	pub audio_path: PathBuf,
+	pub preview: PreviewClip
+}
+
+/// The section of the song played as a preview in the song browser, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PreviewClip {
+	pub start: f32,
+	pub duration: f32
+}
+
+/// Custom colors for a map, as RGBA in the range `0..=1`. Colors which aren't overridden by the map are `None`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColorScheme {
+	pub note_left: Option<[f32; 4]>,
+	pub note_right: Option<[f32; 4]>,
+	pub obstacle: Option<[f32; 4]>,
+	pub saber_left: Option<[f32; 4]>,
+	pub saber_right: Option<[f32; 4]>
+}
+
+impl ColorScheme {
+	/// Parses the SongCore color overrides (`_colorLeft`, `_colorRight`, `_obstacleColor`) from a v2 `_customData`
+	/// object. The game tints sabers with the note colors, so `saber_left`/`saber_right` follow
+	/// `note_left`/`note_right`.
+	///
+	/// Returns `None` if the custom data does not override any colors.
+	pub fn from_v2_custom_data(custom_data: &OwnedValue) -> Option<Self> {
+		fn parse_color(value: &OwnedValue, key: &str) -> Option<[f32; 4]> {
+			let color = value.get(key)?;
+			Some([color.get_f32("r")?, color.get_f32("g")?, color.get_f32("b")?, color.get_f32("a").unwrap_or(1.)])
+		}
+
+		let note_left = parse_color(custom_data, "_colorLeft");
+		let note_right = parse_color(custom_data, "_colorRight");
+		let obstacle = parse_color(custom_data, "_obstacleColor");
+		if note_left.is_none() && note_right.is_none() && obstacle.is_none() {
+			return None;
+		}
+		Some(Self {
+			note_left,
+			note_right,
+			obstacle,
+			saber_left: note_left,
+			saber_right: note_right
+		})
+	}
+}
+
+/// Metadata from a BeatSaver sidecar file (`BeatSaver.json`) saved alongside a downloaded map, in the format of the
+/// BeatSaver API's map details.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeatSaverMeta {
+	/// The map's key on BeatSaver, e.g. `389bc`.
+	pub id: String,
+	pub name: Option<String>,
+	/// The name of the uploader's account.
+	pub uploader: Option<String>,
+	pub upvotes: Option<u32>,
+	pub downvotes: Option<u32>
+}
+
+impl BeatSaverMeta {
+	/// The filename of the sidecar file, matched case-insensitively.
+	pub const FILENAME: &'static str = "BeatSaver.json";
+
+	/// Parses the sidecar file. Returns `None` if it isn't valid JSON or has no map key.
+	pub fn from_bytes(bytes: &mut [u8]) -> Option<Self> {
+		let value = simd_json::to_owned_value(bytes).ok()?;
+		Some(Self {
+			id: value.get_str("id")?.to_owned(),
+			name: value.get_str("name").map(str::to_owned),
+			uploader: value.get("uploader").and_then(|x| x.get_str("name")).map(str::to_owned),
+			upvotes: value.get("stats").and_then(|x| x.get_u32("upvotes")),
+			downvotes: value.get("stats").and_then(|x| x.get_u32("downvotes"))
+		})
+	}
 }
 
 #[derive(Debug, Clone)]
@@ -134,34 +407,141 @@ pub struct Beatmap {
 	pub njs_offset: f32
 }
 
+impl Beatmap {
+	/// Computes the jump values for this difficulty, given the song's BPM.
+	pub fn jump_values(&self, bpm: f32) -> JumpValues {
+		jump::jump_values(bpm, self.njs, self.njs_offset)
+	}
+
+	/// Flips the map left-to-right, keeping colors for characteristics which only use one saber. All first-party
+	/// characteristics use the standard grid.
+	pub fn mirror(&mut self) {
+		self.map.mirror_with(GridSpec::STANDARD, &self.characteristic);
+	}
+
+	/// Checks the map for common mistakes, including ones specific to its characteristic, e.g. arrows in a NoArrows
+	/// map.
+	pub fn validate(&self) -> Vec<Issue> {
+		self.map.validate_for(GridSpec::STANDARD, &self.characteristic)
+	}
+}
+
 #[derive(Debug, Error)]
 pub enum MapReadError {
 	#[error("Failed to parse map info: {0}")]
 	InfoParseError(#[from] simd_json::Error),
 	#[error("Failed to parse beatmap: {0}")]
 	MapParseError(#[from] AnyverParseError),
+	#[error("Failed to parse beatmap '{filename}': {source}")]
+	BadBeatmapFile { filename: String, source: AnyverParseError },
 	#[error("Failed to read file: {0}")]
 	IoError(#[from] io::Error),
+	#[error("Failed to read file '{}': {source}", .path.display())]
+	IoErrorWithPath { source: io::Error, path: PathBuf },
 	#[cfg(feature = "zip")]
 	#[error("Failed to read from ZIP file: {0}")]
 	ZipError(#[from] zip::result::ZipError),
+	#[error("Failed to read file: {0}")]
+	FileSystemError(#[from] BoxedError),
 	#[error("Missing `Info.dat`")]
 	MissingInfoDat,
+	#[error("Map wasn't loaded from a directory")]
+	NoSourceDir,
 	#[error("Unexepcted beatmap difficulty '{0}'")]
 	BadDifficulty(String)
 }
 
+impl From<FileError> for MapReadError {
+	fn from(FileError { source, path }: FileError) -> Self {
+		Self::IoErrorWithPath { source, path }
+	}
+}
+
+/// Options for reading a map with [`MapInfo::from_fs_with`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MapReadOptions {
+	/// Shift every beatmap by the song's `song_time_offset`, so that object times line up with the audio file.
+	/// Objects which would end up before the start of the song are clamped to it.
+	pub apply_song_time_offset: bool
+}
+
+/// The metadata of a map read from its `Info.dat` alone, without parsing any beatmaps.
+#[derive(Debug, Clone)]
+pub struct ShallowMapInfo {
+	/// The SHA-1 of `Info.dat`. This isn't the map's [`SongHash`], which also covers the beatmap files.
+	pub hash_partial: String,
+	pub song: SongMeta,
+	pub audio: AudioMeta,
+	/// Each difficulty of the map, with the filename of its beatmap.
+	pub difficulty_list: Vec<(BeatmapCharacteristic, Difficulty, String)>
+}
+
 #[derive(Debug)]
 pub struct MapInfo {
-	pub hash: String,
+	pub hash: SongHash,
 	pub song: SongMeta,
 	pub audio: AudioMeta,
-	pub maps: Vec<Beatmap>
+	pub environment: Environment,
+	pub color_scheme: Option<ColorScheme>,
+	pub maps: Vec<Beatmap>,
+	/// The BeatSaver metadata saved alongside the map, if there's a readable [`BeatSaverMeta::FILENAME`].
+	pub beatsaver: Option<BeatSaverMeta>,
+	/// The directory the map was loaded from, if it was loaded from a directory.
+	pub source_dir: Option<PathBuf>
 }
 
 impl MapInfo {
 	pub fn from_dir<P: AsRef<Path>>(path: P) -> Result<Self, MapReadError> {
-		Self::from_fs(NativeFileSystem::new(path.as_ref()))
+		let mut map = Self::from_fs(NativeFileSystem::new(path.as_ref()))?;
+		map.source_dir = Some(path.as_ref().to_path_buf());
+		Ok(map)
+	}
+
+	/// Reads only the `Info.dat` of the map directory at `path`, which is much faster than [`Self::from_dir`] when the
+	/// beatmaps aren't needed.
+	pub fn from_dir_shallow<P: AsRef<Path>>(path: P) -> Result<ShallowMapInfo, MapReadError> {
+		Self::from_fs_shallow(NativeFileSystem::new(path.as_ref()))
+	}
+
+	/// Reads only the `Info.dat` of a map from any file system, see [`Self::from_dir_shallow`].
+	pub fn from_fs_shallow<F: FileSystem>(mut fs: F) -> Result<ShallowMapInfo, MapReadError>
+	where
+		MapReadError: From<F::Err>
+	{
+		let info_bytes = fs.read_bytes(&Self::find_info(&fs.list()?)?)?;
+		let info = v2::MapInfo::from_reader(&*info_bytes)?;
+		let mut difficulty_list = Vec::new();
+		for set in &info.beatmap_sets {
+			let characteristic = BeatmapCharacteristic::from_str(&set.characteristic).unwrap();
+			for map in &set.beatmaps {
+				difficulty_list.push((characteristic.clone(), Self::difficulty(map)?, map.filename.clone()));
+			}
+		}
+		let (song, audio) = Self::metadata(&info);
+		Ok(ShallowMapInfo {
+			hash_partial: Sha1::from(&info_bytes).digest().to_string(),
+			song,
+			audio,
+			difficulty_list
+		})
+	}
+
+	/// Watches the map directory at `path`, calling `handler` with the result of reloading the map whenever a file in
+	/// it changes. A change can leave the map briefly unreadable (e.g. a half-written file), in which case `handler`
+	/// gets the error & then the reloaded map once the write finishes. Watching stops when the returned handle is
+	/// dropped.
+	#[cfg(feature = "watch")]
+	pub fn watch<P: AsRef<Path>>(path: P, handler: impl Fn(Result<MapInfo, MapReadError>) + Send + 'static) -> Result<WatchHandle, MapReadError> {
+		Ok(WatchHandle::spawn(path.as_ref().to_path_buf(), move |path| handler(Self::from_dir(path)))?)
+	}
+
+	/// Re-reads the beatmaps from [`Self::source_dir`], updating `maps` & `hash`.
+	pub fn reload_beatmaps(&mut self) -> Result<(), MapReadError> {
+		let source_dir = self.source_dir.as_ref().ok_or(MapReadError::NoSourceDir)?;
+		let reloaded = Self::from_dir(source_dir)?;
+		self.hash = reloaded.hash;
+		self.maps = reloaded.maps;
+		Ok(())
 	}
 
 	#[cfg(feature = "zip")]
@@ -170,30 +550,108 @@ impl MapInfo {
 		Self::from_fs(ZipFileSystem::new(reader)?)
 	}
 
-	fn from_fs<F: FileSystem>(mut fs: F) -> Result<Self, MapReadError>
+	#[cfg(feature = "tokio")]
+	pub async fn from_dir_async<P: AsRef<Path>>(path: P) -> Result<Self, MapReadError> {
+		let root = path.as_ref();
+
+		let mut files = Vec::new();
+		let mut entries = tokio::fs::read_dir(root).await?;
+		while let Some(entry) = entries.next_entry().await? {
+			files.push(PathBuf::from(entry.file_name()));
+		}
+
+		let read = |path: PathBuf| async move {
+			tokio::fs::read(&path)
+				.await
+				.map_err(|source| MapReadError::IoErrorWithPath { source, path })
+		};
+		let info_bytes = read(root.join(Self::find_info(&files)?)).await?;
+		let info = v2::MapInfo::from_reader(&*info_bytes)?;
+		let mut beatmaps = Vec::new();
+		for filename in Self::beatmap_filenames(&info) {
+			beatmaps.push(read(root.join(filename)).await?);
+		}
+		let beatsaver = match Self::find_file(&files, BeatSaverMeta::FILENAME) {
+			Some(path) => BeatSaverMeta::from_bytes(&mut read(root.join(path)).await?),
+			None => None
+		};
+		let mut map = Self::from_parts(&info_bytes, info, beatmaps, MapReadOptions::default())?;
+		map.beatsaver = beatsaver;
+		map.source_dir = Some(root.to_path_buf());
+		Ok(map)
+	}
+
+	/// Loads a map from any file system, e.g. one chosen at runtime as a
+	/// [`BoxedFileSystem`](crate::util::fs::BoxedFileSystem).
+	pub fn from_fs<F: FileSystem>(fs: F) -> Result<Self, MapReadError>
 	where
 		MapReadError: From<F::Err>
 	{
-		let mut hasher = Sha1::new();
+		Self::from_fs_with(fs, MapReadOptions::default())
+	}
 
-		let info = fs.read_bytes(
-			&fs.list()?
-				.into_iter()
-				.find(|c| c.to_string_lossy().eq_ignore_ascii_case("info.dat"))
-				.ok_or(MapReadError::MissingInfoDat)?
-		)?;
-		hasher.update(&info);
-		let info = v2::MapInfo::from_reader(&*info)?;
+	pub fn from_fs_with<F: FileSystem>(mut fs: F, options: MapReadOptions) -> Result<Self, MapReadError>
+	where
+		MapReadError: From<F::Err>
+	{
+		let files = fs.list()?;
+		let info_bytes = fs.read_bytes(&Self::find_info(&files)?)?;
+		let info = v2::MapInfo::from_reader(&*info_bytes)?;
+		let beatmaps = Self::beatmap_filenames(&info)
+			.map(|filename| fs.read_bytes(&PathBuf::from(filename)))
+			.collect::<Result<Vec<_>, _>>()?;
+		let beatsaver = match Self::find_file(&files, BeatSaverMeta::FILENAME) {
+			Some(path) => BeatSaverMeta::from_bytes(&mut fs.read_bytes(&path)?),
+			None => None
+		};
+		let mut map = Self::from_parts(&info_bytes, info, beatmaps, options)?;
+		map.beatsaver = beatsaver;
+		Ok(map)
+	}
 
+	fn find_info(files: &[PathBuf]) -> Result<PathBuf, MapReadError> {
+		Self::find_file(files, "info.dat").ok_or(MapReadError::MissingInfoDat)
+	}
+
+	fn find_file(files: &[PathBuf], name: &str) -> Option<PathBuf> {
+		files.iter().find(|c| c.to_string_lossy().eq_ignore_ascii_case(name)).cloned()
+	}
+
+	fn beatmap_filenames(info: &v2::MapInfo) -> impl Iterator<Item = &str> {
+		info.beatmap_sets
+			.iter()
+			.flat_map(|set| set.beatmaps.iter().map(|map| map.filename.as_str()))
+	}
+
+	/// Builds the map from the raw `Info.dat` and the raw beatmaps, which must be in the order given by
+	/// [`Self::beatmap_filenames`].
+	fn from_parts(info_bytes: &[u8], info: v2::MapInfo, beatmaps: Vec<Vec<u8>>, options: MapReadOptions) -> Result<Self, MapReadError> {
+		let mut hasher = Sha1::new();
+		hasher.update(info_bytes);
+		let color_scheme = info.color_scheme();
+		let (song, audio) = Self::metadata(&info);
+
+		let mut beatmaps = beatmaps.into_iter();
 		let mut maps = Vec::new();
 		for set in info.beatmap_sets {
 			let characteristic = BeatmapCharacteristic::from_str(&set.characteristic).unwrap();
 			for map in set.beatmaps {
-				let beatmap = fs.read_bytes(&PathBuf::from(map.filename))?;
+				let mut beatmap = beatmaps.next().unwrap();
 				hasher.update(&beatmap);
-				let beatmap = beatmap::standard::Beatmap::from_any(AnyverBeatmap::from_reader(&*beatmap)?, info.bpm);
+				let mut beatmap = AnyverBeatmap::from_bytes(&mut beatmap)
+					.and_then(|beatmap| Ok(beatmap::standard::Beatmap::from_any(beatmap, info.bpm)?))
+					.map_err(|source| MapReadError::BadBeatmapFile {
+						filename: map.filename.clone(),
+						source
+					})?;
+				beatmap.mapping_extensions |= map.requirements().any(|x| x == "Mapping Extensions");
+				if options.apply_song_time_offset {
+					beatmap
+						.shift_time(info.song_time_offset, NegativeTimes::Clamp)
+						.expect("clamped shifts can't fail");
+				}
 				maps.push(Beatmap {
-					difficulty: Difficulty::from_str(&map.difficulty).map_err(MapReadError::BadDifficulty)?,
+					difficulty: Self::difficulty(&map)?,
 					characteristic: characteristic.clone(),
 					map: beatmap,
 					njs: map.njs,
@@ -202,26 +660,54 @@ impl MapInfo {
 			}
 		}
 		Ok(Self {
-			hash: hasher.digest().to_string().to_uppercase(),
-			audio: AudioMeta {
-				bpm: info.bpm,
-				audio_path: info.song_filename.into(),
-				song_time_offset: info.song_time_offset
-			},
-			song: SongMeta {
-				title: info.song_name,
-				subtitle: (!info.song_sub_name.is_empty()).then_some(info.song_sub_name),
-				author: info.song_author_name,
-				cover_image_path: info.cover_image_filename.into()
-			},
-			maps
+			hash: hasher.digest().into(),
+			audio,
+			song,
+			environment: Environment::from_str(&info.environment_name).unwrap(),
+			color_scheme,
+			maps,
+			beatsaver: None,
+			source_dir: None
 		})
 	}
+
+	fn metadata(info: &v2::MapInfo) -> (SongMeta, AudioMeta) {
+		let song = SongMeta {
+			title: info.song_name.clone(),
+			subtitle: (!info.song_sub_name.is_empty()).then(|| info.song_sub_name.clone()),
+			author: info.song_author_name.clone(),
+			cover_image_path: info.cover_image_filename.clone().into()
+		};
+		let audio = AudioMeta {
+			bpm: info.bpm,
+			audio_path: info.song_filename.clone().into(),
+			song_time_offset: info.song_time_offset,
+			preview: PreviewClip {
+				start: info.preview_start_time,
+				duration: info.preview_duration
+			}
+		};
+		(song, audio)
+	}
+
+	fn difficulty(map: &v2::Beatmap) -> Result<Difficulty, MapReadError> {
+		// custom labels are allowed, but the rank is always one of the standard difficulties
+		Difficulty::from_str(&map.difficulty)
+			.ok()
+			.or_else(|| Difficulty::from_rank(map.difficulty_rank.clone() as u8))
+			.ok_or_else(|| MapReadError::BadDifficulty(map.difficulty.clone()))
+	}
 }
 
 #[cfg(test)]
 mod tests {
-	use super::MapInfo;
+	use std::{collections::HashSet, str::FromStr};
+
+	use super::{v2, Beatmap, BeatmapCharacteristic, ColorScheme, Difficulty, Environment, MapInfo, MapReadError, MapReadOptions, PreviewClip};
+	use crate::schemas::beatmap::{
+		self,
+		standard::{IssueKind, NoteColor}
+	};
 
 	#[test]
 	#[cfg(feature = "zip")]
@@ -230,5 +716,397 @@ mod tests {
 
 		let map_info = MapInfo::from_zip(BufReader::new(File::open("tests/data/maps/389bc (x=10 - Alpha Cancri).zip").unwrap())).unwrap();
 		assert_eq!(map_info.song.title, "x=1/0");
+		assert_eq!(map_info.audio.preview, PreviewClip { start: 74., duration: 10. });
+		assert_eq!(map_info.environment, Environment::Default);
+		assert_eq!(map_info.color_scheme, None);
+	}
+
+	#[test]
+	#[cfg(feature = "zip")]
+	fn load_shallow() {
+		use std::{fs::File, io::BufReader};
+
+		use super::Difficulty;
+		use crate::util::fs::ZipFileSystem;
+
+		let zip = ZipFileSystem::new(BufReader::new(File::open("tests/data/maps/389bc (x=10 - Alpha Cancri).zip").unwrap())).unwrap();
+		let map_info = MapInfo::from_fs_shallow(zip).unwrap();
+		assert_eq!(map_info.song.title, "x=1/0");
+		assert_eq!(map_info.audio.preview, PreviewClip { start: 74., duration: 10. });
+		assert_eq!(map_info.hash_partial.len(), 40);
+		let (characteristic, difficulty, filename) = &map_info.difficulty_list[0];
+		assert_eq!((characteristic, difficulty), (&BeatmapCharacteristic::Standard, &Difficulty::ExpertPlus));
+		assert!(filename.ends_with(".dat"));
+	}
+
+	#[test]
+	#[cfg(feature = "zip")]
+	fn reload_beatmaps() {
+		use std::{fs::File, io::BufReader};
+
+		let mut map_info = MapInfo::from_zip(BufReader::new(File::open("tests/data/maps/389bc (x=10 - Alpha Cancri).zip").unwrap())).unwrap();
+		assert!(matches!(map_info.reload_beatmaps(), Err(MapReadError::NoSourceDir)));
+
+		let dir = std::env::temp_dir().join("sabers-reload-beatmaps");
+		let _ = std::fs::remove_dir_all(&dir);
+		zip::ZipArchive::new(BufReader::new(File::open("tests/data/maps/389bc (x=10 - Alpha Cancri).zip").unwrap()))
+			.unwrap()
+			.extract(&dir)
+			.unwrap();
+
+		let mut map_info = MapInfo::from_dir(&dir).unwrap();
+		assert_eq!(map_info.source_dir.as_deref(), Some(dir.as_path()));
+		let beats = map_info.maps[0].map.beats.len();
+		map_info.maps.clear();
+		map_info.reload_beatmaps().unwrap();
+		std::fs::remove_dir_all(&dir).unwrap();
+		assert_eq!(map_info.maps[0].map.beats.len(), beats);
+	}
+
+	#[test]
+	#[cfg(feature = "zip")]
+	fn load_dir_missing_difficulty() {
+		use std::{fs::File, io::BufReader};
+
+		let dir = std::env::temp_dir().join("sabers-missing-difficulty");
+		let _ = std::fs::remove_dir_all(&dir);
+		zip::ZipArchive::new(BufReader::new(File::open("tests/data/maps/389bc (x=10 - Alpha Cancri).zip").unwrap()))
+			.unwrap()
+			.extract(&dir)
+			.unwrap();
+		std::fs::remove_file(dir.join("ExpertPlusStandard.dat")).unwrap();
+
+		let err = MapInfo::from_dir(&dir).unwrap_err();
+		std::fs::remove_dir_all(&dir).unwrap();
+		assert!(matches!(&err, MapReadError::IoErrorWithPath { path, .. } if *path == dir.join("ExpertPlusStandard.dat")));
+	}
+
+	#[test]
+	#[cfg(all(feature = "zip", feature = "watch"))]
+	fn watch() {
+		use std::{fs::File, io::BufReader, sync::mpsc, time::Duration};
+
+		let dir = std::env::temp_dir().join("sabers-watch");
+		let _ = std::fs::remove_dir_all(&dir);
+		zip::ZipArchive::new(BufReader::new(File::open("tests/data/maps/389bc (x=10 - Alpha Cancri).zip").unwrap()))
+			.unwrap()
+			.extract(&dir)
+			.unwrap();
+
+		let (tx, rx) = mpsc::channel();
+		let handle = MapInfo::watch(&dir, move |map| tx.send(map.map(|x| x.song.title)).unwrap()).unwrap();
+		assert!(rx.recv_timeout(Duration::from_millis(600)).is_err());
+
+		std::fs::write(dir.join("notes.txt"), "hello").unwrap();
+		assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap(), "x=1/0");
+
+		// reload errors are passed to the handler rather than dropped
+		std::fs::write(dir.join("Info.dat"), "{").unwrap();
+		assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap().is_err());
+
+		drop(handle);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	#[cfg(all(feature = "zip", feature = "watch"))]
+	fn watch_drop_in_handler() {
+		use std::{
+			fs::File,
+			io::BufReader,
+			sync::{mpsc, Arc, Mutex},
+			time::Duration
+		};
+
+		let dir = std::env::temp_dir().join("sabers-watch-drop");
+		let _ = std::fs::remove_dir_all(&dir);
+		zip::ZipArchive::new(BufReader::new(File::open("tests/data/maps/389bc (x=10 - Alpha Cancri).zip").unwrap()))
+			.unwrap()
+			.extract(&dir)
+			.unwrap();
+
+		let (tx, rx) = mpsc::channel();
+		let slot: Arc<Mutex<Option<crate::util::watch::WatchHandle>>> = Arc::default();
+		let handle = MapInfo::watch(&dir, {
+			let slot = Arc::clone(&slot);
+			move |_| {
+				// dropping the handle on the watcher thread mustn't wait for that same thread
+				drop(slot.lock().unwrap().take());
+				tx.send(()).unwrap();
+			}
+		})
+		.unwrap();
+		*slot.lock().unwrap() = Some(handle);
+
+		std::fs::write(dir.join("notes.txt"), "hello").unwrap();
+		rx.recv_timeout(Duration::from_secs(5)).unwrap();
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	#[cfg(all(feature = "zip", feature = "tokio"))]
+	fn load_dir_async() {
+		use std::{fs::File, io::BufReader};
+
+		let dir = std::env::temp_dir().join("sabers-load-dir-async");
+		let _ = std::fs::remove_dir_all(&dir);
+		zip::ZipArchive::new(BufReader::new(File::open("tests/data/maps/389bc (x=10 - Alpha Cancri).zip").unwrap()))
+			.unwrap()
+			.extract(&dir)
+			.unwrap();
+
+		let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+		let map_info = runtime.block_on(MapInfo::from_dir_async(&dir)).unwrap();
+		let expected = MapInfo::from_dir(&dir).unwrap();
+		std::fs::remove_dir_all(&dir).unwrap();
+
+		assert_eq!(map_info.hash, expected.hash);
+		assert_eq!(map_info.song.title, "x=1/0");
+		assert_eq!(map_info.maps.len(), expected.maps.len());
+	}
+
+	#[test]
+	#[cfg(feature = "zip")]
+	fn bad_beatmap_file() {
+		use std::{fs::File, io::BufReader, path::Path};
+
+		use crate::util::fs::{FileSystem, MemoryFileSystem, ZipFileSystem};
+
+		let mut zip = ZipFileSystem::new(BufReader::new(File::open("tests/data/maps/389bc (x=10 - Alpha Cancri).zip").unwrap())).unwrap();
+		let info = zip.read_bytes(Path::new("Info.dat")).unwrap();
+		let filename = super::v2::MapInfo::from_reader(&*info).unwrap().beatmap_sets[0].beatmaps[0]
+			.filename
+			.clone();
+
+		let mut fs = MemoryFileSystem::new();
+		fs.insert("Info.dat", info);
+		fs.insert(&filename, "{ \"_version\": 2 }");
+		match MapInfo::from_fs(fs) {
+			Err(MapReadError::BadBeatmapFile { filename: bad, .. }) => assert_eq!(bad, filename),
+			res => panic!("expected BadBeatmapFile, got {res:?}")
+		}
+	}
+
+	#[test]
+	#[cfg(feature = "zip")]
+	fn custom_difficulty_label() {
+		use std::{fs::File, io::BufReader, path::Path};
+
+		use super::Difficulty;
+		use crate::util::fs::{FileSystem, MemoryFileSystem, ZipFileSystem};
+
+		let mut zip = ZipFileSystem::new(BufReader::new(File::open("tests/data/maps/389bc (x=10 - Alpha Cancri).zip").unwrap())).unwrap();
+		let mut fs = MemoryFileSystem::new();
+		for path in zip.list().unwrap() {
+			let mut bytes = zip.read_bytes(&path).unwrap();
+			if path == Path::new("Info.dat") {
+				bytes = String::from_utf8(bytes)
+					.unwrap()
+					.replace(r#""_difficulty": "ExpertPlus""#, r#""_difficulty": "Nightmare""#)
+					.into_bytes();
+			}
+			fs.insert(path, bytes);
+		}
+
+		let map_info = MapInfo::from_fs(fs).unwrap();
+		assert_eq!(map_info.maps[0].difficulty, Difficulty::ExpertPlus);
+	}
+
+	#[test]
+	#[cfg(feature = "zip")]
+	fn parse_info_from_str() {
+		use std::{fs::File, io::BufReader, path::Path};
+
+		use super::v2;
+		use crate::util::fs::{FileSystem, ZipFileSystem};
+
+		let mut zip = ZipFileSystem::new(BufReader::new(File::open("tests/data/maps/389bc (x=10 - Alpha Cancri).zip").unwrap())).unwrap();
+		let info = String::from_utf8(zip.read_bytes(Path::new("Info.dat")).unwrap()).unwrap();
+		let info: v2::MapInfo = info.parse().unwrap();
+		assert_eq!(info.beatmap_sets[0].beatmaps[0].difficulty, "ExpertPlus");
+	}
+
+	#[test]
+	#[cfg(feature = "zip")]
+	fn apply_song_time_offset() {
+		use std::{fs::File, io::BufReader, path::Path};
+
+		use super::MapReadOptions;
+		use crate::util::fs::{FileSystem, MemoryFileSystem, ZipFileSystem};
+
+		let mut zip = ZipFileSystem::new(BufReader::new(File::open("tests/data/maps/389bc (x=10 - Alpha Cancri).zip").unwrap())).unwrap();
+		let mut fs = MemoryFileSystem::new();
+		for path in zip.list().unwrap() {
+			let mut bytes = zip.read_bytes(&path).unwrap();
+			if path == Path::new("Info.dat") {
+				bytes = String::from_utf8(bytes)
+					.unwrap()
+					.replace(r#""_songTimeOffset": 0.0"#, r#""_songTimeOffset": 0.25"#)
+					.into_bytes();
+			}
+			fs.insert(path, bytes);
+		}
+
+		let unshifted = MapInfo::from_fs(fs.clone()).unwrap();
+		let options = MapReadOptions { apply_song_time_offset: true };
+		let shifted = MapInfo::from_fs_with(fs, options).unwrap();
+		assert_eq!(shifted.audio.song_time_offset, 0.25);
+		let (a, b) = (&unshifted.maps[0].map, &shifted.maps[0].map);
+		assert_eq!(b.beats[0].time, a.beats[0].time + 0.25);
+		assert_eq!(b.end_time(), a.end_time() + 0.25);
+	}
+
+	#[test]
+	#[cfg(feature = "zip")]
+	fn beatsaver_sidecar() {
+		use std::{fs::File, io::BufReader, path::PathBuf};
+
+		use super::BeatSaverMeta;
+		use crate::util::fs::{FileSystem, MemoryFileSystem, ZipFileSystem};
+
+		let mut zip = ZipFileSystem::new(BufReader::new(File::open("tests/data/maps/389bc (x=10 - Alpha Cancri).zip").unwrap())).unwrap();
+		let mut fs = MemoryFileSystem::new();
+		for path in zip.list().unwrap() {
+			let bytes = zip.read_bytes(&path).unwrap();
+			fs.insert(path, bytes);
+		}
+		assert_eq!(MapInfo::from_fs(fs.clone()).unwrap().beatsaver, None);
+
+		let sidecar = r#"{ "id": "389bc", "name": "x=1/0", "uploader": { "id": 1, "name": "someone" }, "stats": { "upvotes": 12, "downvotes": 3 } }"#;
+		fs.insert(PathBuf::from("beatsaver.json"), sidecar.as_bytes().to_vec());
+		let beatsaver = MapInfo::from_fs(fs).unwrap().beatsaver.unwrap();
+		assert_eq!(
+			beatsaver,
+			BeatSaverMeta {
+				id: String::from("389bc"),
+				name: Some(String::from("x=1/0")),
+				uploader: Some(String::from("someone")),
+				upvotes: Some(12),
+				downvotes: Some(3)
+			}
+		);
+	}
+
+	#[test]
+	fn test_characteristic_str() {
+		for name in ["Standard", "360Degree", "Lawless"] {
+			let characteristic = BeatmapCharacteristic::from_str(name).unwrap();
+			assert_eq!(characteristic.as_str(), name);
+			assert_eq!(characteristic.to_string(), name);
+		}
+		assert_eq!(BeatmapCharacteristic::Other(String::from("Lawless")).other_str(), Some("Lawless"));
+		assert_eq!(BeatmapCharacteristic::Degree90.other_str(), None);
+	}
+
+	#[test]
+	fn test_characteristic_eq() {
+		assert_eq!(BeatmapCharacteristic::Other("Standard".to_string()), BeatmapCharacteristic::Standard);
+		assert_ne!(BeatmapCharacteristic::Other("Lawless".to_string()), BeatmapCharacteristic::Standard);
+
+		let set: HashSet<_> = [BeatmapCharacteristic::Standard, BeatmapCharacteristic::Other("Standard".to_string())].into();
+		assert_eq!(set.len(), 1);
+	}
+
+	fn difficulty(characteristic: BeatmapCharacteristic) -> Beatmap {
+		let map = r#"{
+			"_version": "2.2.0",
+			"_notes": [
+				{ "_time": 1, "_lineIndex": 0, "_lineLayer": 0, "_type": 0, "_cutDirection": 1 },
+				{ "_time": 2, "_lineIndex": 3, "_lineLayer": 0, "_type": 1, "_cutDirection": 8 }
+			],
+			"_obstacles": [],
+			"_events": []
+		}"#;
+		Beatmap {
+			difficulty: Difficulty::Expert,
+			characteristic,
+			map: beatmap::standard::Beatmap::from_v2(beatmap::v2::Beatmap::from_string(map).unwrap(), 60.).unwrap(),
+			njs: 16.,
+			njs_offset: 0.
+		}
+	}
+
+	#[test]
+	fn test_mirror_characteristic() {
+		let mut map = difficulty(BeatmapCharacteristic::OneSaber);
+		map.mirror();
+		assert_eq!(map.map.beats.iter().map(|x| (x.x, x.color)).collect::<Vec<_>>(), [(3., NoteColor::Red), (0., NoteColor::Blue)]);
+
+		let mut map = difficulty(BeatmapCharacteristic::Standard);
+		map.mirror();
+		assert_eq!(map.map.beats.iter().map(|x| (x.x, x.color)).collect::<Vec<_>>(), [(3., NoteColor::Blue), (0., NoteColor::Red)]);
+	}
+
+	#[test]
+	fn test_validate_characteristic() {
+		let issues = difficulty(BeatmapCharacteristic::NoArrows).validate();
+		assert_eq!(issues.iter().map(|x| (x.time, x.kind)).collect::<Vec<_>>(), [(1., IssueKind::DirectionalNote)]);
+		assert!(difficulty(BeatmapCharacteristic::Standard).validate().is_empty());
+
+		// the characteristic checks still apply to mapping extensions maps
+		let mut map = difficulty(BeatmapCharacteristic::NoArrows);
+		map.map.mapping_extensions = true;
+		map.map.beats[1].x = 4.;
+		assert_eq!(map.validate().iter().map(|x| x.kind).collect::<Vec<_>>(), [IssueKind::DirectionalNote]);
+	}
+
+	#[test]
+	fn test_mapping_extensions_requirement() {
+		let info = |requirements: &str| {
+			format!(
+				r#"{{
+					"_version": "2.0.0",
+					"_songName": "", "_songSubName": "", "_songAuthorName": "", "_levelAuthorName": "",
+					"_beatsPerMinute": 60, "_shuffle": 0, "_shufflePeriod": 0.5, "_previewStartTime": 0, "_previewDuration": 10,
+					"_songFilename": "song.egg", "_coverImageFilename": "cover.jpg", "_environmentName": "DefaultEnvironment",
+					"_songTimeOffset": 0,
+					"_difficultyBeatmapSets": [{{
+						"_beatmapCharacteristicName": "Standard",
+						"_difficultyBeatmaps": [{{
+							"_difficulty": "Expert", "_difficultyRank": 7, "_beatmapFilename": "Expert.dat",
+							"_noteJumpMovementSpeed": 16, "_noteJumpStartBeatOffset": 0,
+							"_customData": {{ "_requirements": [{requirements}] }}
+						}}]
+					}}]
+				}}"#
+			)
+		};
+		// a whole-lane precise note (5000 is lane 4) & a plain extra lane
+		let beatmap = r#"{
+			"_version": "2.2.0",
+			"_notes": [
+				{ "_time": 1, "_lineIndex": 5000, "_lineLayer": 0, "_type": 0, "_cutDirection": 1 },
+				{ "_time": 2, "_lineIndex": -1, "_lineLayer": 1, "_type": 1, "_cutDirection": 1 }
+			],
+			"_obstacles": [],
+			"_events": []
+		}"#;
+		let load = |info: String| {
+			let parsed = v2::MapInfo::from_string(info.clone()).unwrap();
+			MapInfo::from_parts(info.as_bytes(), parsed, vec![beatmap.as_bytes().to_vec()], MapReadOptions::default()).unwrap()
+		};
+
+		let map = load(info(r#""Mapping Extensions""#));
+		assert!(map.maps[0].map.mapping_extensions);
+		assert!(map.maps[0].map.validate().is_empty());
+
+		let map = load(info(r#""Chroma""#));
+		assert!(!map.maps[0].map.mapping_extensions);
+		assert_eq!(map.maps[0].map.validate().len(), 2);
+	}
+
+	#[test]
+	fn test_color_scheme_from_custom_data() {
+		let custom_data = simd_json::json!({
+			"_colorLeft": { "r": 1.0, "g": 0.0, "b": 0.5 },
+			"_obstacleColor": { "r": 0.0, "g": 1.0, "b": 0.0, "a": 0.5 }
+		});
+		let color_scheme = ColorScheme::from_v2_custom_data(&custom_data).unwrap();
+		assert_eq!(color_scheme.note_left, Some([1.0, 0.0, 0.5, 1.0]));
+		assert_eq!(color_scheme.saber_left, color_scheme.note_left);
+		assert_eq!(color_scheme.note_right, None);
+		assert_eq!(color_scheme.obstacle, Some([0.0, 1.0, 0.0, 0.5]));
+
+		assert_eq!(ColorScheme::from_v2_custom_data(&simd_json::json!({ "_editors": {} })), None);
 	}
 }
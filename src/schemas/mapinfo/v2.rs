@@ -1,13 +1,16 @@
 use std::{
 	fs::File,
 	io::{BufReader, BufWriter, Read, Write},
-	path::Path
+	path::Path,
+	str::FromStr
 };
 
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+use super::standard::ColorScheme;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct MapInfo {
 	#[serde(rename = "_version")]
 	pub version: String,
@@ -38,7 +41,9 @@ pub struct MapInfo {
 	#[serde(rename = "_songTimeOffset")]
 	pub song_time_offset: f32,
 	#[serde(rename = "_difficultyBeatmapSets")]
-	pub beatmap_sets: Vec<BeatmapSet>
+	pub beatmap_sets: Vec<BeatmapSet>,
+	#[serde(rename = "_customData")]
+	pub custom_data: Option<simd_json::OwnedValue>
 }
 
 impl MapInfo {
@@ -73,9 +78,22 @@ impl MapInfo {
 	pub fn from_file<P: AsRef<Path>>(path: P) -> simd_json::Result<Self> {
 		Self::from_reader(BufReader::new(File::open(path)?))
 	}
+
+	/// Returns the color overrides stored in `_customData`, if any.
+	pub fn color_scheme(&self) -> Option<ColorScheme> {
+		self.custom_data.as_ref().and_then(ColorScheme::from_v2_custom_data)
+	}
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+impl FromStr for MapInfo {
+	type Err = simd_json::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::from_string(s)
+	}
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct BeatmapSet {
 	#[serde(rename = "_beatmapCharacteristicName")]
 	pub characteristic: String,
@@ -83,7 +101,7 @@ pub struct BeatmapSet {
 	pub beatmaps: Vec<Beatmap>
 }
 
-#[derive(Deserialize_repr, Serialize_repr, Debug, Clone)]
+#[derive(Deserialize_repr, Serialize_repr, Debug, Clone, PartialEq, Eq)]
 #[repr(i32)]
 pub enum DifficultyRank {
 	Unknown = 0,
@@ -94,7 +112,7 @@ pub enum DifficultyRank {
 	ExpertPlus = 9
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct Beatmap {
 	#[serde(rename = "_difficulty")]
 	pub difficulty: String,
@@ -105,5 +123,22 @@ pub struct Beatmap {
 	#[serde(rename = "_noteJumpMovementSpeed")]
 	pub njs: f32,
 	#[serde(rename = "_noteJumpStartBeatOffset")]
-	pub njs_offset: f32
+	pub njs_offset: f32,
+	#[serde(rename = "_customData", skip_serializing_if = "Option::is_none")]
+	pub custom_data: Option<simd_json::OwnedValue>
+}
+
+impl Beatmap {
+	/// Returns the mods listed in `_customData._requirements`, such as `Mapping Extensions`.
+	pub fn requirements(&self) -> impl Iterator<Item = &str> {
+		use simd_json::prelude::{ValueAsContainer, ValueAsScalar, ValueObjectAccess};
+
+		self.custom_data
+			.as_ref()
+			.and_then(|x| x.get("_requirements"))
+			.and_then(|x| x.as_array())
+			.into_iter()
+			.flatten()
+			.filter_map(|x| x.as_str())
+	}
 }
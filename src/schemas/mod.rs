@@ -1,3 +1,7 @@
 pub mod beatmap;
 pub mod bsor;
+pub mod hash;
 pub mod mapinfo;
+
+#[cfg(feature = "proptest")]
+mod arbitrary;
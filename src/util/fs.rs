@@ -1,12 +1,14 @@
 #[cfg(feature = "zip")]
 use std::io::{Read, Seek};
 use std::{
+	collections::HashMap,
 	error::Error,
 	fs::{self},
 	io::{self},
 	path::{Path, PathBuf}
 };
 
+use thiserror::Error;
 #[cfg(feature = "zip")]
 use zip::{result::ZipError, ZipArchive};
 
@@ -17,6 +19,54 @@ pub trait FileSystem {
 	fn read_bytes(&mut self, path: &Path) -> Result<Vec<u8>, Self::Err>;
 }
 
+/// The error type of a [`FileSystem`] whose error type has been erased by [`DynFileSystem`].
+#[derive(Debug, Error)]
+#[error(transparent)]
+pub struct BoxedError(pub Box<dyn Error + Send + Sync>);
+
+/// A file system backend chosen at runtime.
+pub type BoxedFileSystem = Box<dyn FileSystem<Err = BoxedError> + Send>;
+
+impl<T: FileSystem + ?Sized> FileSystem for Box<T> {
+	type Err = T::Err;
+
+	fn list(&self) -> Result<Vec<PathBuf>, Self::Err> {
+		(**self).list()
+	}
+
+	fn read_bytes(&mut self, path: &Path) -> Result<Vec<u8>, Self::Err> {
+		(**self).read_bytes(path)
+	}
+}
+
+/// Erases a file system's error type, so that different backends can be stored as the same [`BoxedFileSystem`].
+pub struct DynFileSystem<F>(pub F);
+
+impl<F> DynFileSystem<F>
+where
+	F: FileSystem + Send + 'static,
+	F::Err: Send + Sync + 'static
+{
+	pub fn boxed(fs: F) -> BoxedFileSystem {
+		Box::new(Self(fs))
+	}
+}
+
+impl<F: FileSystem> FileSystem for DynFileSystem<F>
+where
+	F::Err: Send + Sync + 'static
+{
+	type Err = BoxedError;
+
+	fn list(&self) -> Result<Vec<PathBuf>, Self::Err> {
+		self.0.list().map_err(|e| BoxedError(Box::new(e)))
+	}
+
+	fn read_bytes(&mut self, path: &Path) -> Result<Vec<u8>, Self::Err> {
+		self.0.read_bytes(path).map_err(|e| BoxedError(Box::new(e)))
+	}
+}
+
 pub struct NativeFileSystem {
 	root: PathBuf
 }
@@ -27,15 +77,57 @@ impl NativeFileSystem {
 	}
 }
 
+/// An I/O error from a [`NativeFileSystem`], with the path of the file or directory being read.
+#[derive(Debug, Error)]
+#[error("Failed to read '{}': {source}", .path.display())]
+pub struct FileError {
+	pub source: io::Error,
+	pub path: PathBuf
+}
+
 impl FileSystem for NativeFileSystem {
+	type Err = FileError;
+
+	fn list(&self) -> Result<Vec<PathBuf>, Self::Err> {
+		fs::read_dir(&self.root)
+			.and_then(|c| c.map(|e| e.map(|e| PathBuf::from(e.file_name()))).collect())
+			.map_err(|source| FileError { source, path: self.root.clone() })
+	}
+
+	fn read_bytes(&mut self, path: &Path) -> Result<Vec<u8>, Self::Err> {
+		let path = self.root.join(path);
+		fs::read(&path).map_err(|source| FileError { source, path })
+	}
+}
+
+/// A file system held entirely in memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFileSystem {
+	files: HashMap<PathBuf, Vec<u8>>
+}
+
+impl MemoryFileSystem {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn insert(&mut self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) {
+		self.files.insert(path.into(), contents.into());
+	}
+}
+
+impl FileSystem for MemoryFileSystem {
 	type Err = io::Error;
 
 	fn list(&self) -> Result<Vec<PathBuf>, Self::Err> {
-		fs::read_dir(&self.root).and_then(|c| c.map(|e| e.map(|e| PathBuf::from(e.file_name()))).collect())
+		Ok(self.files.keys().cloned().collect())
 	}
 
 	fn read_bytes(&mut self, path: &Path) -> Result<Vec<u8>, Self::Err> {
-		fs::read(self.root.join(path))
+		self.files
+			.get(path)
+			.cloned()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.display().to_string()))
 	}
 }
 
@@ -67,3 +159,39 @@ impl<R: Read + Seek> FileSystem for ZipFileSystem<R> {
 		})
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use std::path::Path;
+
+	use super::{BoxedFileSystem, DynFileSystem, FileSystem, MemoryFileSystem, NativeFileSystem};
+
+	fn assert_send_sync<T: Send + Sync>() {}
+
+	#[test]
+	fn test_send_sync() {
+		assert_send_sync::<NativeFileSystem>();
+		assert_send_sync::<MemoryFileSystem>();
+		#[cfg(feature = "zip")]
+		assert_send_sync::<super::ZipFileSystem<std::fs::File>>();
+	}
+
+	#[test]
+	fn test_boxed_file_systems() {
+		let mut memory = MemoryFileSystem::new();
+		memory.insert("Info.dat", "{}");
+
+		let mut file_systems: Vec<BoxedFileSystem> = vec![DynFileSystem::boxed(NativeFileSystem::new("tests/data/maps")), DynFileSystem::boxed(memory)];
+		assert!(
+			file_systems[0]
+				.list()
+				.unwrap()
+				.iter()
+				.any(|p| p == Path::new("1579c_ExpertPlusStandard.dat"))
+		);
+		assert_eq!(file_systems[1].read_bytes(Path::new("Info.dat")).unwrap(), b"{}");
+		for fs in &mut file_systems {
+			assert!(fs.read_bytes(Path::new("missing.dat")).is_err());
+		}
+	}
+}
@@ -0,0 +1,52 @@
+/// The jump values derived from a beatmap's note jump speed & offset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JumpValues {
+	/// The number of beats a note takes to travel from spawning to the player.
+	pub half_jump_duration: f32,
+	/// The distance in meters between where notes spawn & where they leave, behind the player.
+	pub jump_distance: f32,
+	/// The time in seconds between a note spawning & reaching the player.
+	pub reaction_time: f32
+}
+
+/// The jump distance above which the game halves the half jump duration.
+const MAX_HALF_JUMP_DISTANCE: f32 = 17.999;
+const MIN_HALF_JUMP_DURATION: f32 = 0.25;
+
+/// Computes the jump values the game uses for the given BPM, note jump speed & note jump offset.
+pub fn jump_values(bpm: f32, njs: f32, offset: f32) -> JumpValues {
+	let seconds_per_beat = 60. / bpm;
+	let mut half_jump_duration = 4.;
+	while njs * seconds_per_beat * half_jump_duration > MAX_HALF_JUMP_DISTANCE {
+		half_jump_duration /= 2.;
+	}
+	half_jump_duration = (half_jump_duration + offset).max(MIN_HALF_JUMP_DURATION);
+
+	let reaction_time = seconds_per_beat * half_jump_duration;
+	JumpValues {
+		half_jump_duration,
+		jump_distance: njs * reaction_time * 2.,
+		reaction_time
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{jump_values, JumpValues};
+
+	#[test]
+	fn test_jump_values() {
+		assert_eq!(
+			jump_values(200., 16., 0.),
+			JumpValues {
+				half_jump_duration: 2.,
+				jump_distance: 19.2,
+				reaction_time: 0.6
+			}
+		);
+		// 18 NJS at 60 BPM halves twice to get under the limit
+		assert_eq!(jump_values(60., 18., 0.).half_jump_duration, 0.5);
+		assert_eq!(jump_values(120., 10., 0.5).half_jump_duration, 2.5);
+		assert_eq!(jump_values(120., 10., -5.).half_jump_duration, 0.25);
+	}
+}
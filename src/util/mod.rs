@@ -1 +1,4 @@
 pub mod fs;
+pub mod jump;
+#[cfg(feature = "watch")]
+pub mod watch;
@@ -0,0 +1,64 @@
+use std::{
+	io,
+	path::{Path, PathBuf},
+	sync::mpsc::{self, RecvTimeoutError},
+	thread::{self, JoinHandle},
+	time::Duration
+};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+/// How long the directory has to be quiet after a change before `on_change` is called, so that a burst of events
+/// from e.g. an editor saving several files only causes one reload.
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// Stops watching a directory when dropped.
+pub struct WatchHandle {
+	watcher: Option<RecommendedWatcher>,
+	thread: Option<JoinHandle<()>>
+}
+
+impl WatchHandle {
+	/// Watches `path` for file system notifications, calling `on_change` on a background thread whenever a file in it
+	/// is added, removed, or modified.
+	pub(crate) fn spawn(path: PathBuf, mut on_change: impl FnMut(&Path) + Send + 'static) -> io::Result<Self> {
+		let (tx, rx) = mpsc::channel::<notify::Result<notify::Event>>();
+		let mut watcher = notify::recommended_watcher(tx).map_err(io::Error::other)?;
+		watcher.watch(&path, RecursiveMode::NonRecursive).map_err(io::Error::other)?;
+		let thread = thread::spawn(move || {
+			// the channel disconnects once the watcher is dropped
+			while let Ok(event) = rx.recv() {
+				// reading the map back doesn't change anything; errors are passed along as a change, so that the
+				// reload reports them
+				if event.is_ok_and(|x| x.kind.is_access()) {
+					continue;
+				}
+				loop {
+					match rx.recv_timeout(SETTLE_TIME) {
+						Ok(_) => continue,
+						Err(RecvTimeoutError::Timeout) => break,
+						Err(RecvTimeoutError::Disconnected) => return
+					}
+				}
+				on_change(&path);
+			}
+		});
+		Ok(Self {
+			watcher: Some(watcher),
+			thread: Some(thread)
+		})
+	}
+}
+
+impl Drop for WatchHandle {
+	/// Stops the watcher & waits for a running `on_change` to return, unless the handle is dropped from within
+	/// `on_change` itself.
+	fn drop(&mut self) {
+		drop(self.watcher.take());
+		if let Some(thread) = self.thread.take() {
+			if thread.thread().id() != thread::current().id() {
+				let _ = thread.join();
+			}
+		}
+	}
+}